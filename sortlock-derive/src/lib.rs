@@ -0,0 +1,138 @@
+//! Derive macros for `sortlock`.
+//!
+//! These macros are re-exported by `sortlock` when its `derive` feature is enabled and should be
+//! used through that crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident};
+
+/// The largest tuple that implements `LockGroup`.
+const MAX_TUPLE: usize = 16;
+
+/// Derives `LockGroup` for a struct whose fields are all lock requests.
+///
+/// A struct named `<Name>Locked` is generated alongside the struct. It has the same fields as the
+/// original struct but each field contains the locked form of the request. All fields are locked
+/// together in sorted order.
+/// ```ignore
+/// use sortlock::{LockGroup, SortMutex, SortMutexGuard, SortReadGuard, SortRwLock};
+///
+/// #[derive(LockGroup)]
+/// struct Requests<'l> {
+///     count: SortMutexGuard<'l, u32>,
+///     name: SortReadGuard<'l, String>,
+/// }
+///
+/// let count = SortMutex::new(0);
+/// let name = SortRwLock::new("name".to_string());
+///
+/// let mut locked = Requests { count: count.lock(), name: name.read() }.lock_all();
+/// *locked.count += 1;
+/// ```
+#[proc_macro_derive(LockGroup)]
+pub fn derive_lock_group(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_lock_group(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Expands the `LockGroup` derive for a struct.
+///
+/// - `input` - The struct to derive for.
+fn expand_lock_group(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "`LockGroup` can only be derived for structs"));
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let locked = format_ident!("{}Locked", name);
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let where_predicates = where_clause.map(|clause| &clause.predicates);
+
+    let types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+    let bindings: Vec<_> = (0..types.len())
+        .map(|i| Ident::new(&format!("__field{i}"), Span::call_site()))
+        .collect();
+    let nested = nest(bindings.iter().map(|binding| quote!(#binding)).collect());
+
+    let doc = format!("The locked form of `{name}`.");
+
+    let (definition, destructure, construct) = match &data.fields {
+        Fields::Named(fields) => {
+            let names: Vec<_> = fields.named.iter().map(|field| &field.ident).collect();
+            let vises = fields.named.iter().map(|field| &field.vis);
+
+            (
+                quote! {
+                    #[doc = #doc]
+                    #vis struct #locked #impl_generics where #(#types: ::sortlock::LockGroup,)* #where_predicates {
+                        #(#vises #names: <#types as ::sortlock::LockGroup>::Locked,)*
+                    }
+                },
+                quote!(Self { #(#names: #bindings),* }),
+                quote!(#locked { #(#names: #bindings),* }),
+            )
+        },
+        Fields::Unnamed(fields) => {
+            let vises = fields.unnamed.iter().map(|field| &field.vis);
+
+            (
+                quote! {
+                    #[doc = #doc]
+                    #vis struct #locked #impl_generics (
+                        #(#vises <#types as ::sortlock::LockGroup>::Locked,)*
+                    ) where #(#types: ::sortlock::LockGroup,)* #where_predicates;
+                },
+                quote!(Self(#(#bindings),*)),
+                quote!(#locked(#(#bindings),*)),
+            )
+        },
+        Fields::Unit => (
+            quote! {
+                #[doc = #doc]
+                #vis struct #locked;
+            },
+            quote!(Self),
+            quote!(#locked),
+        ),
+    };
+
+    let lock = if bindings.is_empty() {
+        quote!()
+    } else {
+        quote!(let #nested = ::sortlock::LockGroup::lock_all(#nested);)
+    };
+
+    Ok(quote! {
+        #definition
+
+        impl #impl_generics ::sortlock::LockGroup for #name #type_generics where #(#types: ::sortlock::NestedLockGroup,)* #where_predicates {
+            type Locked = #locked #type_generics;
+
+            fn lock_all(self) -> Self::Locked {
+                let #destructure = self;
+                #lock
+
+                #construct
+            }
+        }
+    })
+}
+
+/// Nests items into tuples small enough to implement `LockGroup`.
+///
+/// As nested groups are flattened when locked this does not change the locking order.
+///
+/// - `items` - The items to nest.
+fn nest(items: Vec<TokenStream2>) -> TokenStream2 {
+    match items.len() {
+        1 => items.into_iter().next().unwrap(),
+        len if len <= MAX_TUPLE => quote!((#(#items,)*)),
+        _ => nest(items.chunks(MAX_TUPLE).map(|chunk| nest(chunk.to_vec())).collect()),
+    }
+}
@@ -0,0 +1,148 @@
+//! The counters `sortlock` creates sort keys from.
+//!
+//! A program can end up with several versions of `sortlock` linked in, for example when two of
+//! its dependencies require different versions. If each copy counted keys itself, locks from
+//! different copies could be given the same key and would no longer be locked in a consistent
+//! order. Every copy depends on this crate instead, and as Cargo only links a single copy of
+//! semver-compatible versions of a crate, the copies all share these counters, the layout of the
+//! key bands and, with the `alloc` feature, the record of free and manual keys.
+//!
+//! This crate should be used through `sortlock`. To keep the counters shared it must never make
+//! a breaking release.
+
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use core::panic::Location;
+
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// The integer used to count the keys created in a band.
+#[cfg(not(feature = "narrow"))]
+pub type Count = u64;
+
+/// The integer used to count the keys created in a band.
+///
+/// The `narrow` feature counts with a `u32`, for small targets where 64-bit atomics are emulated
+/// with locks. Features are shared by every copy of `sortlock` in the program, so either every
+/// copy counts with a `u32` or none do. Bands larger than `u32::MAX` keys are cut short at the end
+/// of the count, so the start of every band stays the same.
+#[cfg(feature = "narrow")]
+pub type Count = u32;
+
+/// An atomic `Count`.
+#[cfg(not(feature = "narrow"))]
+pub type AtomicCount = portable_atomic::AtomicU64;
+
+/// An atomic `Count`.
+#[cfg(feature = "narrow")]
+pub type AtomicCount = portable_atomic::AtomicU32;
+
+/// The first key in the early band.
+pub const EARLY_START: u64 = 0;
+/// The number of keys in the early band.
+pub const EARLY_LEN: u64 = 1 << 57;
+/// The bit set on every key derived from the address of a lock.
+pub const ADDRESS_BIT: u64 = 1 << 57;
+/// The first key in the normal band.
+pub const NORMAL_START: u64 = 1 << 58;
+/// The number of keys in the normal band.
+pub const NORMAL_LEN: u64 = 1 << 58;
+/// The first key in the late band.
+pub const LATE_START: u64 = 1 << 59;
+/// The number of keys in the late band.
+pub const LATE_LEN: u64 = 1 << 59;
+/// The bit set on every key derived from the name of a lock.
+pub const NAME_BIT: u64 = 1 << 60;
+/// The bit set on every manual key.
+pub const MANUAL_BIT: u64 = 1 << 61;
+/// The bit set on every key of a lock in a hierarchy.
+pub const LEVEL_BIT: u64 = 1 << 62;
+/// The number of keys in each level of the hierarchy.
+pub const LEVEL_LEN: u64 = 1 << 46;
+/// The bit set on every key identifying a file.
+pub const FILE_BIT: u64 = 1 << 63;
+
+/// The number of keys created in the early band.
+pub static EARLY_KEYS: AtomicCount = AtomicCount::new(0);
+
+/// The number of keys created in the normal band, which contains the keys of most locks.
+pub static NORMAL_KEYS: AtomicCount = AtomicCount::new(0);
+
+/// The number of keys created in the late band.
+pub static LATE_KEYS: AtomicCount = AtomicCount::new(0);
+
+/// The number of keys created for locks in a hierarchy, across every level.
+pub static LEVEL_KEYS: AtomicCount = AtomicCount::new(0);
+
+/// The keys of dropped locks that are known to be unused and can be given to new locks, by the
+/// first key in their band.
+#[cfg(feature = "alloc")]
+static FREE_KEYS: spin::Mutex<BTreeMap<u64, Vec<u64>>> = spin::Mutex::new(BTreeMap::new());
+
+/// The values of the manual keys that are in use and where each was created.
+#[cfg(feature = "alloc")]
+static MANUAL_KEYS: spin::Mutex<BTreeMap<u64, &'static Location<'static>>> = spin::Mutex::new(BTreeMap::new());
+
+/// Gets the first key in the band containing a key.
+///
+/// Each level of the hierarchy is a band of its own.
+///
+/// - `key` - The value of the key.
+pub const fn band_start(key: u64) -> u64 {
+    if key < ADDRESS_BIT {
+        EARLY_START
+    } else if key >= LEVEL_BIT && key < FILE_BIT {
+        key & !(LEVEL_LEN - 1)
+    } else {
+        // Every other band starts at the highest bit of its keys.
+        1 << (u64::BITS - 1 - key.leading_zeros())
+    }
+}
+
+/// Records that a key is no longer used by any lock so it can be given to a new lock in the same
+/// band.
+///
+/// - `key` - The value of the key.
+#[cfg(feature = "alloc")]
+pub fn free_key(key: u64) {
+    FREE_KEYS.lock().entry(band_start(key)).or_default().push(key);
+}
+
+/// Takes a key from a band that is no longer used by any lock, if there is one.
+///
+/// - `band` - The first key in the band, as returned by `band_start`.
+#[cfg(feature = "alloc")]
+pub fn take_free_key(band: u64) -> Option<u64> {
+    FREE_KEYS.lock().get_mut(&band)?.pop()
+}
+
+/// Records that a manual key has been created, returning where it was first created if a manual
+/// key with the same value already exists.
+///
+/// - `value` - The value of the manual key.
+/// - `location` - Where the key was created.
+#[cfg(feature = "alloc")]
+pub fn record_manual_key(value: u64, location: &'static Location<'static>) -> Option<&'static Location<'static>> {
+    let mut keys = MANUAL_KEYS.lock();
+
+    match keys.get(&value) {
+        Some(created) => Some(*created),
+        None => {
+            keys.insert(value, location);
+            None
+        }
+    }
+}
+
+/// Records that a manual key is no longer used by any lock so the value can be used again.
+///
+/// - `value` - The value of the manual key.
+#[cfg(feature = "alloc")]
+pub fn release_manual_key(value: u64) {
+    MANUAL_KEYS.lock().remove(&value);
+}
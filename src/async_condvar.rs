@@ -0,0 +1,190 @@
+use core::fmt::{self, Debug, Formatter};
+
+#[cfg(feature = "tokio")]
+use core::pin::pin;
+
+#[cfg(feature = "tokio")]
+use tokio::sync::Notify;
+#[cfg(not(feature = "tokio"))]
+use event_listener::Event;
+
+use crate::{AsyncSortableLock, SortAsyncMutexLocked};
+
+/// A condition variable that can be used with a `SortAsyncMutex`.
+///
+/// Waiting consumes an acquired guard and returns it once the lock has been reacquired. The lock
+/// is reacquired on its own without sorting.
+/// ```
+/// use std::sync::Arc;
+/// use sortlock::{SortAsyncCondvar, SortAsyncMutex, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let pair = Arc::new((SortAsyncMutex::new(false), SortAsyncCondvar::new()));
+/// let pair2 = pair.clone();
+///
+/// tokio::spawn(async move {
+///     let (lock, condvar) = &*pair2;
+///
+///     *lock.lock().lock_all().await = true;
+///     condvar.notify_one();
+/// });
+///
+/// let (lock, condvar) = &*pair;
+///
+/// let guard = condvar.wait_while(lock.lock().lock_all().await, |ready| !*ready).await;
+/// assert!(*guard);
+/// # });
+/// ```
+///
+/// Only the lock of the given guard is released while waiting. Any other locks held by the
+/// task, such as the rest of a group, remain locked.
+pub struct SortAsyncCondvar {
+    /// The internal notification.
+    #[cfg(feature = "tokio")]
+    notify: Notify,
+    /// The internal event.
+    #[cfg(not(feature = "tokio"))]
+    event: Event,
+}
+
+impl SortAsyncCondvar {
+    /// Creates a new `SortAsyncCondvar`.
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "tokio")]
+            notify: Notify::const_new(),
+            #[cfg(not(feature = "tokio"))]
+            event: Event::new(),
+        }
+    }
+
+    /// Waits until this condition variable is notified.
+    ///
+    /// The lock is released while waiting and reacquired before returning. Spurious wakeups are
+    /// possible so this should usually be called in a loop or replaced with `wait_while`.
+    ///
+    /// - `guard` - The guard to release while waiting.
+    ///
+    /// # Deadlocks
+    /// The lock is reacquired on its own without sorting, so waiting while the task holds other
+    /// locks, such as the rest of a group, can deadlock with a task locking them together in
+    /// sorted order. The same applies to every other wait method.
+    pub async fn wait<'l, T: ?Sized>(&self, guard: SortAsyncMutexLocked<'l, T>) -> SortAsyncMutexLocked<'l, T> {
+        let lock = SortAsyncMutexLocked::lock(&guard);
+
+        let mut guard = Some(guard);
+
+        // The guard is released by the first check once interest has been registered, and any
+        // later check only runs after a wakeup.
+        self.wait_until(|| guard.take().is_none()).await;
+
+        lock.lock().lock_presorted().await
+    }
+
+    /// Waits until a check returns `true`.
+    ///
+    /// The check is run after interest in a notification is registered, so a notification sent
+    /// after the check is run cannot be missed. It is run again after every wakeup.
+    ///
+    /// - `ready` - The check to wait for.
+    pub(crate) async fn wait_until<F: FnMut() -> bool>(&self, mut ready: F) {
+        loop {
+            #[cfg(feature = "tokio")]
+            {
+                let mut notified = pin!(self.notify.notified());
+                notified.as_mut().enable();
+
+                if ready() {
+                    return;
+                }
+
+                notified.await;
+            }
+            #[cfg(not(feature = "tokio"))]
+            {
+                let listener = self.event.listen();
+
+                if ready() {
+                    return;
+                }
+
+                listener.await;
+            }
+        }
+    }
+
+    /// Waits until this condition variable is notified and the condition returns `false`.
+    ///
+    /// The condition is checked before waiting and after every wakeup while the lock is held.
+    ///
+    /// - `guard` - The guard to release while waiting.
+    /// - `condition` - The condition to wait on.
+    pub async fn wait_while<'l, T: ?Sized, F: FnMut(&mut T) -> bool>(&self, mut guard: SortAsyncMutexLocked<'l, T>, mut condition: F) -> SortAsyncMutexLocked<'l, T> {
+        while condition(&mut *guard) {
+            guard = self.wait(guard).await;
+        }
+
+        guard
+    }
+
+    /// Wakes up one task waiting on this condition variable.
+    pub fn notify_one(&self) {
+        #[cfg(feature = "tokio")]
+        self.notify.notify_one();
+        #[cfg(not(feature = "tokio"))]
+        self.event.notify(1);
+    }
+
+    /// Wakes up all tasks waiting on this condition variable.
+    pub fn notify_all(&self) {
+        #[cfg(feature = "tokio")]
+        self.notify.notify_waiters();
+        #[cfg(not(feature = "tokio"))]
+        self.event.notify(usize::MAX);
+    }
+}
+
+impl Debug for SortAsyncCondvar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortAsyncCondvar").finish_non_exhaustive()
+    }
+}
+
+impl Default for SortAsyncCondvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{AsyncLockGroup, SortAsyncCondvar, SortAsyncMutex};
+
+    #[tokio::test]
+    async fn test_async_condvar_group() {
+        let state = Arc::new((SortAsyncMutex::new(0), SortAsyncMutex::new(0), SortAsyncCondvar::new()));
+        let state2 = state.clone();
+
+        let handle = tokio::spawn(async move {
+            let (counter, other, condvar) = &*state2;
+
+            for _ in 0..10 {
+                let (mut counter, mut other) = (counter.lock(), other.lock()).lock_all().await;
+                *counter += 1;
+                *other += 1;
+                condvar.notify_all();
+            }
+        });
+
+        let (counter, other, condvar) = &*state;
+
+        let guard = condvar.wait_while(counter.lock().lock_all().await, |counter| *counter < 10).await;
+        assert_eq!(10, *guard);
+        drop(guard);
+
+        handle.await.unwrap();
+        assert_eq!(10, *other.lock().lock_all().await);
+    }
+}
@@ -0,0 +1,443 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, ops::{Deref, DerefMut}};
+
+#[cfg(feature = "alloc")]
+use core::future::IntoFuture;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+use spin::Mutex;
+
+use crate::{AsyncSortableLock, LockAccess, SortAsyncCondvar, SortKey, async_held::HeldKey, key::LazySortKey};
+
+#[cfg(feature = "std")]
+use crate::AsyncLockGroup;
+#[cfg(feature = "alloc")]
+use crate::LockFuture;
+
+/// A sortable lock that ensures exclusive access to a resource from async tasks in first-in
+/// first-out order.
+///
+/// When the lock is released it is handed directly to the task that has waited the longest, so a
+/// task that repeatedly relocks the lock cannot starve the others. This has lower throughput than
+/// `SortAsyncMutex` under contention.
+/// ```
+/// use sortlock::{SortAsyncFairMutex, SortAsyncMutex, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let lock1 = SortAsyncFairMutex::new(1);
+/// let lock2 = SortAsyncMutex::new(2);
+///
+/// let (mut guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all().await;
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// # });
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct SortAsyncFairMutex<T: ?Sized> {
+    /// The state of the queue of waiting tasks.
+    state: Mutex<FairState>,
+    /// Notified when the lock is handed to a waiting task.
+    handed: SortAsyncCondvar,
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for SortAsyncFairMutex<T> {}
+unsafe impl <T: ?Sized + Send> Sync for SortAsyncFairMutex<T> {}
+
+/// The state of a `SortAsyncFairMutex`.
+struct FairState {
+    /// Whether the lock is held or has been handed to a waiting task.
+    locked: bool,
+    /// The number of tasks waiting for the lock that have not been handed it.
+    waiting: usize,
+    /// The number of times the lock has been handed to a waiting task that has not yet taken it.
+    handed: usize,
+}
+
+impl FairState {
+    /// Creates the state of an unlocked lock.
+    const fn new() -> Self {
+        Self {
+            locked: false,
+            waiting: 0,
+            handed: 0,
+        }
+    }
+
+    /// Releases the lock, handing it to a waiting task if there is one.
+    ///
+    /// Returns whether the lock was handed to a waiting task.
+    fn release(&mut self) -> bool {
+        if self.waiting > 0 {
+            self.waiting -= 1;
+            self.handed += 1;
+
+            true
+        } else {
+            self.locked = false;
+
+            false
+        }
+    }
+}
+
+impl <T> SortAsyncFairMutex<T> {
+    /// Creates a new `SortAsyncFairMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Mutex::new(FairState::new()),
+            handed: SortAsyncCondvar::new(),
+            key: LazySortKey::new(SortKey::new()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortAsyncFairMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            state: Mutex::new(FairState::new()),
+            handed: SortAsyncCondvar::new(),
+            key: LazySortKey::unassigned(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortAsyncFairMutex<T> {
+    /// Requests to lock this lock.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    pub fn lock(&self) -> SortAsyncFairMutexGuard<'_, T> {
+        SortAsyncFairMutexGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock without waiting.
+    ///
+    /// This only succeeds if no other task holds or is waiting for the lock. As only a single
+    /// lock is locked no sorting is required.
+    pub fn try_lock(&self) -> Option<SortAsyncFairMutexLocked<'_, T>> {
+        let mut state = self.state.lock();
+
+        (!state.locked).then(|| {
+            state.locked = true;
+
+            SortAsyncFairMutexLocked {
+                lock: self,
+                held: HeldKey::new(self.sort_key(), LockAccess::Exclusive),
+            }
+        })
+    }
+
+    /// Locks this lock, blocking the current thread until it is acquired.
+    ///
+    /// As only a single lock is acquired no sorting is required. This allows the lock to be used
+    /// from code that is not async.
+    ///
+    /// # Panicking
+    /// This method will panic if called from within a `tokio` runtime.
+    #[cfg(feature = "std")]
+    pub fn blocking_lock(&self) -> SortAsyncFairMutexLocked<'_, T> {
+        self.lock().blocking_lock_all()
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Waits until this lock is free or has been handed to the current task.
+    async fn lock_raw(&self) {
+        let mut waiter = FairWaiter {
+            lock: self,
+            queued: false,
+        };
+
+        self.handed.wait_until(|| waiter.try_take()).await;
+    }
+
+    /// Releases this lock, handing it to the task that has waited the longest.
+    fn unlock(&self) {
+        if self.state.lock().release() {
+            self.handed.notify_one();
+        }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncFairMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SortAsyncFairMutex");
+        d.field("key", &self.sort_key());
+
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+
+        d.finish()
+    }
+}
+
+impl <T: Default> Default for SortAsyncFairMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortAsyncFairMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A task waiting for a `SortAsyncFairMutex`.
+///
+/// If the task stops waiting before it takes the lock it leaves the queue, passing on the lock if
+/// it had already been handed to it.
+struct FairWaiter<'l, T: ?Sized> {
+    /// The lock being waited for.
+    lock: &'l SortAsyncFairMutex<T>,
+    /// Whether this task has joined the queue of waiting tasks.
+    queued: bool,
+}
+
+impl <T: ?Sized> FairWaiter<'_, T> {
+    /// Attempts to take the lock, joining the queue if it is unavailable.
+    ///
+    /// Returns whether the lock was taken.
+    fn try_take(&mut self) -> bool {
+        let mut state = self.lock.state.lock();
+
+        if self.queued {
+            if state.handed == 0 {
+                return false;
+            }
+
+            state.handed -= 1;
+            self.queued = false;
+        } else if state.locked {
+            state.waiting += 1;
+            self.queued = true;
+
+            return false;
+        } else {
+            state.locked = true;
+        }
+
+        true
+    }
+}
+
+impl <T: ?Sized> Drop for FairWaiter<'_, T> {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+
+        let mut state = self.lock.state.lock();
+
+        if state.waiting > 0 {
+            state.waiting -= 1;
+        } else {
+            // Every task still in the queue has been handed the lock, so one of the handoffs was
+            // meant for this task.
+            state.handed -= 1;
+
+            if state.release() {
+                drop(state);
+
+                self.lock.handed.notify_one();
+            }
+        }
+    }
+}
+
+/// A guard for a `SortAsyncFairMutex`.
+pub struct SortAsyncFairMutexGuard<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortAsyncFairMutex<T>,
+}
+
+impl <'l, T: ?Sized> AsyncSortableLock for SortAsyncFairMutexGuard<'l, T> {
+    type Guard = SortAsyncFairMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        HeldKey::check(self.sort_key(), LockAccess::Exclusive);
+
+        self.lock.lock_raw().await;
+
+        SortAsyncFairMutexLocked {
+            lock: self.lock,
+            held: HeldKey::new(self.sort_key(), LockAccess::Exclusive),
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.lock.try_lock()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <'l, T: ?Sized + Send> IntoFuture for SortAsyncFairMutexGuard<'l, T> {
+    type Output = SortAsyncFairMutexLocked<'l, T>;
+    type IntoFuture = LockFuture<'l, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.lock_presorted().await
+        })
+    }
+}
+
+/// An acquired lock on a `SortAsyncFairMutex`.
+///
+/// The lock is passed to the next waiting task when this guard is dropped.
+pub struct SortAsyncFairMutexLocked<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortAsyncFairMutex<T>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortAsyncFairMutexLocked<'_, T> {}
+
+impl <T: ?Sized> Deref for SortAsyncFairMutexLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The lock is held so no other references exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for SortAsyncFairMutexLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.held.touch();
+
+        // SAFETY: The lock is held so no other references exist.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> Drop for SortAsyncFairMutexLocked<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncFairMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortAsyncFairMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{AsyncLockGroup, SortAsyncFairMutex, SortAsyncMutex};
+
+    #[tokio::test]
+    async fn test_try_lock() {
+        let lock = SortAsyncFairMutex::new(1);
+
+        let guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(1, *lock.try_lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_arrival_order() {
+        let lock = Arc::new(SortAsyncFairMutex::new(Vec::new()));
+        let other = Arc::new(SortAsyncMutex::new(()));
+
+        let guard = lock.lock().lock_all().await;
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let lock = lock.clone();
+            let other = other.clone();
+
+            tokio::spawn(async move {
+                let (mut guard, _) = (lock.lock(), other.lock()).lock_all().await;
+                guard.push(i);
+            })
+        }).collect();
+
+        // Let every task queue in turn before releasing the lock.
+        for _ in 0..4 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(guard);
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(vec![0, 1, 2, 3], *lock.lock().lock_all().await);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter() {
+        let lock = Arc::new(SortAsyncFairMutex::new(1));
+
+        let guard = lock.lock().lock_all().await;
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), lock.lock().lock_all()).await;
+        assert!(timed_out.is_err());
+        drop(guard);
+        assert_eq!(1, *lock.try_lock().unwrap());
+
+        // A task that is cancelled after the lock was handed to it passes the lock on.
+        let guard = lock.lock().lock_all().await;
+
+        let lock2 = lock.clone();
+        let handle = tokio::spawn(async move {
+            drop(lock2.lock().lock_all().await);
+        });
+
+        tokio::task::yield_now().await;
+        drop(guard);
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(1, *lock.try_lock().unwrap());
+    }
+}
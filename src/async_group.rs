@@ -0,0 +1,1030 @@
+use core::{error::Error, fmt::{self, Display, Formatter}, future::{Future, poll_fn}, pin::{Pin, pin}, task::Poll};
+
+#[cfg(any(feature = "tokio", feature = "smol"))]
+use core::time::Duration;
+
+#[cfg(feature = "log")]
+use core::panic::Location;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(all(feature = "alloc", any(feature = "tokio", feature = "async-lock")))]
+use alloc::boxed::Box;
+
+#[cfg(any(feature = "std", test))]
+use std::{sync::Arc, task::{Context, Wake, Waker}, thread::{self, Thread}};
+
+use crate::{ChainedOrder, FlatOrder, LockAccess, SortKey, check_conflicts};
+
+/// A lock that can be locked asynchronously in a way that ensures that multiple locks are always
+/// locked in the same order.
+///
+/// This is the asynchronous counterpart of `SortableLock`. Waiting for the lock yields to the
+/// executor rather than blocking the thread, so requests can be locked from within async tasks.
+///
+/// The future returned by `lock_presorted` must be cancellation safe. If it is dropped before it
+/// completes the lock must not be left held.
+pub trait AsyncSortableLock {
+    /// The type of the lock guard once locked.
+    type Guard;
+
+    /// Gets the sort key of the lock.
+    fn sort_key(&self) -> SortKey;
+
+    /// Lock this lock, waiting until it is acquired.
+    ///
+    /// This method assumes that lock sorting has already been done.
+    /// `lock_all` from `AsyncLockGroup` should be used if you want to lock with sorting.
+    fn lock_presorted(&self) -> impl Future<Output = Self::Guard>;
+
+    /// Attempts to lock this lock without waiting.
+    ///
+    /// This method assumes that lock sorting has already been done. `None` is returned if the lock
+    /// is currently held elsewhere.
+    fn try_lock_presorted(&self) -> Option<Self::Guard>;
+
+    /// Gets the kind of access this request has to its lock.
+    ///
+    /// This is used to detect groups containing requests for the same lock that can never be
+    /// locked together. By default requests are assumed to be exclusive.
+    fn access(&self) -> LockAccess {
+        LockAccess::Exclusive
+    }
+
+    /// Gets where the current holder of the lock requested it, if the lock records this.
+    ///
+    /// This is used by `lock_all_watched` to report which task is holding up a group. By default
+    /// the holder is unknown.
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        None
+    }
+}
+
+/// A lock that is only locked if it is present.
+///
+/// This allows a lock to conditionally be part of an async group. Locking `None` does nothing.
+impl <L: AsyncSortableLock> AsyncSortableLock for Option<L> {
+    type Guard = Option<L::Guard>;
+
+    fn sort_key(&self) -> SortKey {
+        self.as_ref().map_or(SortKey::NONE, AsyncSortableLock::sort_key)
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        match self {
+            Some(lock) => Some(lock.lock_presorted().await),
+            None => None,
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        match self {
+            Some(lock) => lock.try_lock_presorted().map(Some),
+            None => Some(None),
+        }
+    }
+
+    fn access(&self) -> LockAccess {
+        self.as_ref().map_or(LockAccess::Shared, AsyncSortableLock::access)
+    }
+
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        self.as_ref().and_then(AsyncSortableLock::holder)
+    }
+}
+
+/// A group of values that can be locked asynchronously.
+///
+/// Locks are acquired one at a time in sorted order, yielding to the executor while waiting for
+/// each lock.
+///
+/// A group is locked by awaiting `lock_all` rather than by awaiting the group itself, as tuples are
+/// defined outside this crate so `IntoFuture` cannot be implemented for them. Single requests from
+/// this crate can be awaited directly with the `alloc` feature, see `LockFuture`.
+pub trait AsyncLockGroup {
+    /// The type of the group once locked.
+    type Locked;
+
+    /// Lock all items in the group.
+    ///
+    /// The locking order will be consistent regardless of the order of the locks within in this
+    /// group, and is the same order that `LockGroup::lock_all` would use for the same keys.
+    ///
+    /// # Cancellation
+    /// The returned future is cancellation safe. If it is dropped before it completes, for example
+    /// when another branch of a `select!` finishes first, every lock it has already acquired is
+    /// released and no lock is left held.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together, as locking them would deadlock.
+    ///
+    /// In debug builds with the `tokio` feature, locks from this crate also record which spawned
+    /// task holds them, and this method will panic if the current task waits for a lock that it
+    /// already holds. Such a wait would only complete if another future in the same task, such as
+    /// another branch of a `join!`, released the lock, so this is almost always a mistake. A guard
+    /// moved to another task is attributed to that task once it is dereferenced there. Owned
+    /// guards are not recorded as they are meant to be moved to other tasks.
+    fn lock_all(self) -> impl Future<Output = Self::Locked>;
+
+    /// Attempts to lock all items in the group without waiting.
+    ///
+    /// Either every lock is acquired or none are. If any lock is held elsewhere the locks that were
+    /// already acquired are released and `None` is returned. As this never yields it can be used
+    /// as a fast path before falling back to `lock_all`.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together.
+    fn try_lock_all(&self) -> Option<Self::Locked>;
+
+    /// Lock all items in the group, giving up if another future completes first.
+    ///
+    /// The locks are locked in the same order as `lock_all`. If `deadline` completes before every
+    /// lock is acquired, the locks that were already acquired are released and an error holding
+    /// the key of the lock that was being waited for is returned. Any timer can be used for the
+    /// deadline, so this works with every executor.
+    ///
+    /// - `deadline` - The future to stop waiting at.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together.
+    fn lock_all_before<D: Future>(self, deadline: D) -> impl Future<Output = Result<Self::Locked, LockTimeoutError>>;
+
+    /// Lock all items in the group, giving up after a timeout.
+    ///
+    /// The timer is chosen when the future is first polled. With the `tokio` feature enabled,
+    /// `tokio`'s timer is used from within a `tokio` runtime, which must have time enabled.
+    /// Otherwise `async-io`'s timer is used, which works with `smol` and `async-std`. See
+    /// `lock_all_before` for use with other timers.
+    ///
+    /// - `timeout` - The longest time to wait for.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together, or if it is polled outside a `tokio` runtime without the `smol` feature
+    /// enabled.
+    #[cfg(any(feature = "tokio", feature = "smol"))]
+    fn lock_all_timeout(self, timeout: Duration) -> impl Future<Output = Result<Self::Locked, LockTimeoutError>> where Self: Sized {
+        self.lock_all_before(sleep(timeout))
+    }
+
+    /// Lock all items in the group, logging a warning if the group is still waiting when a threshold
+    /// future completes.
+    ///
+    /// This behaves like `lock_all` and keeps waiting after the warning. The warning is logged with
+    /// `log` and names the lock being waited for, where its current holder requested it if the lock
+    /// records this, and the keys of the locks that are still to be locked. This helps to diagnose
+    /// stuck tasks without giving up on the group. Any timer can be used for the threshold.
+    ///
+    /// - `threshold` - The future to warn at.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together.
+    #[cfg(feature = "log")]
+    fn lock_all_watched<D: Future>(self, threshold: D) -> impl Future<Output = Self::Locked>;
+
+    /// Lock all items in the group, logging a warning if the group has been waiting for longer
+    /// than a threshold.
+    ///
+    /// This uses the same timer as `lock_all_timeout`. See `lock_all_watched` for use with other
+    /// timers.
+    ///
+    /// - `threshold` - The time to wait for before warning.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together, or if it is polled outside a `tokio` runtime without the `smol` feature
+    /// enabled.
+    #[cfg(all(feature = "log", any(feature = "tokio", feature = "smol")))]
+    fn lock_all_watchdog(self, threshold: Duration) -> impl Future<Output = Self::Locked> where Self: Sized {
+        self.lock_all_watched(sleep(threshold))
+    }
+
+    /// Lock all items in the group, blocking the current thread until every lock is acquired.
+    ///
+    /// This allows async locks to be shared with code that is not async. The locks are locked in
+    /// the same order as `lock_all`.
+    ///
+    /// # Panicking
+    /// This method will panic if called from within a `tokio` runtime, as blocking would stall the
+    /// tasks driven by the thread, or if the group contains requests for the same lock that can
+    /// never be held together.
+    #[cfg(feature = "std")]
+    fn blocking_lock_all(self) -> Self::Locked where Self: Sized {
+        block_on(self.lock_all())
+    }
+}
+
+impl <T: AsyncSortableLock> AsyncLockGroup for T {
+    type Locked = T::Guard;
+
+    async fn lock_all(self) -> Self::Locked {
+        self.lock_presorted().await
+    }
+
+    fn try_lock_all(&self) -> Option<Self::Locked> {
+        self.try_lock_presorted()
+    }
+
+    async fn lock_all_before<D: Future>(self, deadline: D) -> Result<Self::Locked, LockTimeoutError> {
+        before(self.lock_presorted(), pin!(deadline)).await
+            .ok_or(LockTimeoutError {
+                key: self.sort_key()
+            })
+    }
+
+    #[cfg(feature = "log")]
+    async fn lock_all_watched<D: Future>(self, threshold: D) -> Self::Locked {
+        let mut threshold = Some(pin!(threshold));
+        let order = [(0, self.sort_key())];
+
+        watch(self.lock_presorted(), &mut threshold, &order, || self.holder()).await
+    }
+}
+
+/// The future a single async request from this crate is locked with when it is awaited directly.
+///
+/// Awaiting a request locks it in the same way as `lock_all`. The future returned by
+/// `lock_presorted` cannot be named, so it is boxed here. `lock_all` does not allocate.
+/// ```
+/// use sortlock::SortAsyncMutex;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let lock = SortAsyncMutex::new(1);
+///
+/// let mut guard = lock.lock().await;
+/// *guard += 1;
+/// assert_eq!(2, *guard);
+/// # });
+/// ```
+#[cfg(all(feature = "alloc", any(feature = "tokio", feature = "async-lock")))]
+pub type LockFuture<'l, G> = Pin<Box<dyn Future<Output = G> + Send + 'l>>;
+
+/// The error returned when an async group could not be locked before its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockTimeoutError {
+    /// The key of the lock that was being waited for.
+    key: SortKey,
+}
+
+impl LockTimeoutError {
+    /// Gets the sort key of the lock that was being waited for when the deadline was reached.
+    pub fn key(&self) -> SortKey {
+        self.key
+    }
+}
+
+impl Display for LockTimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out waiting for the lock with key {:?}", self.key)
+    }
+}
+
+impl Error for LockTimeoutError {}
+
+/// Waits for a future to complete, giving up if a deadline completes first.
+///
+/// The future is polled before the deadline so it still completes if both are ready. Returns
+/// `None` if the deadline was reached.
+///
+/// - `future` - The future to wait for.
+/// - `deadline` - The future to stop waiting at.
+async fn before<F: Future, D: Future>(future: F, mut deadline: Pin<&mut D>) -> Option<F::Output> {
+    let mut future = pin!(future);
+
+    poll_fn(|context| {
+        if let Poll::Ready(output) = future.as_mut().poll(context) {
+            return Poll::Ready(Some(output));
+        }
+
+        deadline.as_mut().poll(context).map(|_| None)
+    }).await
+}
+
+/// Waits for a duration using the timer of the current runtime.
+///
+/// `tokio`'s timer is used from within a `tokio` runtime and `async-io`'s timer otherwise.
+///
+/// - `duration` - The time to wait for.
+#[cfg(any(feature = "tokio", feature = "smol"))]
+async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        tokio::time::sleep(duration).await;
+        return;
+    }
+
+    #[cfg(feature = "smol")]
+    async_io::Timer::after(duration).await;
+    #[cfg(not(feature = "smol"))]
+    panic!("Failed to wait for timeout: not within a tokio runtime. Enable the smol feature to use async-io's timer elsewhere.");
+}
+
+/// Waits for a future to lock the first of the remaining locks of a group, logging a warning if
+/// the threshold completes first.
+///
+/// The threshold is taken once it has completed so that a group only warns once.
+///
+/// - `future` - The future locking the lock.
+/// - `threshold` - The future to warn at, or `None` if it has already completed.
+/// - `pending` - The index and key of every lock that is still to be locked, starting with the
+///   lock being waited for.
+/// - `holder` - Gets where the holder of the lock being waited for requested it.
+#[cfg(feature = "log")]
+async fn watch<F: Future, D: Future, H: Fn() -> Option<&'static Location<'static>>>(
+    future: F,
+    threshold: &mut Option<Pin<&mut D>>,
+    pending: &[(usize, SortKey)],
+    holder: H,
+) -> F::Output {
+    /// Formats the keys of the pending locks.
+    struct PendingKeys<'a>(&'a [(usize, SortKey)]);
+
+    impl fmt::Debug for PendingKeys<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            f.debug_list()
+                .entries(self.0.iter().map(|(_, key)| key))
+                .finish()
+        }
+    }
+
+    let mut future = pin!(future);
+
+    poll_fn(|context| {
+        if let Poll::Ready(output) = future.as_mut().poll(context) {
+            return Poll::Ready(output);
+        }
+
+        if threshold.as_mut().is_some_and(|threshold| threshold.as_mut().poll(context).is_ready()) {
+            *threshold = None;
+
+            match holder() {
+                Some(location) => log::warn!(
+                    "lock_all is still waiting for the lock with key {:?}, held by the request made at {}; pending keys: {:?}",
+                    pending[0].1, location, PendingKeys(pending),
+                ),
+                None => log::warn!(
+                    "lock_all is still waiting for the lock with key {:?}; pending keys: {:?}",
+                    pending[0].1, PendingKeys(pending),
+                ),
+            }
+        }
+
+        Poll::Pending
+    }).await
+}
+
+/// Wakes a thread blocked in `block_on`.
+#[cfg(any(feature = "std", test))]
+struct ThreadWaker(Thread);
+
+#[cfg(any(feature = "std", test))]
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion on the current thread, parking the thread while it waits.
+///
+/// - `future` - The future to run.
+///
+/// # Panicking
+/// This function will panic if called from within a `tokio` runtime.
+#[cfg(any(feature = "std", test))]
+fn block_on<F: Future>(future: F) -> F::Output {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        panic!("Failed to lock group: cannot block the current thread from within a runtime.");
+    }
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// An async group that can be nested within a tuple group.
+///
+/// As with `NestedLockGroup`, the locks of a nested group take part in the same sort as every
+/// other lock in the outer group.
+/// ```
+/// # async fn example() {
+/// use sortlock::{AsyncLockGroup, AsyncNestedLockGroup};
+/// # use sortlock::{AsyncSortableLock, SortKey};
+/// # struct Request(SortKey);
+/// # impl AsyncSortableLock for Request {
+/// #     type Guard = ();
+/// #     fn sort_key(&self) -> SortKey { self.0 }
+/// #     async fn lock_presorted(&self) {}
+/// #     fn try_lock_presorted(&self) -> Option<()> { Some(()) }
+/// # }
+/// # let (request1, request2, request3) = (Request(SortKey::new()), Request(SortKey::new()), Request(SortKey::new()));
+///
+/// let group = ((request3, request1), request2);
+/// assert_eq!(vec![1, 2, 0], group.acquisition_order().as_ref().iter().map(|(i, _)| *i).collect::<Vec<_>>());
+///
+/// // Locked in the order request1, request2, request3.
+/// let ((guard3, guard1), guard2) = group.lock_all().await;
+/// # }
+/// ```
+///
+/// Each lock in a nested group is referred to by its index when the group is flattened.
+pub trait AsyncNestedLockGroup: AsyncLockGroup {
+    /// The number of locks in the group once flattened.
+    const LEN: usize;
+
+    /// The order in which the members of the group are locked.
+    ///
+    /// Each entry contains the flattened index of a lock within the group and its sort key.
+    type Order: FlatOrder + AsRef<[(usize, SortKey)]> + AsMut<[(usize, SortKey)]>;
+
+    /// The group while only some of its locks have been locked.
+    type Partial;
+
+    /// Gets the sort keys of every lock in the group in flattened order.
+    fn unsorted_order(&self) -> Self::Order;
+
+    /// Creates a group state where none of the locks have been locked.
+    fn unlocked_partial() -> Self::Partial;
+
+    /// Locks a single lock in the group.
+    ///
+    /// This method assumes that lock sorting has already been done.
+    ///
+    /// - `index` - The flattened index of the lock.
+    /// - `partial` - The group state to store the guard in.
+    fn lock_member(&self, index: usize, partial: &mut Self::Partial) -> impl Future<Output = ()>;
+
+    /// Attempts to lock a single lock in the group without waiting.
+    ///
+    /// Returns whether the lock was acquired.
+    ///
+    /// - `index` - The flattened index of the lock.
+    /// - `partial` - The group state to store the guard in.
+    fn try_lock_member(&self, index: usize, partial: &mut Self::Partial) -> bool;
+
+    /// Gets the kind of access a single lock in the group has.
+    ///
+    /// - `index` - The flattened index of the lock.
+    fn member_access(&self, index: usize) -> LockAccess;
+
+    /// Gets where the current holder of a single lock in the group requested it, if the lock
+    /// records this.
+    ///
+    /// - `index` - The flattened index of the lock.
+    #[cfg(feature = "log")]
+    fn member_holder(&self, _index: usize) -> Option<&'static Location<'static>> {
+        None
+    }
+
+    /// Converts a group state where all locks have been locked into the locked group.
+    ///
+    /// - `partial` - The group state.
+    fn complete_partial(partial: Self::Partial) -> Self::Locked;
+
+    /// Gets the order the group would be locked in without locking it.
+    ///
+    /// Each entry contains the flattened index of a lock within the group and its sort key.
+    fn acquisition_order(&self) -> Self::Order {
+        let mut order = self.unsorted_order();
+
+        order.as_mut().sort_unstable_by_key(|(_, key)| *key);
+
+        order
+    }
+}
+
+impl <T: AsyncSortableLock> AsyncNestedLockGroup for T {
+    const LEN: usize = 1;
+
+    type Order = [(usize, SortKey); 1];
+
+    type Partial = Option<T::Guard>;
+
+    fn unsorted_order(&self) -> Self::Order {
+        [(0, self.sort_key())]
+    }
+
+    fn unlocked_partial() -> Self::Partial {
+        None
+    }
+
+    async fn lock_member(&self, _index: usize, partial: &mut Self::Partial) {
+        *partial = Some(self.lock_presorted().await);
+    }
+
+    fn try_lock_member(&self, _index: usize, partial: &mut Self::Partial) -> bool {
+        *partial = self.try_lock_presorted();
+
+        partial.is_some()
+    }
+
+    fn member_access(&self, _index: usize) -> LockAccess {
+        self.access()
+    }
+
+    #[cfg(feature = "log")]
+    fn member_holder(&self, _index: usize) -> Option<&'static Location<'static>> {
+        self.holder()
+    }
+
+    fn complete_partial(partial: Self::Partial) -> Self::Locked {
+        partial.expect("all locks are locked")
+    }
+}
+
+/// Builds the type of the joined order of a tuple's members.
+macro_rules! chained_order {
+    ($a:ty) => { $a };
+    ($a:ty, $($rest:ty),+) => { ChainedOrder<$a, chained_order!($($rest),+)> };
+}
+
+/// Joins the orders of a tuple's members.
+macro_rules! chain_orders {
+    ($a:expr) => { $a };
+    ($a:expr, $($rest:expr),+) => { ChainedOrder::new($a, chain_orders!($($rest),+)) };
+}
+
+/// Implements `AsyncLockGroup` and `AsyncNestedLockGroup` for tuples of `AsyncNestedLockGroup`s.
+macro_rules! tuple_async_lock_group {
+    ($(($($t:ident $i:tt),+);)+) => {
+        $(
+            impl <$($t: AsyncNestedLockGroup),+> AsyncLockGroup for ($($t,)+) {
+                type Locked = ($($t::Locked,)+);
+
+                async fn lock_all(self) -> Self::Locked {
+                    let order = self.acquisition_order();
+
+                    check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, self.member_access(*i))));
+
+                    let mut partial = Self::unlocked_partial();
+
+                    for (i, _) in order.as_ref() {
+                        self.lock_member(*i, &mut partial).await;
+                    }
+
+                    Self::complete_partial(partial)
+                }
+
+                fn try_lock_all(&self) -> Option<Self::Locked> {
+                    let order = self.acquisition_order();
+
+                    check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, self.member_access(*i))));
+
+                    let mut partial = Self::unlocked_partial();
+
+                    for (i, _) in order.as_ref() {
+                        if !self.try_lock_member(*i, &mut partial) {
+                            return None;
+                        }
+                    }
+
+                    Some(Self::complete_partial(partial))
+                }
+
+                async fn lock_all_before<D: Future>(self, deadline: D) -> Result<Self::Locked, LockTimeoutError> {
+                    let order = self.acquisition_order();
+
+                    check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, self.member_access(*i))));
+
+                    let mut partial = Self::unlocked_partial();
+                    let mut deadline = pin!(deadline);
+
+                    for (i, key) in order.as_ref() {
+                        if before(self.lock_member(*i, &mut partial), deadline.as_mut()).await.is_none() {
+                            return Err(LockTimeoutError {
+                                key: *key
+                            });
+                        }
+                    }
+
+                    Ok(Self::complete_partial(partial))
+                }
+
+                #[cfg(feature = "log")]
+                async fn lock_all_watched<D: Future>(self, threshold: D) -> Self::Locked {
+                    let order = self.acquisition_order();
+
+                    check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, self.member_access(*i))));
+
+                    let mut partial = Self::unlocked_partial();
+                    let mut threshold = Some(pin!(threshold));
+
+                    for (n, (i, _)) in order.as_ref().iter().enumerate() {
+                        watch(self.lock_member(*i, &mut partial), &mut threshold, &order.as_ref()[n..], || self.member_holder(*i)).await;
+                    }
+
+                    Self::complete_partial(partial)
+                }
+            }
+
+            impl <$($t: AsyncNestedLockGroup),+> AsyncNestedLockGroup for ($($t,)+) {
+                const LEN: usize = 0 $(+ $t::LEN)+;
+
+                type Order = chained_order!($($t::Order),+);
+
+                type Partial = ($($t::Partial,)+);
+
+                fn unsorted_order(&self) -> Self::Order {
+                    let mut order = chain_orders!($(self.$i.unsorted_order()),+);
+
+                    for (i, (index, _)) in order.as_mut().iter_mut().enumerate() {
+                        *index = i;
+                    }
+
+                    order
+                }
+
+                fn unlocked_partial() -> Self::Partial {
+                    ($($t::unlocked_partial(),)+)
+                }
+
+                async fn lock_member(&self, index: usize, partial: &mut Self::Partial) {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.lock_member(index, &mut partial.$i).await;
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                fn try_lock_member(&self, index: usize, partial: &mut Self::Partial) -> bool {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.try_lock_member(index, &mut partial.$i);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                fn member_access(&self, index: usize) -> LockAccess {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.member_access(index);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                #[cfg(feature = "log")]
+                fn member_holder(&self, index: usize) -> Option<&'static Location<'static>> {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.member_holder(index);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                fn complete_partial(partial: Self::Partial) -> Self::Locked {
+                    ($($t::complete_partial(partial.$i),)+)
+                }
+            }
+        )+
+    };
+}
+
+tuple_async_lock_group! {
+    (T1 0, T2 1);
+    (T1 0, T2 1, T3 2);
+    (T1 0, T2 1, T3 2, T4 3);
+    (T1 0, T2 1, T3 2, T4 3, T5 4);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13, T15 14);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13, T15 14, T16 15);
+}
+
+/// Locks a runtime-sized collection of requests, returning the guards in the same order.
+/// ```
+/// # async fn example() {
+/// use sortlock::AsyncLockGroup;
+/// # use sortlock::{AsyncSortableLock, SortKey};
+/// # struct Request(SortKey, u32);
+/// # impl AsyncSortableLock for Request {
+/// #     type Guard = u32;
+/// #     fn sort_key(&self) -> SortKey { self.0 }
+/// #     async fn lock_presorted(&self) -> u32 { self.1 }
+/// #     fn try_lock_presorted(&self) -> Option<u32> { Some(self.1) }
+/// # }
+/// # let shards: Vec<_> = (0..4).map(|i| Request(SortKey::new(), i)).collect();
+///
+/// // The shards are locked in sorted order whatever order they are listed in.
+/// let guards = shards.into_iter().rev().collect::<Vec<_>>().lock_all().await;
+/// assert_eq!(vec![3, 2, 1, 0], guards);
+/// # }
+/// ```
+#[cfg(feature = "alloc")]
+impl <L: AsyncSortableLock> AsyncLockGroup for Vec<L> {
+    type Locked = Vec<L::Guard>;
+
+    async fn lock_all(self) -> Self::Locked {
+        let order = sorted_order(&self);
+        let mut guards: Vec<_> = self.iter().map(|_| None).collect();
+
+        for (i, _) in &order {
+            guards[*i] = Some(self[*i].lock_presorted().await);
+        }
+
+        guards.into_iter().map(Option::unwrap).collect()
+    }
+
+    fn try_lock_all(&self) -> Option<Self::Locked> {
+        let order = sorted_order(self);
+        let mut guards: Vec<_> = self.iter().map(|_| None).collect();
+
+        for (i, _) in &order {
+            guards[*i] = Some(self[*i].try_lock_presorted()?);
+        }
+
+        Some(guards.into_iter().map(Option::unwrap).collect())
+    }
+
+    async fn lock_all_before<D: Future>(self, deadline: D) -> Result<Self::Locked, LockTimeoutError> {
+        let order = sorted_order(&self);
+        let mut guards: Vec<_> = self.iter().map(|_| None).collect();
+        let mut deadline = pin!(deadline);
+
+        for (i, key) in &order {
+            guards[*i] = Some(before(self[*i].lock_presorted(), deadline.as_mut()).await
+                .ok_or(LockTimeoutError {
+                    key: *key
+                })?);
+        }
+
+        Ok(guards.into_iter().map(Option::unwrap).collect())
+    }
+
+    #[cfg(feature = "log")]
+    async fn lock_all_watched<D: Future>(self, threshold: D) -> Self::Locked {
+        let order = sorted_order(&self);
+        let mut guards: Vec<_> = self.iter().map(|_| None).collect();
+        let mut threshold = Some(pin!(threshold));
+
+        for (n, (i, _)) in order.iter().enumerate() {
+            guards[*i] = Some(watch(self[*i].lock_presorted(), &mut threshold, &order[n..], || self[*i].holder()).await);
+        }
+
+        guards.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+/// Gets the order to lock a collection of requests in.
+///
+/// - `locks` - The requests to order.
+///
+/// # Panicking
+/// This function will panic if the collection contains requests for the same lock that can never
+/// be held together.
+#[cfg(feature = "alloc")]
+fn sorted_order<L: AsyncSortableLock>(locks: &[L]) -> Vec<(usize, SortKey)> {
+    let mut order: Vec<_> = locks.iter()
+        .map(AsyncSortableLock::sort_key)
+        .enumerate()
+        .collect();
+
+    order.sort_unstable_by_key(|(_, key)| *key);
+    check_conflicts(order.iter().map(|(i, key)| (*key, locks[*i].access())));
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::{Cell, RefCell}, future::Future, sync::Arc, task::{Context, Poll, Waker}, thread, vec::Vec};
+
+    use crate::{AsyncLockGroup, AsyncSortableLock, SortKey, async_group::{ThreadWaker, block_on}};
+
+    /// Returns `Pending` once before completing.
+    async fn yield_now() {
+        let mut yielded = false;
+
+        core::future::poll_fn(|context| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }).await;
+    }
+
+    /// An async lock that records when it is locked and yields before each acquisition.
+    struct RecordingLock<'l> {
+        key: SortKey,
+        name: &'static str,
+        events: &'l RefCell<Vec<&'static str>>,
+    }
+
+    impl AsyncSortableLock for RecordingLock<'_> {
+        type Guard = &'static str;
+
+        fn sort_key(&self) -> SortKey {
+            self.key
+        }
+
+        async fn lock_presorted(&self) -> Self::Guard {
+            yield_now().await;
+            self.events.borrow_mut().push(self.name);
+
+            self.name
+        }
+
+        fn try_lock_presorted(&self) -> Option<Self::Guard> {
+            self.events.borrow_mut().push(self.name);
+
+            Some(self.name)
+        }
+    }
+
+    #[test]
+    fn test_async_order() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+
+        let ((guard3, guard1), guard2) = block_on(((third, first), second).lock_all());
+        assert_eq!(("third", "first", "second"), (guard3, guard1, guard2));
+
+        assert_eq!(vec!["first", "second", "third"], *events.borrow());
+    }
+
+    #[test]
+    fn test_try_lock_all_order() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+
+        assert_eq!(Some(("second", "first")), (second, first).try_lock_all());
+        assert_eq!(vec!["first", "second"], *events.borrow());
+    }
+
+    #[test]
+    fn test_async_optional() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+
+        let (guard1, guard2) = block_on((Some(first), None::<RecordingLock>).lock_all());
+        assert_eq!((Some("first"), None), (guard1, guard2));
+
+        assert_eq!(vec!["first"], *events.borrow());
+    }
+
+    #[test]
+    fn test_async_cancelled() {
+        /// Releases a lock when dropped.
+        struct ReleaseGuard<'l>(&'l Cell<bool>);
+
+        impl Drop for ReleaseGuard<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+
+        /// An async lock that is acquired immediately unless it is blocked.
+        struct BlockingLock<'l> {
+            key: SortKey,
+            held: &'l Cell<bool>,
+            blocked: bool,
+        }
+
+        impl <'l> AsyncSortableLock for BlockingLock<'l> {
+            type Guard = ReleaseGuard<'l>;
+
+            fn sort_key(&self) -> SortKey {
+                self.key
+            }
+
+            async fn lock_presorted(&self) -> Self::Guard {
+                if self.blocked {
+                    core::future::pending::<()>().await;
+                }
+
+                self.held.set(true);
+                ReleaseGuard(self.held)
+            }
+
+            fn try_lock_presorted(&self) -> Option<Self::Guard> {
+                if self.blocked {
+                    return None;
+                }
+
+                self.held.set(true);
+                Some(ReleaseGuard(self.held))
+            }
+        }
+
+        let (held1, held2) = (Cell::new(false), Cell::new(false));
+        let first = BlockingLock { key: SortKey::new(), held: &held1, blocked: false };
+        let second = BlockingLock { key: SortKey::new(), held: &held2, blocked: true };
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut future = Box::pin((second, first).lock_all());
+
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        assert!(held1.get());
+
+        drop(future);
+        assert!(!held1.get());
+        assert!(!held2.get());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_async_vec() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+        let third_key = third.key;
+
+        let guards = block_on(vec![third, first, second].lock_all());
+        assert_eq!(vec!["third", "first", "second"], guards);
+        assert_eq!(vec!["first", "second", "third"], *events.borrow());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let third = RecordingLock { key: third_key, name: "third", events: &events };
+        assert_eq!(Some(vec!["third", "first"]), vec![third, first].try_lock_all());
+
+        // Each lock and the deadline yield once, so the deadline is reached while waiting for the
+        // lock with the later key.
+        let fourth = RecordingLock { key: SortKey::new(), name: "fourth", events: &events };
+        let fourth_key = fourth.key;
+        let third = RecordingLock { key: third_key, name: "third", events: &events };
+        let error = block_on(vec![fourth, third].lock_all_before(yield_now())).unwrap_err();
+        assert_eq!(fourth_key, error.key());
+    }
+
+    #[test]
+    fn test_lock_all_before() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let second_key = second.key;
+
+        // Each lock and the deadline yield once, so the deadline is reached while waiting for the
+        // second lock.
+        let error = block_on((second, first).lock_all_before(yield_now())).unwrap_err();
+        assert_eq!(second_key, error.key());
+        assert_eq!(vec!["first"], *events.borrow());
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to lock group")]
+    fn test_async_conflicting_requests() {
+        let events = RefCell::new(Vec::new());
+        let key = SortKey::new();
+
+        let first = RecordingLock { key, name: "first", events: &events };
+        let second = RecordingLock { key, name: "second", events: &events };
+
+        block_on((first, second).lock_all());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{LockAccess, SortKey};
+
+#[cfg(all(debug_assertions, feature = "tokio"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(all(debug_assertions, feature = "tokio"))]
+use alloc::vec::Vec;
+
+#[cfg(all(debug_assertions, feature = "tokio"))]
+use spin::Mutex;
+
+#[cfg(all(debug_assertions, feature = "tokio"))]
+use tokio::task::{self, Id};
+
+/// The number of shards the held locks are split between.
+#[cfg(all(debug_assertions, feature = "tokio"))]
+const SHARDS: usize = 64;
+
+/// The locks held by `tokio` tasks, split between shards by sort key so that tasks holding
+/// different locks do not contend and only the holders of a lock are searched when it is locked.
+#[cfg(all(debug_assertions, feature = "tokio"))]
+static HELD: [Mutex<Vec<Held>>; SHARDS] = [const { Mutex::new(Vec::new()) }; SHARDS];
+
+/// The identifier given to the next `HeldKey`.
+#[cfg(all(debug_assertions, feature = "tokio"))]
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A held lock and the task holding it.
+#[cfg(all(debug_assertions, feature = "tokio"))]
+struct Held {
+    /// The identifier of the `HeldKey` that recorded the lock.
+    id: usize,
+    /// The sort key of the lock.
+    key: SortKey,
+    /// The access the lock was acquired with.
+    access: LockAccess,
+    /// The task that holds the guard for the lock.
+    task: Id,
+}
+
+/// Records that the current task holds a lock for as long as this value is kept.
+///
+/// In debug builds with the `tokio` feature enabled the lock is attributed to the `tokio` task
+/// that acquired it so that the task can be stopped from waiting for the lock again, which would
+/// never complete. The lock is attributed to another task once the guard is used there, so a
+/// guard moved to another task does not stop the task that acquired it from waiting for the lock.
+/// Otherwise this does nothing.
+pub(crate) struct HeldKey {
+    /// The identifier of the record and the key of the lock, if the lock was acquired in a task.
+    #[cfg(all(debug_assertions, feature = "tokio"))]
+    held: Option<(usize, SortKey)>,
+}
+
+impl HeldKey {
+    /// Records that the current task has acquired a lock.
+    ///
+    /// - `key` - The sort key of the lock.
+    /// - `access` - The access the lock was acquired with.
+    #[cfg_attr(not(all(debug_assertions, feature = "tokio")), allow(unused_variables))]
+    pub(crate) fn new(key: SortKey, access: LockAccess) -> Self {
+        #[cfg(all(debug_assertions, feature = "tokio"))]
+        {
+            let held = task::try_id().map(|task| {
+                let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+                Self::shard(key).lock().push(Held {
+                    id,
+                    key,
+                    access,
+                    task,
+                });
+
+                (id, key)
+            });
+
+            Self {
+                held
+            }
+        }
+
+        #[cfg(not(all(debug_assertions, feature = "tokio")))]
+        Self {}
+    }
+
+    /// Attributes the lock to the current task, as the guard is being used in it.
+    ///
+    /// This is called whenever the guard is dereferenced so that a guard moved to another task is
+    /// attributed to that task.
+    pub(crate) fn touch(&self) {
+        #[cfg(all(debug_assertions, feature = "tokio"))]
+        if let (Some((id, key)), Some(task)) = (self.held, task::try_id()) {
+            if let Some(held) = Self::shard(key).lock().iter_mut().find(|held| held.id == id) {
+                held.task = task;
+            }
+        }
+    }
+
+    /// Panics if the current task is about to wait for a lock that it already holds.
+    ///
+    /// - `key` - The sort key of the lock.
+    /// - `access` - The access the lock is requested with.
+    #[cfg_attr(not(all(debug_assertions, feature = "tokio")), allow(unused_variables))]
+    pub(crate) fn check(key: SortKey, access: LockAccess) {
+        #[cfg(all(debug_assertions, feature = "tokio"))]
+        if let Some(task) = task::try_id() {
+            let reentrant = Self::shard(key).lock()
+                .iter()
+                .any(|held| {
+                    held.task == task && held.key == key && (held.access == LockAccess::Exclusive || access == LockAccess::Exclusive)
+                });
+
+            if reentrant {
+                panic!("Failed to lock group: the current task already holds the lock with key {key:?}, so waiting for it would never complete.");
+            }
+        }
+    }
+
+    /// Gets the shard recording the holders of a lock.
+    ///
+    /// - `key` - The sort key of the lock.
+    #[cfg(all(debug_assertions, feature = "tokio"))]
+    fn shard(key: SortKey) -> &'static Mutex<Vec<Held>> {
+        &HELD[key.value() as usize % SHARDS]
+    }
+}
+
+#[cfg(all(debug_assertions, feature = "tokio"))]
+impl Drop for HeldKey {
+    fn drop(&mut self) {
+        if let Some((id, key)) = self.held {
+            let mut shard = Self::shard(key).lock();
+
+            if let Some(index) = shard.iter().position(|held| held.id == id) {
+                shard.swap_remove(index);
+            }
+        }
+    }
+}
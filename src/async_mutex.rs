@@ -0,0 +1,991 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, marker::PhantomData, ops::{Deref, DerefMut}, ptr::NonNull};
+
+#[cfg(feature = "alloc")]
+use core::{future::IntoFuture, mem::{self, ManuallyDrop}};
+
+#[cfg(any(feature = "alloc", feature = "log"))]
+use core::panic::Location;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+
+#[cfg(feature = "tokio")]
+use tokio::sync::{Mutex, MutexGuard};
+#[cfg(not(feature = "tokio"))]
+use async_lock::{Mutex, MutexGuard};
+
+use crate::{AsyncSortableLock, LockAccess, SortKey, async_held::HeldKey, key::LazySortKey};
+
+#[cfg(feature = "std")]
+use crate::AsyncLockGroup;
+#[cfg(feature = "alloc")]
+use crate::LockFuture;
+
+/// A sortable lock that ensures exclusive access to a resource from async tasks.
+///
+/// Waiting for the lock yields to the executor rather than blocking the thread, so a group can be
+/// locked with `lock_all` from `AsyncLockGroup` inside a task. The internal lock is `tokio`'s
+/// `Mutex` when the `tokio` feature is enabled and `async-lock`'s `Mutex` otherwise. Both work with
+/// any executor.
+/// ```
+/// use sortlock::{SortAsyncMutex, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let lock1 = SortAsyncMutex::new(1);
+/// let lock2 = SortAsyncMutex::new(2);
+///
+/// // Here lock1 is locked then lock2, whatever order they are listed in.
+/// let (mut guard2, guard1) = (lock2.lock(), lock1.lock()).lock_all().await;
+/// *guard2 += *guard1;
+/// assert_eq!(3, *guard2);
+/// # });
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct SortAsyncMutex<T: ?Sized> {
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The internal mutex.
+    mutex: Mutex<()>,
+    /// Where the current holder of the lock requested it, if known.
+    #[cfg(feature = "log")]
+    holder: spin::Mutex<Option<&'static Location<'static>>>,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for SortAsyncMutex<T> {}
+unsafe impl <T: ?Sized + Send> Sync for SortAsyncMutex<T> {}
+
+impl <T> SortAsyncMutex<T> {
+    /// Creates a new `SortAsyncMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self::with_key(value, SortKey::new())
+    }
+
+    /// Creates a new `SortAsyncMutex` with a given sort key.
+    ///
+    /// This allows the order of the lock to be controlled with a key such as one from
+    /// `SortKey::from_value`. The key must not be used by any other lock.
+    ///
+    /// - `value` - The value of the lock.
+    /// - `key` - The sort key of the lock.
+    pub fn with_key(value: T, key: SortKey) -> Self {
+        Self {
+            key: LazySortKey::new(key),
+            mutex: Mutex::new(()),
+            #[cfg(feature = "log")]
+            holder: spin::Mutex::new(None),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortAsyncMutex` in an `Arc` with a sort key derived from its address.
+    ///
+    /// As the lock never moves while it is in the `Arc` its address identifies it, so no key has
+    /// to be taken from the global counter. This avoids contention on the counter when many locks
+    /// are created at once, and the key is reused once the allocation is freed.
+    ///
+    /// - `value` - The value of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is allocated at an address of `2^57` or above.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn new_arc(value: T) -> Arc<Self> {
+        let location = Location::caller();
+
+        Arc::new_cyclic(|lock| Self::with_key(value, SortKey::from_address(lock.as_ptr() as usize, location)))
+    }
+
+    /// Creates a new `SortAsyncMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used rather than when it is created. This
+    /// allows the lock to be placed in a `static`.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            key: LazySortKey::unassigned(),
+            #[cfg(feature = "tokio")]
+            mutex: Mutex::const_new(()),
+            #[cfg(not(feature = "tokio"))]
+            mutex: Mutex::new(()),
+            #[cfg(feature = "log")]
+            holder: spin::Mutex::new(None),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortAsyncMutex<T> {
+    /// Requests to lock this lock.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn lock(&self) -> SortAsyncMutexGuard<'_, T> {
+        SortAsyncMutexGuard {
+            lock: self,
+            #[cfg(feature = "log")]
+            location: Location::caller(),
+        }
+    }
+
+    /// Attempts to lock this lock without waiting.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently held elsewhere.
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn try_lock(&self) -> Option<SortAsyncMutexLocked<'_, T>> {
+        #[cfg(feature = "tokio")]
+        let guard = self.mutex.try_lock().ok();
+        #[cfg(not(feature = "tokio"))]
+        let guard = self.mutex.try_lock();
+
+        let guard = guard.map(|guard| SortAsyncMutexLocked::new(guard, self));
+
+        #[cfg(feature = "log")]
+        if guard.is_some() {
+            self.set_holder(Some(Location::caller()));
+        }
+
+        guard
+    }
+
+    /// Locks this lock, blocking the current thread until it is acquired.
+    ///
+    /// As only a single lock is acquired no sorting is required. This allows the lock to be used
+    /// from code that is not async.
+    ///
+    /// # Panicking
+    /// This method will panic if called from within a `tokio` runtime.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn blocking_lock(&self) -> SortAsyncMutexLocked<'_, T> {
+        self.lock().blocking_lock_all()
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is the key used to order this lock when it is locked as part of a group. A lock
+    /// created with `const_new` is assigned its key on the first call to this method.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Gets where the current holder of this lock requested it, if known.
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        *self.holder.lock()
+    }
+
+    /// Records where the current holder of this lock requested it.
+    ///
+    /// - `location` - Where the lock was requested, or `None` if this is unknown.
+    #[cfg(feature = "log")]
+    fn set_holder(&self, location: Option<&'static Location<'static>>) {
+        *self.holder.lock() = location;
+    }
+}
+
+impl <T: ?Sized + 'static> SortAsyncMutex<T> {
+    /// Locks this lock through an `Arc`, returning a guard that keeps the lock alive.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// The returned guard is not bound to a borrow of the lock so it can be moved into a spawned
+    /// task.
+    #[cfg(feature = "alloc")]
+    pub async fn lock_owned(self: Arc<Self>) -> OwnedSortAsyncMutexLocked<T> {
+        HeldKey::check(self.sort_key(), LockAccess::Exclusive);
+
+        let guard = self.mutex.lock().await;
+
+        #[cfg(feature = "log")]
+        self.set_holder(None);
+
+        OwnedSortAsyncMutexLocked::new(guard, &self)
+    }
+
+    /// Attempts to lock this lock through an `Arc` without waiting.
+    ///
+    /// `None` is returned if the lock is currently held elsewhere.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn try_lock_owned(self: Arc<Self>) -> Option<OwnedSortAsyncMutexLocked<T>> {
+        self.try_lock()
+            .map(|guard| OwnedSortAsyncMutexLocked::new(guard.guard, &self))
+    }
+
+    /// Requests to lock this lock through an `Arc`.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Once locked this produces an `OwnedSortAsyncMutexLocked` so the locked group is not bound
+    /// to a borrow of any of its locks and can be held in a detached task.
+    /// ```
+    /// use std::sync::Arc;
+    /// use sortlock::{SortAsyncMutex, AsyncLockGroup};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let lock1 = Arc::new(SortAsyncMutex::new(1));
+    /// let lock2 = Arc::new(SortAsyncMutex::new(2));
+    ///
+    /// let (mut guard1, guard2) = (lock1.clone().lock_owned_request(), lock2.clone().lock_owned_request())
+    ///     .lock_all()
+    ///     .await;
+    ///
+    /// tokio::spawn(async move {
+    ///     *guard1 += *guard2;
+    /// }).await.unwrap();
+    ///
+    /// assert_eq!(3, *lock1.lock().lock_all().await);
+    /// # });
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn lock_owned_request(self: Arc<Self>) -> OwnedSortAsyncMutexGuard<T> {
+        OwnedSortAsyncMutexGuard {
+            lock: self
+        }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SortAsyncMutex");
+        d.field("key", &self.sort_key());
+
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+
+        d.finish()
+    }
+}
+
+impl <T: Default> Default for SortAsyncMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortAsyncMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A reference to a `SortAsyncMutex` is a request to lock it.
+impl <'l, T: ?Sized> AsyncSortableLock for &'l SortAsyncMutex<T> {
+    type Guard = SortAsyncMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        SortAsyncMutex::sort_key(self)
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        HeldKey::check(self.sort_key(), LockAccess::Exclusive);
+
+        let guard = self.mutex.lock().await;
+
+        #[cfg(feature = "log")]
+        self.set_holder(None);
+
+        SortAsyncMutexLocked::new(guard, self)
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        SortAsyncMutex::try_lock(self)
+    }
+
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        SortAsyncMutex::holder(self)
+    }
+}
+
+/// A guard for a `SortAsyncMutex`.
+pub struct SortAsyncMutexGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortAsyncMutex<T>,
+    /// Where the lock was requested.
+    #[cfg(feature = "log")]
+    location: &'static Location<'static>,
+}
+
+impl <'l, T: ?Sized> AsyncSortableLock for SortAsyncMutexGuard<'l, T> {
+    type Guard = SortAsyncMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        HeldKey::check(self.sort_key(), LockAccess::Exclusive);
+
+        let guard = self.lock.mutex.lock().await;
+
+        #[cfg(feature = "log")]
+        self.lock.set_holder(Some(self.location));
+
+        SortAsyncMutexLocked::new(guard, self.lock)
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        let guard = self.lock.try_lock();
+
+        #[cfg(feature = "log")]
+        if guard.is_some() {
+            self.lock.set_holder(Some(self.location));
+        }
+
+        guard
+    }
+
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        self.lock.holder()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <'l, T: ?Sized + Send> IntoFuture for SortAsyncMutexGuard<'l, T> {
+    type Output = SortAsyncMutexLocked<'l, T>;
+    type IntoFuture = LockFuture<'l, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.lock_presorted().await
+        })
+    }
+}
+
+/// A guard for a `SortAsyncMutex` that owns a reference to the lock.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortAsyncMutexGuard<T: ?Sized + 'static> {
+    /// The lock this request references.
+    lock: Arc<SortAsyncMutex<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + 'static> AsyncSortableLock for OwnedSortAsyncMutexGuard<T> {
+    type Guard = OwnedSortAsyncMutexLocked<T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        self.lock.clone().lock_owned().await
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.lock.clone().try_lock_owned()
+    }
+
+    #[cfg(feature = "log")]
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        self.lock.holder()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Send + 'static> IntoFuture for OwnedSortAsyncMutexGuard<T> {
+    type Output = OwnedSortAsyncMutexLocked<T>;
+    type IntoFuture = LockFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.lock_presorted().await
+        })
+    }
+}
+
+/// An acquired lock on a `SortAsyncMutex`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortAsyncMutexLocked<'l, T: ?Sized> {
+    /// The guard for the internal mutex.
+    guard: MutexGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The lock this guard references.
+    lock: &'l SortAsyncMutex<T>,
+}
+
+impl <'l, T: ?Sized> SortAsyncMutexLocked<'l, T> {
+    /// Creates a new `SortAsyncMutexLocked` from an acquired internal guard.
+    ///
+    /// - `guard` - The guard for the internal mutex.
+    /// - `lock` - The lock the guard was acquired from.
+    fn new(guard: MutexGuard<'l, ()>, lock: &'l SortAsyncMutex<T>) -> Self {
+        Self {
+            guard,
+            held: HeldKey::new(lock.sort_key(), LockAccess::Exclusive),
+            lock,
+        }
+    }
+
+    /// Gets the lock this guard references.
+    pub fn lock(this: &Self) -> &'l SortAsyncMutex<T> {
+        this.lock
+    }
+
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped, so the narrower guard can be
+    /// passed to helper futures that only need part of the value.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortAsyncMutex, SortAsyncMutexLocked, AsyncLockGroup};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let lock = SortAsyncMutex::new((1, 2));
+    ///
+    /// let mut guard = SortAsyncMutexLocked::map(lock.lock().lock_all().await, |value| &mut value.1);
+    /// *guard += 1;
+    /// drop(guard);
+    ///
+    /// assert_eq!((1, 3), *lock.lock().lock_all().await);
+    /// # });
+    /// ```
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortAsyncMutexLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortAsyncMutexLocked {
+            _guard: this.guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortAsyncMutexLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncMutexLocked {
+                    _guard: this.guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortAsyncMutexLocked<'_, T> {}
+
+impl <T: ?Sized> Deref for SortAsyncMutexLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for SortAsyncMutexLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.held.touch();
+
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortAsyncMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A guard for a component of a value locked by a `SortAsyncMutex`.
+///
+/// This is created with `SortAsyncMutexLocked::map`. The lock is released when this guard is
+/// dropped.
+///
+/// Like `&mut T`, the guard is invariant over `T`, so it cannot be used to store a shorter lived
+/// reference in the locked value.
+/// ```compile_fail
+/// use sortlock::MappedSortAsyncMutexLocked;
+///
+/// fn shorten<'a, 'l>(guard: MappedSortAsyncMutexLocked<'l, &'static str>) -> MappedSortAsyncMutexLocked<'l, &'a str> {
+///     guard
+/// }
+/// ```
+pub struct MappedSortAsyncMutexLocked<'l, T: ?Sized> {
+    /// The guard for the internal mutex.
+    _guard: MutexGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Makes the guard invariant over `T`, as it gives mutable access to the value.
+    _marker: PhantomData<&'l mut T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for MappedSortAsyncMutexLocked<'_, T> {}
+unsafe impl <T: ?Sized + Sync> Sync for MappedSortAsyncMutexLocked<'_, T> {}
+
+impl <'l, T: ?Sized> MappedSortAsyncMutexLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortAsyncMutexLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortAsyncMutexLocked {
+            _guard: this._guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortAsyncMutexLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncMutexLocked {
+                    _guard: this._guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for MappedSortAsyncMutexLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for MappedSortAsyncMutexLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.held.touch();
+
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for MappedSortAsyncMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for MappedSortAsyncMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired lock on a `SortAsyncMutex` that owns a reference to the lock.
+///
+/// This is created with `SortAsyncMutex::lock_owned`. The lock is released when this guard is
+/// dropped. Unlike `SortAsyncMutexLocked` this guard is `'static` so it can be moved into a
+/// spawned task.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortAsyncMutexLocked<T: ?Sized + 'static> {
+    /// The guard for the internal mutex.
+    ///
+    /// This must be dropped before `lock`.
+    _guard: MutexGuard<'static, ()>,
+    /// The lock this guard references.
+    lock: Arc<SortAsyncMutex<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + 'static> OwnedSortAsyncMutexLocked<T> {
+    /// Creates a new `OwnedSortAsyncMutexLocked` from an acquired internal guard.
+    ///
+    /// - `guard` - The guard for the internal mutex.
+    /// - `lock` - The lock the guard was acquired from.
+    fn new(guard: MutexGuard<'_, ()>, lock: &Arc<SortAsyncMutex<T>>) -> Self {
+        let guard = ManuallyDrop::new(guard);
+        // SAFETY: The returned guard keeps the lock alive until after the internal guard is
+        // dropped, and the original guard is never dropped.
+        let guard = unsafe { mem::transmute_copy::<MutexGuard<'_, ()>, MutexGuard<'static, ()>>(&guard) };
+
+        Self {
+            _guard: guard,
+            lock: lock.clone(),
+        }
+    }
+
+    /// Gets the lock this guard references.
+    pub fn lock(this: &Self) -> &Arc<SortAsyncMutex<T>> {
+        &this.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl <T: ?Sized + Sync> Sync for OwnedSortAsyncMutexLocked<T> {}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> Deref for OwnedSortAsyncMutexLocked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> DerefMut for OwnedSortAsyncMutexLocked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Debug> Debug for OwnedSortAsyncMutexLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Display> Display for OwnedSortAsyncMutexLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    #[cfg(feature = "std")]
+    use std::{thread, time::Duration};
+
+    use crate::{AsyncLockGroup, MappedSortAsyncMutexLocked, SortAsyncMutex, SortAsyncMutexLocked};
+
+    #[tokio::test]
+    async fn test_async_lock2() {
+        let lock1 = SortAsyncMutex::new(1);
+        let lock2 = SortAsyncMutex::new(2);
+
+        let (guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all().await;
+        assert_eq!(3, *guard1 + *guard2);
+        assert!(lock1.try_lock().is_none());
+
+        drop((guard1, guard2));
+        assert!(lock1.try_lock().is_some());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[tokio::test]
+    async fn test_await_request() {
+        let lock = Arc::new(SortAsyncMutex::new(1));
+
+        *lock.lock().await += 1;
+
+        // The boxed future is `Send` so it can be awaited from a spawned task.
+        let task_lock = lock.clone();
+        tokio::spawn(async move {
+            *task_lock.lock().await += 1;
+        }).await.unwrap();
+
+        assert_eq!(3, *lock.lock().await);
+    }
+
+    #[tokio::test]
+    async fn test_async_map() {
+        static LOCK: SortAsyncMutex<(i32, Option<i32>)> = SortAsyncMutex::const_new((1, Some(2)));
+        let lock = &LOCK;
+
+        let guard = SortAsyncMutexLocked::try_map(lock.lock().lock_all().await, |_| None::<&mut i32>).unwrap_err();
+        let guard = SortAsyncMutexLocked::map(guard, |value| &mut value.1);
+        let mut guard = MappedSortAsyncMutexLocked::try_map(guard, Option::as_mut).unwrap();
+        *guard += 1;
+        assert!(lock.try_lock().is_none());
+
+        // The mapped guard can be held across an await in a spawned task.
+        tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            drop(guard);
+        }).await.unwrap();
+
+        assert_eq!((1, Some(3)), *lock.lock().lock_all().await);
+    }
+
+    #[test]
+    fn test_try_lock_all() {
+        let lock1 = SortAsyncMutex::new(1);
+        let lock2 = SortAsyncMutex::new(2);
+
+        let held = lock2.try_lock().unwrap();
+        assert!((lock1.lock(), lock2.lock()).try_lock_all().is_none());
+        assert!(lock1.try_lock().is_some());
+
+        drop(held);
+        let (guard1, guard2) = (lock1.lock(), lock2.lock()).try_lock_all().unwrap();
+        assert_eq!(3, *guard1 + *guard2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_lock_all_timeout() {
+        let lock1 = SortAsyncMutex::new(1);
+        let lock2 = SortAsyncMutex::new(2);
+
+        let held = lock2.try_lock().unwrap();
+        let error = (lock1.lock(), lock2.lock()).lock_all_timeout(Duration::from_millis(10)).await.unwrap_err();
+        assert_eq!(lock2.sort_key(), error.key());
+        assert!(lock1.try_lock().is_some());
+
+        drop(held);
+        assert!((lock1.lock(), lock2.lock()).lock_all_timeout(Duration::from_millis(10)).await.is_ok());
+    }
+
+    // With the `tokio` feature also enabled this checks that `async-io`'s timer is used outside a
+    // `tokio` runtime.
+    #[cfg(feature = "smol")]
+    #[test]
+    fn test_lock_all_timeout_smol() {
+        async_io::block_on(async {
+            let lock1 = SortAsyncMutex::new(1);
+            let lock2 = SortAsyncMutex::new(2);
+
+            let held = lock2.try_lock().unwrap();
+            let error = (lock1.lock(), lock2.lock()).lock_all_timeout(Duration::from_millis(10)).await.unwrap_err();
+            assert_eq!(lock2.sort_key(), error.key());
+
+            drop(held);
+            assert!((lock1.lock(), lock2.lock()).lock_all_timeout(Duration::from_millis(10)).await.is_ok());
+        });
+    }
+
+    #[cfg(all(debug_assertions, feature = "tokio"))]
+    #[tokio::test]
+    async fn test_reentrant_lock() {
+        let lock = Arc::new(SortAsyncMutex::new(1));
+        let lock2 = SortAsyncMutex::new(2);
+
+        let handle = tokio::spawn(async move {
+            drop(lock.lock().lock_all().await);
+
+            let _guard = lock.lock().lock_all().await;
+            (lock2.lock(), lock.lock()).lock_all().await;
+        });
+
+        let error = handle.await.unwrap_err().into_panic();
+        assert!(error.downcast_ref::<String>().unwrap().contains("already holds"));
+    }
+
+    #[cfg(all(debug_assertions, feature = "tokio"))]
+    #[tokio::test]
+    async fn test_moved_guard() {
+        static LOCK: SortAsyncMutex<i32> = SortAsyncMutex::const_new(1);
+
+        let handle = tokio::spawn(async {
+            let mut guard = LOCK.lock().lock_all().await;
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+
+            // The guard is used by another task, so this task can wait for it to be released.
+            tokio::spawn(async move {
+                *guard += 1;
+                sender.send(()).unwrap();
+                tokio::task::yield_now().await;
+            });
+
+            receiver.await.unwrap();
+            *LOCK.lock().lock_all().await += 1;
+        });
+
+        handle.await.unwrap();
+        assert_eq!(3, *LOCK.lock().lock_all().await);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_blocking_lock() {
+        let lock = Arc::new(SortAsyncMutex::new(1));
+        let held = lock.blocking_lock();
+
+        let lock2 = lock.clone();
+        let handle = thread::spawn(move || {
+            *lock2.blocking_lock() += 1;
+        });
+
+        thread::sleep(Duration::from_millis(10));
+        drop(held);
+        handle.join().unwrap();
+
+        assert_eq!(2, *lock.blocking_lock());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    #[should_panic]
+    async fn test_blocking_lock_in_runtime() {
+        let lock = SortAsyncMutex::new(1);
+
+        let _guard = lock.blocking_lock();
+    }
+
+    #[cfg(feature = "alloc")]
+    #[tokio::test]
+    async fn test_owned_guards() {
+        let lock1 = Arc::new(SortAsyncMutex::new(1));
+        let lock2 = Arc::new(SortAsyncMutex::new(2));
+
+        let (guard2, mut guard1) = (lock2.clone().lock_owned_request(), lock1.clone().lock_owned_request())
+            .lock_all()
+            .await;
+        assert!(lock2.try_lock().is_none());
+
+        let handle = tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            *guard1 += *guard2;
+        });
+        handle.await.unwrap();
+
+        assert_eq!(3, *lock1.clone().try_lock_owned().unwrap());
+        assert!(lock2.try_lock().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_async_select() {
+        let lock1 = SortAsyncMutex::new(1);
+        let lock2 = SortAsyncMutex::new(2);
+
+        let held = lock2.try_lock().unwrap();
+
+        tokio::select! {
+            biased;
+            _ = (lock1.lock(), lock2.lock()).lock_all() => panic!("locked a held lock"),
+            _ = tokio::task::yield_now() => {},
+        }
+
+        // lock1 was acquired while waiting for lock2 and released when the future was dropped.
+        assert!(lock1.try_lock().is_some());
+        drop(held);
+    }
+
+    #[tokio::test]
+    async fn test_async_tasks() {
+        let lock1 = Arc::new(SortAsyncMutex::new(0));
+        let lock2 = Arc::new(SortAsyncMutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let lock1 = lock1.clone();
+            let lock2 = lock2.clone();
+
+            tokio::spawn(async move {
+                for _ in 0..100 {
+                    let (mut guard1, mut guard2) = if i % 2 == 0 {
+                        (lock1.lock(), lock2.lock()).lock_all().await
+                    } else {
+                        let (guard2, guard1) = (lock2.lock(), lock1.lock()).lock_all().await;
+                        (guard1, guard2)
+                    };
+
+                    *guard1 += 1;
+                    tokio::task::yield_now().await;
+                    *guard2 += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!((400, 400), (*lock1.lock().lock_all().await, *lock2.lock().lock_all().await));
+    }
+
+    #[cfg(feature = "log")]
+    #[tokio::test]
+    async fn test_lock_all_watched() {
+        use std::sync::Mutex;
+
+        /// Collects the warnings that are logged.
+        struct Warnings(Mutex<Vec<String>>);
+
+        impl log::Log for Warnings {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Warn
+            }
+
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    self.0.lock().unwrap().push(record.args().to_string());
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        static WARNINGS: Warnings = Warnings(Mutex::new(Vec::new()));
+
+        let _ = log::set_logger(&WARNINGS);
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let lock1 = SortAsyncMutex::new(1);
+        let lock2 = SortAsyncMutex::new(2);
+
+        let held = lock2.lock().lock_all().await;
+
+        let ((guard1, guard2), ()) = tokio::join!(
+            (lock1.lock(), lock2.lock()).lock_all_watched(tokio::task::yield_now()),
+            async {
+                tokio::task::yield_now().await;
+                tokio::task::yield_now().await;
+                drop(held);
+            },
+        );
+        assert_eq!(3, *guard1 + *guard2);
+
+        let warnings = WARNINGS.0.lock().unwrap();
+        let key = format!("{:?}", lock2.sort_key());
+        assert!(warnings.iter().any(|warning| warning.contains(&key) && warning.contains("async_mutex.rs")));
+    }
+}
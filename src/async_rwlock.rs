@@ -0,0 +1,774 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, marker::PhantomData, ops::{Deref, DerefMut}, ptr::NonNull};
+
+#[cfg(feature = "alloc")]
+use core::{future::IntoFuture, panic::Location};
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+
+#[cfg(feature = "tokio")]
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(feature = "tokio"))]
+use async_lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{AsyncSortableLock, LockAccess, SortKey, async_held::HeldKey, key::LazySortKey};
+
+#[cfg(feature = "std")]
+use crate::AsyncLockGroup;
+#[cfg(feature = "alloc")]
+use crate::LockFuture;
+
+/// A sortable lock that allows either exclusive write access or shared read access from async
+/// tasks.
+///
+/// Locking for reading can be performed with `read` while locking for writing can be performed
+/// with `write`. Both requests can be locked in a group with `lock_all` from `AsyncLockGroup`.
+/// The internal lock is `tokio`'s `RwLock` when the `tokio` feature is enabled and `async-lock`'s
+/// `RwLock` otherwise.
+/// ```
+/// use sortlock::{SortAsyncMutex, SortAsyncRwLock, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let lock1 = SortAsyncRwLock::new(100);
+/// let lock2 = SortAsyncMutex::new(200);
+///
+/// let (guard1, mut guard2) = (lock1.read(), lock2.lock()).lock_all().await;
+/// *guard2 += *guard1;
+/// drop((guard1, guard2));
+///
+/// *lock1.write().lock_all().await += 1;
+/// assert_eq!(101, *lock1.read().lock_all().await);
+/// # });
+/// ```
+///
+/// Unlike `SortRwLock` this lock is never poisoned.
+pub struct SortAsyncRwLock<T: ?Sized> {
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The internal lock.
+    lock: RwLock<()>,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for SortAsyncRwLock<T> {}
+unsafe impl <T: ?Sized + Send + Sync> Sync for SortAsyncRwLock<T> {}
+
+impl <T> SortAsyncRwLock<T> {
+    /// Creates a new `SortAsyncRwLock`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self::with_key(value, SortKey::new())
+    }
+
+    /// Creates a new `SortAsyncRwLock` with a given sort key.
+    ///
+    /// This allows the order of the lock to be controlled with a key such as one from
+    /// `SortKey::from_value`. The key must not be used by any other lock.
+    ///
+    /// - `value` - The value of the lock.
+    /// - `key` - The sort key of the lock.
+    pub fn with_key(value: T, key: SortKey) -> Self {
+        Self {
+            key: LazySortKey::new(key),
+            lock: RwLock::new(()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortAsyncRwLock` in an `Arc` with a sort key derived from its address.
+    ///
+    /// As the lock never moves while it is in the `Arc` its address identifies it, so no key has
+    /// to be taken from the global counter. This avoids contention on the counter when many locks
+    /// are created at once, and the key is reused once the allocation is freed.
+    ///
+    /// - `value` - The value of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is allocated at an address of `2^57` or above.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn new_arc(value: T) -> Arc<Self> {
+        let location = Location::caller();
+
+        Arc::new_cyclic(|lock| Self::with_key(value, SortKey::from_address(lock.as_ptr() as usize, location)))
+    }
+
+    /// Creates a new `SortAsyncRwLock` in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used rather than when it is created. This
+    /// allows the lock to be placed in a `static`.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            key: LazySortKey::unassigned(),
+            #[cfg(feature = "tokio")]
+            lock: RwLock::const_new(()),
+            #[cfg(not(feature = "tokio"))]
+            lock: RwLock::new(()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortAsyncRwLock<T> {
+    /// Requests to lock this lock for reading.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    pub fn read(&self) -> SortAsyncReadGuard<'_, T> {
+        SortAsyncReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for writing.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    pub fn write(&self) -> SortAsyncWriteGuard<'_, T> {
+        SortAsyncWriteGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock for reading without waiting.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently locked for writing.
+    pub fn try_read(&self) -> Option<SortAsyncReadLocked<'_, T>> {
+        #[cfg(feature = "tokio")]
+        let guard = self.lock.try_read().ok();
+        #[cfg(not(feature = "tokio"))]
+        let guard = self.lock.try_read();
+
+        guard.map(|guard| SortAsyncReadLocked {
+            guard,
+            held: HeldKey::new(self.sort_key(), LockAccess::Shared),
+            lock: self,
+        })
+    }
+
+    /// Attempts to lock this lock for writing without waiting.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently locked elsewhere.
+    pub fn try_write(&self) -> Option<SortAsyncWriteLocked<'_, T>> {
+        #[cfg(feature = "tokio")]
+        let guard = self.lock.try_write().ok();
+        #[cfg(not(feature = "tokio"))]
+        let guard = self.lock.try_write();
+
+        guard.map(|guard| SortAsyncWriteLocked {
+            guard,
+            held: HeldKey::new(self.sort_key(), LockAccess::Exclusive),
+            lock: self,
+        })
+    }
+
+    /// Locks this lock for reading, blocking the current thread until it is acquired.
+    ///
+    /// As only a single lock is acquired no sorting is required. This allows the lock to be used
+    /// from code that is not async.
+    ///
+    /// # Panicking
+    /// This method will panic if called from within a `tokio` runtime.
+    #[cfg(feature = "std")]
+    pub fn blocking_read(&self) -> SortAsyncReadLocked<'_, T> {
+        self.read().blocking_lock_all()
+    }
+
+    /// Locks this lock for writing, blocking the current thread until it is acquired.
+    ///
+    /// As only a single lock is acquired no sorting is required. This allows the lock to be used
+    /// from code that is not async.
+    ///
+    /// # Panicking
+    /// This method will panic if called from within a `tokio` runtime.
+    #[cfg(feature = "std")]
+    pub fn blocking_write(&self) -> SortAsyncWriteLocked<'_, T> {
+        self.write().blocking_lock_all()
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is the key used to order this lock when it is locked as part of a group. A lock
+    /// created with `const_new` is assigned its key on the first call to this method.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncRwLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SortAsyncRwLock");
+        d.field("key", &self.sort_key());
+
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+
+        d.finish()
+    }
+}
+
+impl <T: Default> Default for SortAsyncRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortAsyncRwLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A guard for reading a `SortAsyncRwLock`.
+pub struct SortAsyncReadGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortAsyncRwLock<T>,
+}
+
+impl <'l, T: ?Sized> AsyncSortableLock for SortAsyncReadGuard<'l, T> {
+    type Guard = SortAsyncReadLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        HeldKey::check(self.sort_key(), LockAccess::Shared);
+
+        SortAsyncReadLocked {
+            guard: self.lock.lock.read().await,
+            held: HeldKey::new(self.sort_key(), LockAccess::Shared),
+            lock: self.lock,
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.lock.try_read()
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <'l, T: ?Sized + Send + Sync> IntoFuture for SortAsyncReadGuard<'l, T> {
+    type Output = SortAsyncReadLocked<'l, T>;
+    type IntoFuture = LockFuture<'l, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.lock_presorted().await
+        })
+    }
+}
+
+/// A guard for writing to a `SortAsyncRwLock`.
+pub struct SortAsyncWriteGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortAsyncRwLock<T>,
+}
+
+impl <'l, T: ?Sized> AsyncSortableLock for SortAsyncWriteGuard<'l, T> {
+    type Guard = SortAsyncWriteLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        HeldKey::check(self.sort_key(), LockAccess::Exclusive);
+
+        SortAsyncWriteLocked {
+            guard: self.lock.lock.write().await,
+            held: HeldKey::new(self.sort_key(), LockAccess::Exclusive),
+            lock: self.lock,
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.lock.try_write()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <'l, T: ?Sized + Send + Sync> IntoFuture for SortAsyncWriteGuard<'l, T> {
+    type Output = SortAsyncWriteLocked<'l, T>;
+    type IntoFuture = LockFuture<'l, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.lock_presorted().await
+        })
+    }
+}
+
+/// An acquired read lock on a `SortAsyncRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortAsyncReadLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    guard: RwLockReadGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The lock this guard references.
+    lock: &'l SortAsyncRwLock<T>,
+}
+
+impl <'l, T: ?Sized> SortAsyncReadLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> MappedSortAsyncReadLocked<'l, U> {
+        let data = NonNull::from(f(&*this));
+
+        MappedSortAsyncReadLocked {
+            _guard: this.guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F) -> Result<MappedSortAsyncReadLocked<'l, U>, Self> {
+        match f(&*this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncReadLocked {
+                    _guard: this.guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for SortAsyncReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortAsyncReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired write lock on a `SortAsyncRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortAsyncWriteLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    guard: RwLockWriteGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The lock this guard references.
+    lock: &'l SortAsyncRwLock<T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortAsyncWriteLocked<'_, T> {}
+
+impl <'l, T: ?Sized> SortAsyncWriteLocked<'l, T> {
+    /// Converts this write lock into a read lock without releasing the lock.
+    ///
+    /// No other writer can acquire the lock between the write and the read so any changes made
+    /// while holding the write lock are guaranteed to be observed through the returned guard.
+    /// ```
+    /// use sortlock::{SortAsyncRwLock, AsyncLockGroup};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let lock = SortAsyncRwLock::new(1);
+    ///
+    /// let mut guard = lock.write().lock_all().await;
+    /// *guard += 1;
+    ///
+    /// let guard = guard.downgrade();
+    /// assert_eq!(2, *guard);
+    /// assert!(lock.try_read().is_some());
+    /// # });
+    /// ```
+    pub fn downgrade(self) -> SortAsyncReadLocked<'l, T> {
+        let Self { guard, held: _, lock } = self;
+
+        SortAsyncReadLocked {
+            guard: RwLockWriteGuard::downgrade(guard),
+            held: HeldKey::new(lock.sort_key(), LockAccess::Shared),
+            lock,
+        }
+    }
+
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortAsyncRwLock, SortAsyncWriteLocked, AsyncLockGroup};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let lock = SortAsyncRwLock::new((1, 2));
+    ///
+    /// let mut guard = SortAsyncWriteLocked::map(lock.write().lock_all().await, |value| &mut value.0);
+    /// *guard += 1;
+    /// drop(guard);
+    ///
+    /// assert_eq!((2, 2), *lock.read().lock_all().await);
+    /// # });
+    /// ```
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortAsyncWriteLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortAsyncWriteLocked {
+            _guard: this.guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortAsyncWriteLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncWriteLocked {
+                    _guard: this.guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for SortAsyncWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for SortAsyncWriteLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.held.touch();
+
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortAsyncWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortAsyncWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A read guard for a component of a value locked by a `SortAsyncRwLock`.
+///
+/// This is created with `SortAsyncReadLocked::map`. The lock is released when this guard is
+/// dropped.
+///
+/// Like `&T`, the guard borrows the locked value, so it cannot outlive the lock.
+/// ```compile_fail
+/// use sortlock::{SortAsyncRwLock, SortAsyncReadLocked, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let lock = SortAsyncRwLock::new((1, 2));
+/// let guard = SortAsyncReadLocked::map(lock.read().lock_all().await, |value| &value.0);
+/// drop(lock);
+/// assert_eq!(1, *guard);
+/// # });
+/// ```
+pub struct MappedSortAsyncReadLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    _guard: RwLockReadGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Ties the guard to the lifetime of the value it reads.
+    _marker: PhantomData<&'l T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Send for MappedSortAsyncReadLocked<'_, T> {}
+unsafe impl <T: ?Sized + Sync> Sync for MappedSortAsyncReadLocked<'_, T> {}
+
+impl <'l, T: ?Sized> MappedSortAsyncReadLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> MappedSortAsyncReadLocked<'l, U> {
+        let data = NonNull::from(f(&*this));
+
+        MappedSortAsyncReadLocked {
+            _guard: this._guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F) -> Result<MappedSortAsyncReadLocked<'l, U>, Self> {
+        match f(&*this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncReadLocked {
+                    _guard: this._guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for MappedSortAsyncReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for MappedSortAsyncReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for MappedSortAsyncReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A write guard for a component of a value locked by a `SortAsyncRwLock`.
+///
+/// This is created with `SortAsyncWriteLocked::map`. The lock is released when this guard is
+/// dropped.
+///
+/// Like `&mut T`, the guard is invariant over `T`, so it cannot be used to store a shorter lived
+/// reference in the locked value.
+/// ```compile_fail
+/// use sortlock::MappedSortAsyncWriteLocked;
+///
+/// fn shorten<'a, 'l>(guard: MappedSortAsyncWriteLocked<'l, &'static str>) -> MappedSortAsyncWriteLocked<'l, &'a str> {
+///     guard
+/// }
+/// ```
+pub struct MappedSortAsyncWriteLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    _guard: RwLockWriteGuard<'l, ()>,
+    /// Records that the current task holds the lock.
+    held: HeldKey,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Makes the guard invariant over `T`, as it gives mutable access to the value.
+    _marker: PhantomData<&'l mut T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for MappedSortAsyncWriteLocked<'_, T> {}
+unsafe impl <T: ?Sized + Sync> Sync for MappedSortAsyncWriteLocked<'_, T> {}
+
+impl <'l, T: ?Sized> MappedSortAsyncWriteLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortAsyncWriteLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortAsyncWriteLocked {
+            _guard: this._guard,
+            held: this.held,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortAsyncWriteLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortAsyncWriteLocked {
+                    _guard: this._guard,
+                    held: this.held,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for MappedSortAsyncWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.held.touch();
+
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for MappedSortAsyncWriteLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.held.touch();
+
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for MappedSortAsyncWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for MappedSortAsyncWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncLockGroup, MappedSortAsyncReadLocked, SortAsyncReadLocked, SortAsyncRwLock, SortAsyncWriteLocked};
+
+    #[tokio::test]
+    async fn test_async_read_write() {
+        let lock1 = SortAsyncRwLock::new(1);
+        let lock2 = SortAsyncRwLock::new(2);
+
+        let (guard1, guard2, mut guard3) = (lock1.read(), lock1.read(), lock2.write()).lock_all().await;
+        *guard3 += *guard1 + *guard2;
+        assert!(lock1.try_write().is_none());
+        assert!(lock2.try_read().is_none());
+        drop((guard1, guard2, guard3));
+
+        assert_eq!(4, *lock2.try_read().unwrap());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[tokio::test]
+    async fn test_await_request() {
+        let lock = SortAsyncRwLock::new(1);
+
+        *lock.write().await += 1;
+
+        let (guard1, guard2) = (lock.read().await, lock.read().await);
+        assert_eq!(4, *guard1 + *guard2);
+    }
+
+    #[tokio::test]
+    async fn test_async_map() {
+        let lock = SortAsyncRwLock::new((1, Some(2)));
+
+        let guard = SortAsyncReadLocked::map(lock.read().lock_all().await, |value| &value.1);
+        let guard = MappedSortAsyncReadLocked::try_map(guard, Option::as_ref).unwrap();
+        assert_eq!(2, *guard);
+        assert!(lock.try_write().is_none());
+        drop(guard);
+
+        let guard = SortAsyncWriteLocked::try_map(lock.write().lock_all().await, |_| None::<&mut i32>).unwrap_err();
+        let mut guard = SortAsyncWriteLocked::map(guard, |value| &mut value.0);
+        *guard += 1;
+        drop(guard);
+
+        assert_eq!((2, Some(2)), *lock.read().lock_all().await);
+    }
+
+    #[tokio::test]
+    async fn test_async_downgrade() {
+        let lock = SortAsyncRwLock::new(1);
+
+        let mut guard = lock.write().lock_all().await;
+        *guard += 1;
+
+        let guard = guard.downgrade();
+        assert_eq!(2, *guard);
+        assert!(lock.try_write().is_none());
+        assert_eq!(2, *lock.try_read().unwrap());
+        drop(guard);
+
+        assert!(lock.try_write().is_some());
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_async_conflicting_requests() {
+        let lock = SortAsyncRwLock::new(1);
+
+        let _guards = (lock.read(), lock.write()).lock_all().await;
+    }
+}
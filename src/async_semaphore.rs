@@ -0,0 +1,248 @@
+use core::{fmt::{self, Debug, Formatter}, mem, sync::atomic::{AtomicUsize, Ordering}};
+
+use crate::{AsyncSortableLock, SortAsyncCondvar, SortKey, key::LazySortKey};
+
+/// A sortable counting semaphore for async tasks.
+///
+/// A semaphore holds a number of permits. Acquiring takes permits from the semaphore, waiting
+/// until enough are available, and they are returned when the acquired guard is dropped.
+/// Acquiring permits can be sorted with other locks through the use of `lock_all` from
+/// `AsyncLockGroup`, which allows admission control alongside data locks.
+/// ```
+/// use sortlock::{SortAsyncMutex, SortAsyncSemaphore, AsyncLockGroup};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let semaphore = SortAsyncSemaphore::new(2);
+/// let lock = SortAsyncMutex::new(1);
+///
+/// let (permit, guard) = (semaphore.acquire(), lock.lock()).lock_all().await;
+/// assert_eq!(1, semaphore.available_permits());
+/// assert_eq!(1, *guard);
+/// # });
+/// ```
+///
+/// Multiple permits can be acquired at once with `acquire_many`. Permits are acquired all at once
+/// so a group should contain at most one request for each semaphore.
+pub struct SortAsyncSemaphore {
+    /// The number of available permits.
+    permits: AtomicUsize,
+    /// Notified when permits are released.
+    released: SortAsyncCondvar,
+    /// The sort key for this semaphore.
+    key: LazySortKey,
+}
+
+impl SortAsyncSemaphore {
+    /// Creates a new `SortAsyncSemaphore`.
+    ///
+    /// - `permits` - The number of permits initially available.
+    #[track_caller]
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            released: SortAsyncCondvar::new(),
+            key: LazySortKey::new(SortKey::new()),
+        }
+    }
+
+    /// Creates a new `SortAsyncSemaphore` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `permits` - The number of permits initially available.
+    pub const fn const_new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            released: SortAsyncCondvar::new(),
+            key: LazySortKey::unassigned(),
+        }
+    }
+
+    /// Requests to acquire a single permit.
+    pub fn acquire(&self) -> SortAsyncSemaphoreGuard<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Requests to acquire multiple permits at once.
+    ///
+    /// Locking waits until all of the permits are available at the same time.
+    ///
+    /// - `permits` - The number of permits to acquire.
+    pub fn acquire_many(&self, permits: usize) -> SortAsyncSemaphoreGuard<'_> {
+        SortAsyncSemaphoreGuard {
+            semaphore: self,
+            permits,
+        }
+    }
+
+    /// Attempts to acquire a single permit without waiting.
+    ///
+    /// As only a single semaphore is acquired no sorting is required.
+    pub fn try_acquire(&self) -> Option<SortAsyncSemaphoreLocked<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Attempts to acquire multiple permits without waiting.
+    ///
+    /// As only a single semaphore is acquired no sorting is required.
+    ///
+    /// - `permits` - The number of permits to acquire.
+    pub fn try_acquire_many(&self, permits: usize) -> Option<SortAsyncSemaphoreLocked<'_>> {
+        self.take(permits)
+            .then(|| SortAsyncSemaphoreLocked {
+                semaphore: self,
+                permits,
+            })
+    }
+
+    /// Gets the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Acquire)
+    }
+
+    /// Adds permits to this semaphore.
+    ///
+    /// - `permits` - The number of permits to add.
+    pub fn add_permits(&self, permits: usize) {
+        self.permits.fetch_add(permits, Ordering::Release);
+
+        self.released.notify_all();
+    }
+
+    /// Gets the sort key of this semaphore.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Takes permits if enough are available.
+    ///
+    /// Returns whether the permits were taken.
+    ///
+    /// - `permits` - The number of permits to take.
+    fn take(&self, permits: usize) -> bool {
+        self.permits.fetch_update(Ordering::Acquire, Ordering::Relaxed, |available| available.checked_sub(permits))
+            .is_ok()
+    }
+}
+
+impl Debug for SortAsyncSemaphore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortAsyncSemaphore")
+            .field("permits", &self.available_permits())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard for a `SortAsyncSemaphore`.
+///
+/// This requests a number of permits from the semaphore.
+pub struct SortAsyncSemaphoreGuard<'l> {
+    /// The semaphore this guard references.
+    semaphore: &'l SortAsyncSemaphore,
+    /// The number of permits to acquire.
+    permits: usize,
+}
+
+impl <'l> AsyncSortableLock for SortAsyncSemaphoreGuard<'l> {
+    type Guard = SortAsyncSemaphoreLocked<'l>;
+
+    fn sort_key(&self) -> SortKey {
+        self.semaphore.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        self.semaphore.released.wait_until(|| self.semaphore.take(self.permits)).await;
+
+        SortAsyncSemaphoreLocked {
+            semaphore: self.semaphore,
+            permits: self.permits,
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.semaphore.try_acquire_many(self.permits)
+    }
+}
+
+/// Permits acquired from a `SortAsyncSemaphore`.
+///
+/// The permits are returned to the semaphore when this guard is dropped.
+pub struct SortAsyncSemaphoreLocked<'l> {
+    /// The semaphore the permits were acquired from.
+    semaphore: &'l SortAsyncSemaphore,
+    /// The number of permits held.
+    permits: usize,
+}
+
+impl SortAsyncSemaphoreLocked<'_> {
+    /// Gets the number of permits held by this guard.
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permits held by this guard so they are never returned to the semaphore.
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for SortAsyncSemaphoreLocked<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+impl Debug for SortAsyncSemaphoreLocked<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortAsyncSemaphoreLocked")
+            .field("permits", &self.permits)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+
+    use crate::{AsyncLockGroup, SortAsyncMutex, SortAsyncSemaphore};
+
+    #[tokio::test]
+    async fn test_acquire_many() {
+        let semaphore = SortAsyncSemaphore::new(3);
+        let lock = SortAsyncMutex::new(1);
+
+        let (guard, permits) = (lock.lock(), semaphore.acquire_many(3)).lock_all().await;
+        assert_eq!(1, *guard);
+        assert_eq!(3, permits.permits());
+        assert!(semaphore.try_acquire().is_none());
+
+        drop(permits);
+        assert_eq!(3, semaphore.available_permits());
+    }
+
+    #[tokio::test]
+    async fn test_limit() {
+        let semaphore = Arc::new(SortAsyncSemaphore::new(2));
+        let active = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8).map(|i| {
+            let semaphore = semaphore.clone();
+            let active = active.clone();
+
+            tokio::spawn(async move {
+                for _ in 0..50 {
+                    let permits = semaphore.acquire_many(1 + i % 2).lock_all().await;
+                    assert!(active.fetch_add(permits.permits(), Ordering::SeqCst) + permits.permits() <= 2);
+                    tokio::task::yield_now().await;
+                    active.fetch_sub(permits.permits(), Ordering::SeqCst);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(2, semaphore.available_permits());
+    }
+}
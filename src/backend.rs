@@ -0,0 +1,360 @@
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+#[cfg(feature = "parking_lot")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "critical-section")]
+use core::{cell::Cell, marker::PhantomData, sync::atomic::{AtomicBool, Ordering}};
+
+#[cfg(feature = "critical-section")]
+use critical_section::{CriticalSection, RestoreState};
+
+/// The internal lock used by a `SortMutex`.
+///
+/// A backend is selected for each lock through the type parameter of `SortMutex`, so locks with
+/// different backends can be used in the same program and sorted together.
+/// ```
+/// use sortlock::{LockGroup, SortMutex, SpinBackend};
+///
+/// let hot = SortMutex::with_backend(1, SpinBackend::new());
+/// let cold = SortMutex::new(2);
+///
+/// let (guard1, guard2) = (hot.lock(), cold.lock()).lock_all();
+/// assert_eq!(3, *guard1 + *guard2);
+/// ```
+///
+/// # Safety
+/// While a guard returned by `lock` or `try_lock` exists no other guard may be returned for the
+/// same backend. Guards must be safe to share between threads.
+pub unsafe trait MutexBackend {
+    /// The guard that holds the lock until it is dropped.
+    type Guard<'l> where Self: 'l;
+
+    /// Blocks until the lock is acquired.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Attempts to acquire the lock without blocking.
+    ///
+    /// Returns `None` if the lock is held elsewhere.
+    fn try_lock(&self) -> Option<Self::Guard<'_>>;
+
+    /// Checks whether the lock is poisoned, in which case locking it panics.
+    ///
+    /// This allows the `SortMutex` to report which lock is poisoned. By default backends are
+    /// never poisoned.
+    fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Blocks until the lock is acquired, even if it is poisoned.
+    ///
+    /// This is used to reacquire a lock that must not be left released, such as when a closure
+    /// passed to `unlocked` panics. By default this is the same as `lock`.
+    fn lock_ignoring_poison(&self) -> Self::Guard<'_> {
+        self.lock()
+    }
+}
+
+pub(crate) mod private {
+    /// Prevents `PoisonBackend` from being implemented outside this crate.
+    pub trait Sealed {}
+}
+
+/// A backend whose locks are poisoned if a thread panics while holding them.
+///
+/// This is implemented for `StdBackend` and `FutexBackend`, and allows the poisoned state of a
+/// `SortMutex` to be checked and cleared.
+pub trait PoisonBackend: MutexBackend + private::Sealed {
+    /// Clears the poisoned state of the lock.
+    fn clear_poison(&self);
+}
+
+/// The backend used by `SortMutex` when none is given.
+///
+/// This is `FutexBackend` on Linux on x86_64 and aarch64, `StdBackend` elsewhere when the `std`
+/// feature is enabled and `SpinBackend` otherwise. With the `std` feature locks using the default
+/// backend can always be poisoned and used with `SortCondvar`.
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub type DefaultBackend = crate::FutexBackend;
+
+/// The backend used by `SortMutex` when none is given.
+///
+/// This is `FutexBackend` on Linux on x86_64 and aarch64, `StdBackend` elsewhere when the `std`
+/// feature is enabled and `SpinBackend` otherwise. With the `std` feature locks using the default
+/// backend can always be poisoned and used with `SortCondvar`.
+#[cfg(all(feature = "std", not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))))]
+pub type DefaultBackend = StdBackend;
+
+/// The backend used by `SortMutex` when none is given.
+///
+/// This is `FutexBackend` on Linux on x86_64 and aarch64, `StdBackend` elsewhere when the `std`
+/// feature is enabled and `SpinBackend` otherwise.
+#[cfg(not(feature = "std"))]
+pub type DefaultBackend = SpinBackend;
+
+/// A backend using the standard library's `Mutex`.
+///
+/// Locks using this backend are poisoned if a thread panics while holding them.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct StdBackend(Mutex<()>);
+
+#[cfg(feature = "std")]
+impl StdBackend {
+    /// Creates a new unlocked `StdBackend`.
+    pub const fn new() -> Self {
+        Self(Mutex::new(()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl private::Sealed for StdBackend {}
+
+#[cfg(feature = "std")]
+impl PoisonBackend for StdBackend {
+    fn clear_poison(&self) {
+        self.0.clear_poison();
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl MutexBackend for StdBackend {
+    type Guard<'l> = MutexGuard<'l, ()>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock()
+            .expect("Failed to lock mutex: mutex is poisoned.")
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        match self.0.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(_)) => panic!("Failed to lock mutex: mutex is poisoned."),
+        }
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.0.is_poisoned()
+    }
+
+    fn lock_ignoring_poison(&self) -> Self::Guard<'_> {
+        self.0.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A backend using `spin`'s `Mutex`, which busy-waits instead of blocking.
+///
+/// This is available without the `std` feature and is never poisoned.
+#[derive(Debug, Default)]
+pub struct SpinBackend(spin::Mutex<()>);
+
+impl SpinBackend {
+    /// Creates a new unlocked `SpinBackend`.
+    pub const fn new() -> Self {
+        Self(spin::Mutex::new(()))
+    }
+}
+
+unsafe impl MutexBackend for SpinBackend {
+    type Guard<'l> = spin::MutexGuard<'l, ()>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock()
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.0.try_lock()
+    }
+}
+
+/// A backend using `parking_lot`'s `Mutex`.
+///
+/// This is smaller and faster than `StdBackend`, is never poisoned and allows locks to be
+/// acquired with a timeout through `SortMutex::try_lock_for`.
+///
+/// `SortRwLock` has no backends, as its poisoning and recursive and upgradable reads are built on
+/// its own internal locks. A `parking_lot` reader-writer lock can be sorted instead by wrapping
+/// its raw lock with `Sorted`.
+/// ```
+/// use sortlock::{LockGroup, ParkingLotBackend, SortMutex, Sorted};
+///
+/// type RwLock<T> = lock_api::RwLock<Sorted<parking_lot::RawRwLock>, T>;
+///
+/// let lock1 = SortMutex::with_backend(1, ParkingLotBackend::new());
+/// let lock2: RwLock<i32> = RwLock::new(2);
+///
+/// let (mut guard1, guard2) = (lock1.lock(), Sorted::write(&lock2)).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+#[cfg(feature = "parking_lot")]
+#[derive(Debug, Default)]
+pub struct ParkingLotBackend(parking_lot::Mutex<()>);
+
+#[cfg(feature = "parking_lot")]
+impl ParkingLotBackend {
+    /// Creates a new unlocked `ParkingLotBackend`.
+    pub const fn new() -> Self {
+        Self(parking_lot::Mutex::new(()))
+    }
+
+    /// Attempts to acquire the lock, giving up after a timeout.
+    ///
+    /// - `timeout` - The longest time to wait for.
+    pub(crate) fn try_lock_for(&self, timeout: Duration) -> Option<parking_lot::MutexGuard<'_, ()>> {
+        self.0.try_lock_for(timeout)
+    }
+
+    /// Attempts to acquire the lock, giving up at a deadline.
+    ///
+    /// - `deadline` - The time to stop waiting at.
+    pub(crate) fn try_lock_until(&self, deadline: Instant) -> Option<parking_lot::MutexGuard<'_, ()>> {
+        self.0.try_lock_until(deadline)
+    }
+
+    /// Unlocks the lock fairly, passing it directly to a waiting thread if there is one.
+    ///
+    /// - `guard` - The guard for the lock.
+    pub(crate) fn unlock_fair(guard: parking_lot::MutexGuard<'_, ()>) {
+        parking_lot::MutexGuard::unlock_fair(guard);
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+unsafe impl MutexBackend for ParkingLotBackend {
+    type Guard<'l> = parking_lot::MutexGuard<'l, ()>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock()
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.0.try_lock()
+    }
+}
+
+/// The number of critical sections entered by guards and the state to restore once all are left.
+#[cfg(feature = "critical-section")]
+static NESTING: critical_section::Mutex<Cell<(usize, RestoreState)>> = critical_section::Mutex::new(Cell::new((0, RestoreState::invalid())));
+
+/// A backend for bare-metal targets using the `critical-section` crate.
+///
+/// A critical section is held for as long as any lock using this backend is held, so on a
+/// microcontroller interrupts are disabled until every guard is dropped. This makes locks safe to
+/// share with interrupt handlers. Guards may be dropped in any order.
+///
+/// As nothing else can run while a lock is held, locking a lock that is already held by the
+/// current context would never finish and panics instead.
+#[cfg(feature = "critical-section")]
+#[derive(Debug, Default)]
+pub struct CriticalSectionBackend {
+    /// Whether the lock is held.
+    locked: AtomicBool,
+}
+
+#[cfg(feature = "critical-section")]
+impl CriticalSectionBackend {
+    /// Creates a new unlocked `CriticalSectionBackend`.
+    pub const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Enters a critical section that is left by calling `exit`.
+    fn enter() {
+        // SAFETY: The critical section is released by `exit`, or immediately if already held.
+        let state = unsafe { critical_section::acquire() };
+        // SAFETY: A critical section is held.
+        let nesting = NESTING.borrow(unsafe { CriticalSection::new() });
+
+        match nesting.get() {
+            (0, _) => nesting.set((1, state)),
+            (depth, outer) => {
+                nesting.set((depth + 1, outer));
+
+                // SAFETY: This nested critical section was the last to be acquired and the outer
+                // critical section is still held.
+                unsafe { critical_section::release(state) };
+            },
+        }
+    }
+
+    /// Leaves a critical section entered by `enter`.
+    fn exit() {
+        // SAFETY: A critical section entered by `enter` is held.
+        let nesting = NESTING.borrow(unsafe { CriticalSection::new() });
+        let (depth, outer) = nesting.get();
+
+        if depth == 1 {
+            nesting.set((0, RestoreState::invalid()));
+
+            // SAFETY: This is the last guard so the outermost critical section is released.
+            unsafe { critical_section::release(outer) };
+        } else {
+            nesting.set((depth - 1, outer));
+        }
+    }
+
+    /// Marks the lock as held while in a critical section.
+    ///
+    /// Returns `None` and leaves the critical section if the lock is already held.
+    fn try_lock_raw(&self) -> Option<CriticalSectionBackendGuard<'_>> {
+        Self::enter();
+
+        if self.locked.load(Ordering::Acquire) {
+            Self::exit();
+
+            return None;
+        }
+
+        self.locked.store(true, Ordering::Relaxed);
+
+        Some(CriticalSectionBackendGuard {
+            backend: self,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "critical-section")]
+unsafe impl MutexBackend for CriticalSectionBackend {
+    type Guard<'l> = CriticalSectionBackendGuard<'l>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.try_lock_raw()
+            .expect("Failed to lock mutex: the lock is already held by the current context.")
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.try_lock_raw()
+    }
+}
+
+/// A held lock on a `CriticalSectionBackend`.
+///
+/// The lock is released when this guard is dropped, and the critical section is left once every
+/// guard has been dropped.
+#[cfg(feature = "critical-section")]
+#[derive(Debug)]
+pub struct CriticalSectionBackendGuard<'l> {
+    /// The backend this guard references.
+    backend: &'l CriticalSectionBackend,
+    /// Prevents the guard from leaving the context that entered the critical section.
+    _not_send: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "critical-section")]
+unsafe impl Sync for CriticalSectionBackendGuard<'_> {}
+
+#[cfg(feature = "critical-section")]
+impl Drop for CriticalSectionBackendGuard<'_> {
+    fn drop(&mut self) {
+        self.backend.locked.store(false, Ordering::Release);
+
+        CriticalSectionBackend::exit();
+    }
+}
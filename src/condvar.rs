@@ -0,0 +1,307 @@
+use core::{fmt::{self, Debug, Formatter}, time::Duration};
+
+use std::{sync::{Condvar, Mutex, MutexGuard, PoisonError, WaitTimeoutResult}, time::Instant};
+
+use crate::{MutexBackend, SortMutexLocked};
+
+/// A condition variable that can be used with a `SortMutex`.
+/// This is a sortable version of rust's `Condvar` type.
+///
+/// Waiting consumes an acquired guard and returns it once the lock has been reacquired. As only
+/// a single lock is reacquired no sorting is required.
+/// ```
+/// use std::{sync::Arc, thread};
+/// use sortlock::{SortCondvar, SortMutex, LockGroup};
+///
+/// let pair = Arc::new((SortMutex::new(false), SortCondvar::new()));
+/// let pair2 = pair.clone();
+///
+/// thread::spawn(move || {
+///     let (lock, condvar) = &*pair2;
+///
+///     *lock.lock().lock_all() = true;
+///     condvar.notify_one();
+/// });
+///
+/// let (lock, condvar) = &*pair;
+///
+/// let guard = condvar.wait_while(lock.lock().lock_all(), |ready| !*ready);
+/// assert!(*guard);
+/// ```
+///
+/// Only the lock of the given guard is released while waiting. Any other locks held by the
+/// thread, such as the rest of a group, remain locked.
+///
+/// A condition variable can be used with locks using any backend.
+pub struct SortCondvar {
+    /// The internal mutex, which is held from before a lock is released until its thread is
+    /// waiting so a notification cannot be missed.
+    mutex: Mutex<()>,
+    /// The internal condition variable.
+    condvar: Condvar,
+}
+
+impl SortCondvar {
+    /// Creates a new `SortCondvar`.
+    pub const fn new() -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Blocks the current thread until this condition variable is notified.
+    ///
+    /// The lock is released while waiting and reacquired before returning. Spurious wakeups are
+    /// possible so this should usually be called in a loop or replaced with `wait_while`.
+    ///
+    /// - `guard` - The guard to release while waiting.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock becomes poisoned.
+    ///
+    /// # Deadlocks
+    /// The lock is reacquired on its own without sorting, so waiting while the thread holds other
+    /// locks, such as the rest of a group, can deadlock with a thread locking them together in
+    /// sorted order. The same applies to every other wait method.
+    pub fn wait<'l, T: ?Sized, B: MutexBackend>(&self, mut guard: SortMutexLocked<'l, T, B>) -> SortMutexLocked<'l, T, B> {
+        let waiting = self.lock_internal();
+
+        SortMutexLocked::unlocked(&mut guard, || {
+            drop(self.condvar.wait(waiting).unwrap_or_else(PoisonError::into_inner));
+        });
+
+        guard
+    }
+
+    /// Blocks the current thread until this condition variable is notified and the condition
+    /// returns `false`.
+    ///
+    /// The condition is checked before waiting and after every wakeup while the lock is held.
+    /// ```
+    /// use sortlock::{SortCondvar, SortMutex, LockGroup};
+    ///
+    /// let lock = SortMutex::new(1);
+    /// let condvar = SortCondvar::new();
+    ///
+    /// // The condition is already false so this does not block.
+    /// let guard = condvar.wait_while(lock.lock().lock_all(), |value| *value == 0);
+    /// assert_eq!(1, *guard);
+    /// ```
+    ///
+    /// - `guard` - The guard to release while waiting.
+    /// - `condition` - The condition to wait on.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock becomes poisoned.
+    pub fn wait_while<'l, T: ?Sized, B: MutexBackend, F: FnMut(&mut T) -> bool>(&self, mut guard: SortMutexLocked<'l, T, B>, mut condition: F) -> SortMutexLocked<'l, T, B> {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+
+        guard
+    }
+
+    /// Blocks the current thread until this condition variable is notified or the timeout
+    /// elapses.
+    ///
+    /// The lock is released while waiting and reacquired before returning.
+    ///
+    /// - `guard` - The guard to release while waiting.
+    /// - `timeout` - The maximum time to wait.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock becomes poisoned.
+    pub fn wait_timeout<'l, T: ?Sized, B: MutexBackend>(&self, mut guard: SortMutexLocked<'l, T, B>, timeout: Duration) -> (SortMutexLocked<'l, T, B>, WaitTimeoutResult) {
+        let waiting = self.lock_internal();
+
+        let result = SortMutexLocked::unlocked(&mut guard, || {
+            self.condvar.wait_timeout(waiting, timeout)
+                .unwrap_or_else(PoisonError::into_inner)
+                .1
+        });
+
+        (guard, result)
+    }
+
+    /// Blocks the current thread until the condition returns `false` or the timeout elapses.
+    ///
+    /// The returned `WaitTimeoutResult` reports a timeout only if the condition was still `true`
+    /// when the timeout elapsed.
+    /// ```
+    /// use std::time::Duration;
+    /// use sortlock::{SortCondvar, SortMutex, LockGroup};
+    ///
+    /// let lock = SortMutex::new(0);
+    /// let condvar = SortCondvar::new();
+    ///
+    /// let (guard, result) = condvar.wait_timeout_while(lock.lock().lock_all(), Duration::from_millis(10), |value| *value == 0);
+    /// assert!(result.timed_out());
+    /// assert_eq!(0, *guard);
+    /// ```
+    ///
+    /// - `guard` - The guard to release while waiting.
+    /// - `timeout` - The maximum time to wait.
+    /// - `condition` - The condition to wait on.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock becomes poisoned.
+    pub fn wait_timeout_while<'l, T: ?Sized, B: MutexBackend, F: FnMut(&mut T) -> bool>(&self, mut guard: SortMutexLocked<'l, T, B>, timeout: Duration, mut condition: F) -> (SortMutexLocked<'l, T, B>, WaitTimeoutResult) {
+        let start = Instant::now();
+
+        loop {
+            if !condition(&mut *guard) {
+                return (guard, timeout_result(false));
+            }
+
+            let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+                return (guard, timeout_result(true));
+            };
+
+            guard = self.wait_timeout(guard, remaining).0;
+        }
+    }
+
+    /// Wakes up one thread blocked on this condition variable.
+    pub fn notify_one(&self) {
+        drop(self.lock_internal());
+
+        self.condvar.notify_one();
+    }
+
+    /// Wakes up all threads blocked on this condition variable.
+    pub fn notify_all(&self) {
+        drop(self.lock_internal());
+
+        self.condvar.notify_all();
+    }
+
+    /// Locks the internal mutex.
+    ///
+    /// A waiting thread holds this until it is waiting and a notifying thread acquires it before
+    /// notifying, so a thread that has released its lock but is not yet waiting is not missed.
+    /// No code that can panic runs while it is held so it is never poisoned.
+    fn lock_internal(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for SortCondvar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortCondvar").finish_non_exhaustive()
+    }
+}
+
+impl Default for SortCondvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates a `WaitTimeoutResult`, which can only be created by waiting on a `Condvar`.
+///
+/// This waits on a new condition variable with a condition that returns `timed_out` and no time
+/// left, which returns without blocking.
+///
+/// - `timed_out` - Whether the result reports a timeout.
+fn timeout_result(timed_out: bool) -> WaitTimeoutResult {
+    let mutex = Mutex::new(());
+    let guard = mutex.lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    let (guard, result) = Condvar::new().wait_timeout_while(guard, Duration::ZERO, |_| timed_out)
+        .unwrap_or_else(PoisonError::into_inner);
+    drop(guard);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use crate::{LockGroup, SortCondvar, SortMutex, SpinBackend, StdBackend};
+
+    #[test]
+    fn test_wait() {
+        let pair = Arc::new((SortMutex::new(0), SortCondvar::new()));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let pair = pair.clone();
+
+            thread::spawn(move || {
+                let (lock, condvar) = &*pair;
+
+                *lock.lock().lock_all() += 1;
+                condvar.notify_all();
+            })
+        }).collect();
+
+        let (lock, condvar) = &*pair;
+
+        let mut guard = lock.lock().lock_all();
+        while *guard < 4 {
+            guard = condvar.wait(guard);
+        }
+        drop(guard);
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let lock = SortMutex::new(0);
+        let condvar = SortCondvar::new();
+
+        let (mut guard, result) = condvar.wait_timeout(lock.lock().lock_all(), Duration::from_millis(1));
+        assert!(result.timed_out());
+        *guard += 1;
+        drop(guard);
+
+        assert_eq!(1, *lock.lock().lock_all());
+    }
+
+    #[test]
+    fn test_wait_timeout_while() {
+        let pair = Arc::new((SortMutex::new(false), SortCondvar::new()));
+        let pair2 = pair.clone();
+
+        let handle = thread::spawn(move || {
+            let (lock, condvar) = &*pair2;
+
+            *lock.lock().lock_all() = true;
+            condvar.notify_one();
+        });
+
+        let (lock, condvar) = &*pair;
+
+        let (guard, result) = condvar.wait_timeout_while(lock.lock().lock_all(), Duration::from_secs(60), |ready| !*ready);
+        assert!(!result.timed_out());
+        assert!(*guard);
+        drop(guard);
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_backends() {
+        let spin = Arc::new((SortMutex::with_backend(false, SpinBackend::new()), SortCondvar::new()));
+        let std = Arc::new((SortMutex::with_backend(false, StdBackend::new()), SortCondvar::new()));
+        let (spin2, std2) = (spin.clone(), std.clone());
+
+        let handle = thread::spawn(move || {
+            *spin2.0.lock().lock_all() = true;
+            spin2.1.notify_all();
+            *std2.0.lock().lock_all() = true;
+            std2.1.notify_all();
+        });
+
+        assert!(*spin.1.wait_while(spin.0.lock().lock_all(), |ready| !*ready));
+        assert!(*std.1.wait_while(std.0.lock().lock_all(), |ready| !*ready));
+
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use core::{any::Any, ops::{Deref, DerefMut}};
+
+use alloc::boxed::Box;
+
+use crate::{LockAccess, SortKey, SortableLock};
+
+/// An object safe version of `SortableLock` whose guard is boxed as `G`.
+///
+/// This allows requests for different types of lock to be stored together, for example in a
+/// `Vec<Box<dyn DynSortableLock<G>>>`, and still be locked in sorted order. Every `SortableLock`
+/// implements this trait for the following guard types:
+/// - `dyn Deref<Target = T>` if its guard dereferences to `T`.
+/// - `dyn DerefMut<Target = T>` if its guard mutably dereferences to `T`.
+/// - `dyn Any` if its guard is `'static`.
+/// ```
+/// use std::ops::DerefMut;
+/// use sortlock::{DynSortableLock, LockGroup, SortMutex, SortRwLock};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortRwLock::new(2);
+///
+/// let requests: Vec<Box<dyn DynSortableLock<dyn DerefMut<Target = i32>>>> = vec![
+///     Box::new(lock2.write()),
+///     Box::new(lock1.lock()),
+/// ];
+///
+/// let mut guards = requests.lock_all();
+/// **guards[0] += 1;
+/// assert_eq!(4, **guards[0] + **guards[1]);
+/// ```
+pub trait DynSortableLock<G: ?Sized> {
+    /// Gets the sort key of the lock.
+    fn sort_key(&self) -> SortKey;
+
+    /// Lock this lock, returning a boxed guard.
+    ///
+    /// This method assumes that lock sorting has already been done.
+    fn lock_presorted_boxed(&self) -> Box<G>;
+
+    /// Gets the kind of access this request has to its lock.
+    fn access(&self) -> LockAccess;
+}
+
+impl <'l, T: ?Sized, L: SortableLock> DynSortableLock<dyn Deref<Target = T> + 'l> for L where L::Guard: Deref<Target = T> + 'l {
+    fn sort_key(&self) -> SortKey {
+        SortableLock::sort_key(self)
+    }
+
+    fn access(&self) -> LockAccess {
+        SortableLock::access(self)
+    }
+
+    fn lock_presorted_boxed(&self) -> Box<dyn Deref<Target = T> + 'l> {
+        Box::new(self.lock_presorted())
+    }
+}
+
+impl <'l, T: ?Sized, L: SortableLock> DynSortableLock<dyn DerefMut<Target = T> + 'l> for L where L::Guard: DerefMut<Target = T> + 'l {
+    fn sort_key(&self) -> SortKey {
+        SortableLock::sort_key(self)
+    }
+
+    fn access(&self) -> LockAccess {
+        SortableLock::access(self)
+    }
+
+    fn lock_presorted_boxed(&self) -> Box<dyn DerefMut<Target = T> + 'l> {
+        Box::new(self.lock_presorted())
+    }
+}
+
+impl <L: SortableLock> DynSortableLock<dyn Any> for L where L::Guard: 'static {
+    fn sort_key(&self) -> SortKey {
+        SortableLock::sort_key(self)
+    }
+
+    fn access(&self) -> LockAccess {
+        SortableLock::access(self)
+    }
+
+    fn lock_presorted_boxed(&self) -> Box<dyn Any> {
+        Box::new(self.lock_presorted())
+    }
+}
+
+impl <G: ?Sized> SortableLock for Box<dyn DynSortableLock<G> + '_> {
+    type Guard = Box<G>;
+
+    fn sort_key(&self) -> SortKey {
+        (**self).sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        (**self).lock_presorted_boxed()
+    }
+
+    fn access(&self) -> LockAccess {
+        DynSortableLock::access(&**self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::Any, boxed::Box, sync::Arc, vec, vec::Vec};
+
+    use crate::{DynSortableLock, LockGroup, OwnedSortMutexLocked, SortMutex, SortRwLock};
+
+    #[test]
+    fn test_dyn_any() {
+        let lock1 = Arc::new(SortMutex::new(1));
+        let lock2 = Arc::new(SortRwLock::new("value"));
+
+        let requests: Vec<Box<dyn DynSortableLock<dyn Any>>> = vec![
+            Box::new(lock2.clone().read_owned_request()),
+            Box::new(lock1.clone().lock_owned_request()),
+        ];
+
+        let mut guards = requests.lock_all();
+        let guard = guards[1].downcast_mut::<OwnedSortMutexLocked<i32>>().unwrap();
+        **guard += 1;
+        drop(guards);
+
+        assert_eq!(2, *lock1.lock().lock_all());
+        assert!(lock2.try_write().is_some());
+    }
+}
@@ -0,0 +1,233 @@
+use core::{fmt::{self, Debug, Display, Formatter}, ops::{Deref, DerefMut}};
+
+use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::{Mutex, MutexGuard}};
+
+use crate::{AsyncSortableLock, SortKey, key::LazySortKey};
+
+/// A sortable async lock built on `embassy-sync`'s `Mutex` for embedded firmware.
+///
+/// The raw mutex `M` decides how the lock may be shared, as with `embassy-sync`. Requests can be
+/// locked in a group with `lock_all` from `AsyncLockGroup` alongside any other async lock.
+/// ```
+/// use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+/// use sortlock::{SortEmbassyMutex, AsyncLockGroup};
+///
+/// static LOCK1: SortEmbassyMutex<CriticalSectionRawMutex, u32> = SortEmbassyMutex::const_new(1);
+/// static LOCK2: SortEmbassyMutex<CriticalSectionRawMutex, u32> = SortEmbassyMutex::const_new(2);
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let (mut guard2, guard1) = (LOCK2.lock(), LOCK1.lock()).lock_all().await;
+/// *guard2 += *guard1;
+/// assert_eq!(3, *guard2);
+/// # });
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct SortEmbassyMutex<M: RawMutex, T: ?Sized> {
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The internal mutex.
+    mutex: Mutex<M, T>,
+}
+
+impl <M: RawMutex, T> SortEmbassyMutex<M, T> {
+    /// Creates a new `SortEmbassyMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            key: LazySortKey::new(SortKey::new()),
+            mutex: Mutex::new(value),
+        }
+    }
+
+    /// Creates a new `SortEmbassyMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used rather than when it is created. This
+    /// allows the lock to be placed in a `static`.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            key: LazySortKey::unassigned(),
+            mutex: Mutex::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl <M: RawMutex, T: ?Sized> SortEmbassyMutex<M, T> {
+    /// Requests to lock this lock.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    pub fn lock(&self) -> SortEmbassyMutexGuard<'_, M, T> {
+        SortEmbassyMutexGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock without waiting.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently held elsewhere.
+    pub fn try_lock(&self) -> Option<SortEmbassyMutexLocked<'_, M, T>> {
+        self.mutex.try_lock()
+            .ok()
+            .map(|guard| SortEmbassyMutexLocked {
+                guard
+            })
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is the key used to order this lock when it is locked as part of a group. A lock
+    /// created with `const_new` is assigned its key on the first call to this method.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+}
+
+impl <M: RawMutex, T: ?Sized + Debug> Debug for SortEmbassyMutex<M, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortEmbassyMutex")
+            .field("key", &self.sort_key())
+            .field("mutex", &&self.mutex)
+            .finish()
+    }
+}
+
+impl <M: RawMutex, T: Default> Default for SortEmbassyMutex<M, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <M: RawMutex, T> From<T> for SortEmbassyMutex<M, T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A reference to a `SortEmbassyMutex` is a request to lock it.
+impl <'l, M: RawMutex, T: ?Sized> AsyncSortableLock for &'l SortEmbassyMutex<M, T> {
+    type Guard = SortEmbassyMutexLocked<'l, M, T>;
+
+    fn sort_key(&self) -> SortKey {
+        SortEmbassyMutex::sort_key(self)
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        SortEmbassyMutexLocked {
+            guard: self.mutex.lock().await
+        }
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        SortEmbassyMutex::try_lock(self)
+    }
+}
+
+/// A guard for a `SortEmbassyMutex`.
+pub struct SortEmbassyMutexGuard<'l, M: RawMutex, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortEmbassyMutex<M, T>,
+}
+
+impl <'l, M: RawMutex, T: ?Sized> AsyncSortableLock for SortEmbassyMutexGuard<'l, M, T> {
+    type Guard = SortEmbassyMutexLocked<'l, M, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    async fn lock_presorted(&self) -> Self::Guard {
+        self.lock.lock_presorted().await
+    }
+
+    fn try_lock_presorted(&self) -> Option<Self::Guard> {
+        self.lock.try_lock()
+    }
+}
+
+/// An acquired lock on a `SortEmbassyMutex`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortEmbassyMutexLocked<'l, M: RawMutex, T: ?Sized> {
+    /// The guard for the internal mutex.
+    guard: MutexGuard<'l, M, T>,
+}
+
+impl <M: RawMutex, T: ?Sized> Deref for SortEmbassyMutexLocked<'_, M, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl <M: RawMutex, T: ?Sized> DerefMut for SortEmbassyMutexLocked<'_, M, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl <M: RawMutex, T: ?Sized + Debug> Debug for SortEmbassyMutexLocked<'_, M, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <M: RawMutex, T: ?Sized + Display> Display for SortEmbassyMutexLocked<'_, M, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    use crate::{AsyncLockGroup, SortEmbassyMutex};
+
+    #[tokio::test]
+    async fn test_embassy_group() {
+        let lock1 = SortEmbassyMutex::<NoopRawMutex, _>::new(1);
+        let lock2 = SortEmbassyMutex::<NoopRawMutex, _>::new(2);
+
+        let (guard2, guard1) = (lock2.lock(), &lock1).lock_all().await;
+        assert_eq!(3, *guard1 + *guard2);
+        assert!(lock1.try_lock().is_none());
+        assert!((lock1.lock(), lock2.lock()).try_lock_all().is_none());
+
+        drop((guard1, guard2));
+        assert!((lock1.lock(), lock2.lock()).try_lock_all().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_embassy_cancelled() {
+        let lock1 = SortEmbassyMutex::<CriticalSectionRawMutex, _>::new(1);
+        let lock2 = SortEmbassyMutex::<CriticalSectionRawMutex, _>::new(2);
+
+        let held = lock2.try_lock().unwrap();
+
+        tokio::select! {
+            biased;
+            _ = (lock1.lock(), lock2.lock()).lock_all() => panic!("locked a held lock"),
+            _ = tokio::task::yield_now() => {},
+        }
+
+        assert!(lock1.try_lock().is_some());
+        drop(held);
+    }
+}
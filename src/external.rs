@@ -0,0 +1,161 @@
+use core::{fmt::{self, Debug, Formatter}, mem::ManuallyDrop, ops::Deref};
+
+use crate::{SortKey, SortableLock};
+
+/// A sortable lock on a resource managed outside this crate.
+///
+/// The resource is locked and unlocked by calling the given closures, which allows resources such
+/// as database advisory locks or handles from C libraries to be locked in a group with other
+/// locks. The acquire closure returns a handle that is passed to the release closure when the
+/// lock is released.
+///
+/// The sort key must be unique to the resource, and every `SortableExternal` for the same resource
+/// must use the same key.
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use sortlock::{SortableExternal, SortKey, SortMutex, LockGroup};
+///
+/// static HELD: AtomicBool = AtomicBool::new(false);
+///
+/// let external = SortableExternal::new(
+///     SortKey::new(),
+///     || HELD.store(true, Ordering::Relaxed),
+///     |()| HELD.store(false, Ordering::Relaxed),
+/// );
+/// let lock = SortMutex::new(1);
+///
+/// let (guard1, guard2) = (external.lock(), lock.lock()).lock_all();
+/// assert!(HELD.load(Ordering::Relaxed));
+/// drop((guard1, guard2));
+/// assert!(!HELD.load(Ordering::Relaxed));
+/// ```
+pub struct SortableExternal<A, R> {
+    /// The closure that acquires the resource.
+    acquire: A,
+    /// The closure that releases the resource.
+    release: R,
+    /// The sort key for this resource.
+    key: SortKey,
+}
+
+impl <H, A: Fn() -> H, R: Fn(H)> SortableExternal<A, R> {
+    /// Creates a new `SortableExternal`.
+    ///
+    /// - `key` - The sort key for the resource.
+    /// - `acquire` - The closure that blocks until the resource is acquired.
+    /// - `release` - The closure that releases the resource.
+    pub fn new(key: SortKey, acquire: A, release: R) -> Self {
+        Self {
+            acquire,
+            release,
+            key,
+        }
+    }
+
+    /// Requests to lock the resource.
+    pub fn lock(&self) -> SortableExternalGuard<'_, A, R> {
+        SortableExternalGuard {
+            lock: self
+        }
+    }
+}
+
+impl <A, R> SortableExternal<A, R> {
+    /// Gets the sort key of this resource.
+    pub fn sort_key(&self) -> SortKey {
+        self.key
+    }
+}
+
+impl <A, R> Debug for SortableExternal<A, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortableExternal")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard for a `SortableExternal`.
+pub struct SortableExternalGuard<'l, A, R> {
+    /// The lock this guard references.
+    lock: &'l SortableExternal<A, R>,
+}
+
+impl <'l, H, A: Fn() -> H, R: Fn(H)> SortableLock for SortableExternalGuard<'l, A, R> {
+    type Guard = SortableExternalLocked<'l, H, A, R>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortableExternalLocked {
+            handle: ManuallyDrop::new((self.lock.acquire)()),
+            lock: self.lock,
+        }
+    }
+}
+
+/// An acquired lock on a `SortableExternal`.
+///
+/// The resource is released by passing the handle to the release closure when this guard is
+/// dropped.
+pub struct SortableExternalLocked<'l, H, A: Fn() -> H, R: Fn(H)> {
+    /// The handle returned when the resource was acquired.
+    handle: ManuallyDrop<H>,
+    /// The lock this guard references.
+    lock: &'l SortableExternal<A, R>,
+}
+
+impl <H, A: Fn() -> H, R: Fn(H)> Deref for SortableExternalLocked<'_, H, A, R> {
+    type Target = H;
+
+    fn deref(&self) -> &H {
+        &self.handle
+    }
+}
+
+impl <H, A: Fn() -> H, R: Fn(H)> Drop for SortableExternalLocked<'_, H, A, R> {
+    fn drop(&mut self) {
+        // SAFETY: The handle is not used again after being taken.
+        let handle = unsafe { ManuallyDrop::take(&mut self.handle) };
+
+        (self.lock.release)(handle);
+    }
+}
+
+impl <H: Debug, A: Fn() -> H, R: Fn(H)> Debug for SortableExternalLocked<'_, H, A, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::{LockGroup, SortKey, SortMutex, SortableExternal};
+
+    #[test]
+    fn test_external() {
+        let log = Mutex::new(Vec::new());
+        let external = SortableExternal::new(
+            SortKey::new(),
+            || {
+                log.lock().unwrap().push("acquire");
+                7
+            },
+            |handle| {
+                assert_eq!(7, handle);
+                log.lock().unwrap().push("release");
+            },
+        );
+        let lock = SortMutex::new(1);
+
+        let (guard, handle) = (lock.lock(), external.lock()).lock_all();
+        assert_eq!(8, *guard + *handle);
+        drop((guard, handle));
+
+        assert_eq!(vec!["acquire", "release"], *log.lock().unwrap());
+    }
+}
@@ -0,0 +1,217 @@
+use core::{fmt::{self, Debug, Display, Formatter}, ops::Deref, sync::atomic::{AtomicUsize, Ordering}};
+
+use crate::{LockGroup, MutexBackend, SortMutex, SortMutexGuard, SortMutexLocked};
+
+/// A sortable lock that ensures exclusive access to a resource in first-in first-out order.
+///
+/// Each thread takes a ticket when it starts waiting and threads are granted the lock in ticket
+/// order. This prevents a thread from being starved by other threads repeatedly relocking the
+/// lock, at the cost of lower throughput than `SortMutex` under contention.
+/// ```
+/// use sortlock::{SortFairMutex, SortMutex, LockGroup};
+///
+/// let lock1 = SortFairMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (mut guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+///
+/// This is a `SortMutex` using a `FairBackend`, which it derefs to. Unlike `SortMutex` this lock
+/// is never poisoned.
+pub struct SortFairMutex<T: ?Sized> {
+    /// The internal lock.
+    mutex: SortMutex<T, FairBackend>,
+}
+
+impl <T> SortFairMutex<T> {
+    /// Creates a new `SortFairMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::with_backend(value, FairBackend::new()),
+        }
+    }
+
+    /// Creates a new `SortFairMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::const_with_backend(value, FairBackend::new()),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortFairMutex<T> {
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+}
+
+impl <T: ?Sized> Deref for SortFairMutex<T> {
+    type Target = SortMutex<T, FairBackend>;
+
+    fn deref(&self) -> &SortMutex<T, FairBackend> {
+        &self.mutex
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortFairMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortFairMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortFairMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortFairMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A guard for a `SortFairMutex`.
+pub type SortFairMutexGuard<'l, T> = SortMutexGuard<'l, T, FairBackend>;
+
+/// An acquired lock on a `SortFairMutex`.
+///
+/// The lock is passed to the next waiting thread when this guard is dropped.
+pub type SortFairMutexLocked<'l, T> = SortMutexLocked<'l, T, FairBackend>;
+
+/// A backend that grants the lock in first-in first-out order.
+///
+/// Each thread takes a ticket when it starts waiting and waits for its ticket to be served, so
+/// no thread can be starved. Trying to lock only succeeds if no other thread holds or is waiting
+/// for the lock. This backend is never poisoned.
+/// ```
+/// use sortlock::{FairBackend, SortMutex, LockGroup};
+///
+/// let lock = SortMutex::with_backend(1, FairBackend::new());
+///
+/// assert_eq!(1, *lock.lock().lock_all());
+/// ```
+#[derive(Debug, Default)]
+pub struct FairBackend {
+    /// The next ticket to hand out.
+    next: AtomicUsize,
+    /// The ticket currently allowed to hold the lock.
+    serving: AtomicUsize,
+}
+
+impl FairBackend {
+    /// Creates a new unlocked `FairBackend`.
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+}
+
+unsafe impl MutexBackend for FairBackend {
+    type Guard<'l> = FairBackendGuard<'l>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+
+        while self.serving.load(Ordering::Acquire) != ticket {
+            #[cfg(feature = "std")]
+            std::thread::yield_now();
+            #[cfg(not(feature = "std"))]
+            core::hint::spin_loop();
+        }
+
+        FairBackendGuard {
+            backend: self
+        }
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        let serving = self.serving.load(Ordering::Acquire);
+
+        self.next.compare_exchange(serving, serving.wrapping_add(1), Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| FairBackendGuard {
+                backend: self
+            })
+    }
+}
+
+/// A held lock on a `FairBackend`.
+///
+/// The lock is passed to the next waiting thread when this guard is dropped.
+#[derive(Debug)]
+pub struct FairBackendGuard<'l> {
+    /// The backend this guard references.
+    backend: &'l FairBackend,
+}
+
+impl Drop for FairBackendGuard<'_> {
+    fn drop(&mut self) {
+        self.backend.serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortFairMutex};
+
+    #[test]
+    fn test_try_lock() {
+        let lock = SortFairMutex::new(1);
+
+        let guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(1, *lock.try_lock().unwrap());
+    }
+
+    #[test]
+    fn test_fair_threads() {
+        let lock = Arc::new(SortFairMutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    *lock.lock().lock_all() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *lock.lock().lock_all());
+    }
+}
@@ -0,0 +1,282 @@
+use core::{ffi::c_int, fmt::{self, Debug, Formatter}, ops::Deref};
+
+use std::{fs::{File, OpenOptions}, io, os::unix::{fs::MetadataExt, io::AsRawFd}, path::Path, sync::{Mutex, MutexGuard, PoisonError, TryLockError}};
+
+use libc::{LOCK_EX, LOCK_NB, LOCK_UN};
+
+use crate::{SortKey, SortableLock};
+
+/// A sortable lock that ensures exclusive access to a file across processes.
+///
+/// This uses advisory `flock` locks, so it only excludes other processes that also lock the
+/// file. The sort key is derived from the device and inode of the file, so every process sorts
+/// the same files in the same order and processes locking several files cannot deadlock with each
+/// other. Locks opened separately on the same file share a sort key. The device and inode are
+/// hashed into the key, so different files are very unlikely to share a key, and in debug builds
+/// opening a file whose key is already used by a different file panics.
+/// ```
+/// use std::io::Write;
+/// use sortlock::{SortFileLock, SortMutex, LockGroup};
+///
+/// let path = std::env::temp_dir().join("sortlock-doc-file.lock");
+/// let file = SortFileLock::open(&path).unwrap();
+/// let count = SortMutex::new(0);
+///
+/// let (locked, mut count) = (file.lock(), count.lock()).lock_all();
+/// (&*locked).write_all(b"locked").unwrap();
+/// *count += 1;
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct SortFileLock {
+    /// The locked file.
+    file: File,
+    /// Excludes other threads in this process, as `flock` locks are shared by a file handle.
+    local: Mutex<()>,
+    /// The sort key for this lock.
+    key: SortKey,
+}
+
+impl SortFileLock {
+    /// Creates a new `SortFileLock` for a file.
+    ///
+    /// Returns an error if the metadata of the file cannot be read.
+    ///
+    /// - `file` - The file to lock.
+    pub fn new(file: File) -> io::Result<Self> {
+        let metadata = file.metadata()?;
+
+        Ok(Self {
+            file,
+            local: Mutex::new(()),
+            key: SortKey::from_file(metadata.dev(), metadata.ino()),
+        })
+    }
+
+    /// Opens a file for reading and writing, creating it if it does not exist.
+    ///
+    /// Returns an error if the file cannot be opened.
+    ///
+    /// - `path` - The path of the file to lock.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        Self::new(file)
+    }
+
+    /// Requests to lock this lock.
+    pub fn lock(&self) -> SortFileLockGuard<'_> {
+        SortFileLockGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock without blocking.
+    ///
+    /// As only a single lock is locked no sorting is required.
+    ///
+    /// # Panicking
+    /// This method will panic if the file cannot be locked for a reason other than another lock
+    /// being held on it.
+    pub fn try_lock(&self) -> Option<SortFileLocked<'_>> {
+        let local = match self.local.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+
+        match self.flock(LOCK_EX | LOCK_NB) {
+            Ok(()) => Some(SortFileLocked {
+                _local: local,
+                lock: self,
+            }),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => None,
+            Err(error) => panic!("Failed to lock file: {error}."),
+        }
+    }
+
+    /// Gets the file without locking it.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Consumes this lock and returns the file.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key
+    }
+
+    /// Applies a `flock` operation to the file, retrying if interrupted.
+    ///
+    /// - `operation` - The operation to apply.
+    fn flock(&self, operation: c_int) -> io::Result<()> {
+        loop {
+            // SAFETY: The file descriptor is owned by this lock and stays open while it exists.
+            if unsafe { libc::flock(self.file.as_raw_fd(), operation) } == 0 {
+                return Ok(());
+            }
+
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl Debug for SortFileLock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortFileLock")
+            .field("file", &self.file)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+/// A guard for a `SortFileLock`.
+pub struct SortFileLockGuard<'l> {
+    /// The lock this guard references.
+    lock: &'l SortFileLock,
+}
+
+impl <'l> SortableLock for SortFileLockGuard<'l> {
+    type Guard = SortFileLocked<'l>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        let local = self.lock.local.lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Err(error) = self.lock.flock(LOCK_EX) {
+            panic!("Failed to lock file: {error}.");
+        }
+
+        SortFileLocked {
+            _local: local,
+            lock: self.lock,
+        }
+    }
+}
+
+/// An acquired lock on a `SortFileLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortFileLocked<'l> {
+    /// The guard excluding other threads in this process.
+    _local: MutexGuard<'l, ()>,
+    /// The lock this guard references.
+    lock: &'l SortFileLock,
+}
+
+impl Deref for SortFileLocked<'_> {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        &self.lock.file
+    }
+}
+
+impl Drop for SortFileLocked<'_> {
+    fn drop(&mut self) {
+        // Unlocking can only fail if the file descriptor is invalid, which the lock prevents.
+        let _ = self.lock.flock(LOCK_UN);
+    }
+}
+
+impl Debug for SortFileLocked<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{Read, Seek, Write}, thread};
+
+    use crate::{LockGroup, SortFileLock, SortKey};
+
+    #[test]
+    fn test_file_lock() {
+        let path = std::env::temp_dir().join(format!("sortlock-test-file-{}.lock", std::process::id()));
+        let lock1 = SortFileLock::open(&path).unwrap();
+        let lock2 = SortFileLock::open(&path).unwrap();
+
+        assert_eq!(lock1.sort_key(), lock2.sort_key());
+
+        let guard = lock1.lock().lock_all();
+        assert!(lock1.try_lock().is_none());
+        assert!(lock2.try_lock().is_none());
+        drop(guard);
+
+        let guard = lock2.try_lock().unwrap();
+        (&*guard).write_all(b"test").unwrap();
+        let mut contents = String::new();
+        (&*guard).rewind().unwrap();
+        (&*guard).read_to_string(&mut contents).unwrap();
+        assert_eq!("test", contents);
+        drop(guard);
+
+        drop((lock1, lock2));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_lock_threads() {
+        let directory = std::env::temp_dir();
+        let id = std::process::id();
+        let paths = [
+            directory.join(format!("sortlock-test-threads-a-{id}.lock")),
+            directory.join(format!("sortlock-test-threads-b-{id}.lock")),
+        ];
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let paths = paths.clone();
+
+            thread::spawn(move || {
+                let a = SortFileLock::open(&paths[0]).unwrap();
+                let b = SortFileLock::open(&paths[1]).unwrap();
+
+                for _ in 0..20 {
+                    if i % 2 == 0 {
+                        let _ = (a.lock(), b.lock()).lock_all();
+                    } else {
+                        let _ = (b.lock(), a.lock()).lock_all();
+                    }
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_file_keys() {
+        // Files on devices that only differ in their high bits.
+        assert_ne!(SortKey::from_file(1 << 20, 7), SortKey::from_file(1 << 40, 7));
+        // Files whose device and inode would cancel out when combined directly.
+        assert_ne!(SortKey::from_file(1, 1 << 48), SortKey::from_file(0, 0));
+
+        let key = SortKey::from_file(u64::MAX, u64::MAX);
+        assert_eq!(key, SortKey::from_file(u64::MAX, u64::MAX));
+        assert!(SortKey::new() < key);
+        assert!(key < SortKey::NONE);
+    }
+}
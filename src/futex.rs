@@ -0,0 +1,207 @@
+use core::{fmt::{self, Debug, Formatter}, ptr, sync::atomic::{AtomicBool, AtomicU32, Ordering}};
+
+use std::{thread, time::{Duration, Instant}};
+
+use crate::{MutexBackend, PoisonBackend, backend::private};
+
+/// The state of an unlocked futex mutex.
+const UNLOCKED: u32 = 0;
+/// The state of a locked futex mutex with no waiting threads.
+const LOCKED: u32 = 1;
+/// The state of a locked futex mutex that may have waiting threads.
+const CONTENDED: u32 = 2;
+
+/// A backend using a Linux futex directly.
+///
+/// The state of the lock is a single 4 byte word and waiting threads sleep in the kernel. Unlike
+/// `StdBackend` this allows locks to be acquired with a timeout through `SortMutex::try_lock_for`.
+/// Like `StdBackend`, locks using this backend are poisoned if a thread panics while holding them.
+/// This is the default backend on the targets it is available on.
+/// ```
+/// use std::time::Duration;
+/// use sortlock::{FutexBackend, SortMutex, LockGroup};
+///
+/// let lock = SortMutex::with_backend(1, FutexBackend::new());
+///
+/// let guard = lock.lock().lock_all();
+/// assert!(lock.try_lock_for(Duration::from_millis(10)).is_none());
+/// ```
+pub struct FutexBackend {
+    /// The futex word holding the state of the lock.
+    state: AtomicU32,
+    /// Whether a thread panicked while holding the lock.
+    poisoned: AtomicBool,
+}
+
+impl FutexBackend {
+    /// Creates a new unlocked `FutexBackend`.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    /// Attempts to acquire the lock, giving up after a timeout.
+    ///
+    /// - `timeout` - The longest time to wait for.
+    pub(crate) fn try_lock_for(&self, timeout: Duration) -> Option<FutexBackendGuard<'_>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.try_lock_until(deadline),
+            None => Some(self.lock()),
+        }
+    }
+
+    /// Attempts to acquire the lock, giving up at a deadline.
+    ///
+    /// - `deadline` - The time to stop waiting at.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is poisoned.
+    pub(crate) fn try_lock_until(&self, deadline: Instant) -> Option<FutexBackendGuard<'_>> {
+        self.lock_raw(Some(deadline))
+            .then(|| self.guard().unpoisoned())
+    }
+
+    /// Creates a guard for the lock once it has been acquired.
+    fn guard(&self) -> FutexBackendGuard<'_> {
+        FutexBackendGuard {
+            backend: self,
+            panicking: thread::panicking(),
+        }
+    }
+
+    /// Acquires the lock, waiting until the deadline if there is one.
+    ///
+    /// Returns whether the lock was acquired.
+    ///
+    /// - `deadline` - The time to stop waiting at.
+    fn lock_raw(&self, deadline: Option<Instant>) -> bool {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            return true;
+        }
+
+        // Marks the lock as contended so the holder wakes a waiter when it unlocks.
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return false,
+                },
+                None => None,
+            };
+
+            self.wait(timeout);
+        }
+
+        true
+    }
+
+    /// Sleeps while the lock is contended, waking early if the lock is released.
+    ///
+    /// - `timeout` - The longest time to sleep for.
+    fn wait(&self, timeout: Option<Duration>) {
+        let timespec = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs().try_into().unwrap_or(libc::time_t::MAX),
+            tv_nsec: timeout.subsec_nanos().into(),
+        });
+        let timespec = timespec.as_ref()
+            .map_or(ptr::null(), |timespec| timespec as *const libc::timespec);
+
+        // SAFETY: The futex word lives as long as the backend and the timeout outlives the call.
+        // Errors such as timeouts and interruptions are handled by checking the state again.
+        unsafe { libc::syscall(libc::SYS_futex, self.state.as_ptr(), libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG, CONTENDED, timespec) };
+    }
+
+    /// Releases the lock, waking a waiting thread if there may be one.
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            // SAFETY: The futex word lives as long as the backend.
+            unsafe { libc::syscall(libc::SYS_futex, self.state.as_ptr(), libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG, 1) };
+        }
+    }
+}
+
+unsafe impl MutexBackend for FutexBackend {
+    type Guard<'l> = FutexBackendGuard<'l>;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.lock_ignoring_poison()
+            .unpoisoned()
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.guard().unpoisoned())
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    fn lock_ignoring_poison(&self) -> Self::Guard<'_> {
+        self.lock_raw(None);
+
+        self.guard()
+    }
+}
+
+impl private::Sealed for FutexBackend {}
+
+impl PoisonBackend for FutexBackend {
+    fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Debug for FutexBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FutexBackend")
+            .field("locked", &(self.state.load(Ordering::Relaxed) != UNLOCKED))
+            .field("poisoned", &self.is_poisoned())
+            .finish()
+    }
+}
+
+impl Default for FutexBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A held lock on a `FutexBackend`.
+///
+/// The lock is released when this guard is dropped, and poisoned if the thread dropping it is
+/// panicking.
+#[derive(Debug)]
+pub struct FutexBackendGuard<'l> {
+    /// The backend this guard references.
+    backend: &'l FutexBackend,
+    /// Whether the thread was already panicking when the lock was acquired.
+    panicking: bool,
+}
+
+impl FutexBackendGuard<'_> {
+    /// Checks that the lock was not poisoned before it was acquired.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is poisoned. The lock is released while panicking.
+    fn unpoisoned(self) -> Self {
+        if self.backend.is_poisoned() {
+            panic!("Failed to lock mutex: mutex is poisoned.");
+        }
+
+        self
+    }
+}
+
+impl Drop for FutexBackendGuard<'_> {
+    fn drop(&mut self) {
+        if !self.panicking && thread::panicking() {
+            self.backend.poisoned.store(true, Ordering::Relaxed);
+        }
+
+        self.backend.unlock();
+    }
+}
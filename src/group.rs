@@ -0,0 +1,562 @@
+use core::{error::Error, fmt::{self, Debug, Display, Formatter}, mem::{self, ManuallyDrop}, ops::{Deref, DerefMut}, ptr, slice};
+
+use crate::{NestedLockGroup, OrderedLockGroup, SortKey, SortableLock, check_conflicts, finally};
+
+/// The order of a nested group, formed by joining the orders of its members.
+///
+/// This dereferences to a single slice containing the entries of both orders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ChainedOrder<A, B> {
+    /// The order of the first member.
+    first: A,
+    /// The order of the remaining members.
+    rest: B,
+}
+
+impl <A: FlatOrder, B: FlatOrder> ChainedOrder<A, B> {
+    /// Creates a new `ChainedOrder` by joining two orders.
+    ///
+    /// - `first` - The order of the first member.
+    /// - `rest` - The order of the remaining members.
+    pub(crate) fn new(first: A, rest: B) -> Self {
+        Self {
+            first,
+            rest,
+        }
+    }
+}
+
+impl <A: FlatOrder, B: FlatOrder> AsRef<[(usize, SortKey)]> for ChainedOrder<A, B> {
+    fn as_ref(&self) -> &[(usize, SortKey)] {
+        let len = mem::size_of::<Self>() / mem::size_of::<(usize, SortKey)>();
+
+        // SAFETY: Both halves are contiguous arrays of entries without padding between them.
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), len) }
+    }
+}
+
+impl <A: FlatOrder, B: FlatOrder> AsMut<[(usize, SortKey)]> for ChainedOrder<A, B> {
+    fn as_mut(&mut self) -> &mut [(usize, SortKey)] {
+        let len = mem::size_of::<Self>() / mem::size_of::<(usize, SortKey)>();
+
+        // SAFETY: Both halves are contiguous arrays of entries without padding between them.
+        unsafe { slice::from_raw_parts_mut((self as *mut Self).cast(), len) }
+    }
+}
+
+mod private {
+    use crate::SortKey;
+
+    use super::ChainedOrder;
+
+    /// An order that is laid out as a contiguous array of entries.
+    ///
+    /// # Safety
+    /// Implementors must have the same layout as `[(usize, SortKey); N]` for some `N`.
+    pub unsafe trait FlatOrder {}
+
+    unsafe impl <const N: usize> FlatOrder for [(usize, SortKey); N] {}
+    unsafe impl <A: FlatOrder, B: FlatOrder> FlatOrder for ChainedOrder<A, B> {}
+}
+
+pub(crate) use private::FlatOrder;
+
+/// A locked group that releases its locks in the reverse of the order they were locked in.
+///
+/// This is created with `lock_all_guarded` and dereferences to the locked group. The locks are
+/// released when this guard is dropped or when `unlock_all` is called.
+/// ```
+/// use sortlock::{SortMutex, SortRwLock, OrderedLockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortRwLock::new(2);
+///
+/// let mut guard = (lock1.lock(), lock2.read()).lock_all_guarded();
+/// *guard.0 += *guard.1;
+/// assert_eq!(3, *guard.0);
+///
+/// guard.unlock_all();
+/// ```
+pub struct GroupGuard<G: OrderedLockGroup> {
+    /// The group that was locked.
+    group: G,
+    /// The locked group.
+    ///
+    /// This is only `None` while the group is temporarily released by `unlocked` or when the
+    /// guard is dropped.
+    locked: Option<G::Locked>,
+    /// The order the group was locked in.
+    order: G::Order,
+}
+
+impl <G: OrderedLockGroup> GroupGuard<G> {
+    /// Creates a new `GroupGuard` by locking a group.
+    ///
+    /// - `group` - The group to lock.
+    pub(crate) fn new(group: G) -> Self {
+        let (locked, order) = group.lock_ordered();
+
+        Self {
+            group,
+            locked: Some(locked),
+            order,
+        }
+    }
+
+    /// Temporarily unlocks all locks in the group while running a closure and then locks them
+    /// again.
+    ///
+    /// The locks are released in the reverse of the order they were locked in and are then
+    /// reacquired in sorted order so this cannot introduce a lock ordering cycle.
+    /// ```
+    /// use sortlock::{SortMutex, OrderedLockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let mut guard = (lock1.lock(), lock2.lock()).lock_all_guarded();
+    /// guard.unlocked(|| {
+    ///     // The locks can be acquired elsewhere while unlocked.
+    ///     assert!(lock1.try_lock().is_some());
+    /// });
+    /// *guard.0 += *guard.1;
+    /// ```
+    ///
+    /// - `f` - The closure to run while unlocked.
+    ///
+    /// # Panicking
+    /// If the closure panics the group is locked again before the panic continues.
+    pub fn unlocked<R, F: FnOnce() -> R>(&mut self, f: F) -> R {
+        if let Some(locked) = self.locked.take() {
+            G::release_ordered(locked, &self.order);
+        }
+
+        finally(f, || {
+            let (locked, order) = self.group.lock_ordered();
+            self.locked = Some(locked);
+            self.order = order;
+        })
+    }
+
+    /// Gets the order the group was locked in.
+    ///
+    /// Each entry contains the index of a member within the group and its sort key.
+    /// ```
+    /// use sortlock::{SortMutex, OrderedLockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let guard = (lock2.lock(), lock1.lock()).lock_all_guarded();
+    /// assert_eq!(vec![1, 0], guard.order().iter().map(|(i, _)| *i).collect::<Vec<_>>());
+    /// ```
+    pub fn order(&self) -> &[(usize, SortKey)] {
+        self.order.as_ref()
+    }
+
+    /// Unlocks all locks in the group in the reverse of the order they were locked in.
+    ///
+    /// This is equivalent to dropping the guard.
+    pub fn unlock_all(self) {
+        drop(self);
+    }
+
+    /// Locks an additional lock while keeping the locks in this group held.
+    ///
+    /// This only succeeds if the new lock sorts after every lock that is already held, as locking
+    /// it would otherwise break the locking order. If it does not, the guard and request are
+    /// returned in an `ExtendError` and the whole group should be released and locked again
+    /// with the new lock included.
+    /// ```
+    /// use sortlock::{SortMutex, OrderedLockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    /// let lock3 = SortMutex::new(3);
+    ///
+    /// let guard = (lock1.lock(), lock2.lock()).lock_all_guarded();
+    /// let guard = guard.extend(lock3.lock()).unwrap();
+    /// assert_eq!(6, *guard.0.0 + *guard.0.1 + *guard.1);
+    /// ```
+    ///
+    /// - `request` - The request for the lock to add.
+    pub fn extend<L: SortableLock>(self, request: L) -> Result<GroupGuard<(G, L)>, ExtendError<G, L>> where G: NestedLockGroup {
+        let key = request.sort_key();
+
+        let in_order = self.order.as_ref()
+            .iter()
+            .all(|(_, held)| *held == SortKey::NONE || *held < key);
+
+        if !in_order {
+            return Err(ExtendError {
+                guard: self,
+                request,
+            });
+        }
+
+        let (group, locked, order) = self.into_parts();
+        let guard = request.lock_presorted();
+
+        Ok(GroupGuard {
+            group: (group, request),
+            locked: locked.map(|locked| (locked, guard)),
+            order: ChainedOrder::new(order, [(G::LEN, key)]),
+        })
+    }
+
+    /// Splits this guard into its fields without releasing the locks.
+    fn into_parts(self) -> (G, Option<G::Locked>, G::Order) {
+        let this = ManuallyDrop::new(self);
+
+        // SAFETY: `this` is never dropped so each field is only read once.
+        unsafe {
+            (ptr::read(&this.group), ptr::read(&this.locked), ptr::read(&this.order))
+        }
+    }
+}
+
+impl <G: OrderedLockGroup> Deref for GroupGuard<G> {
+    type Target = G::Locked;
+
+    fn deref(&self) -> &G::Locked {
+        self.locked.as_ref().expect("group is locked")
+    }
+}
+
+impl <G: OrderedLockGroup> DerefMut for GroupGuard<G> {
+    fn deref_mut(&mut self) -> &mut G::Locked {
+        self.locked.as_mut().expect("group is locked")
+    }
+}
+
+impl <G: OrderedLockGroup> Drop for GroupGuard<G> {
+    fn drop(&mut self) {
+        if let Some(locked) = self.locked.take() {
+            G::release_ordered(locked, &self.order);
+        }
+    }
+}
+
+impl <G: OrderedLockGroup> Debug for GroupGuard<G> where G::Locked: Debug {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// The error returned by `GroupGuard::extend` when the new lock does not sort after every held
+/// lock.
+pub struct ExtendError<G: OrderedLockGroup, L> {
+    /// The guard that was being extended.
+    guard: GroupGuard<G>,
+    /// The request that could not be added.
+    request: L,
+}
+
+impl <G: OrderedLockGroup, L> ExtendError<G, L> {
+    /// Consumes this error, returning the guard that was being extended and the request that could
+    /// not be added.
+    pub fn into_inner(self) -> (GroupGuard<G>, L) {
+        (self.guard, self.request)
+    }
+}
+
+impl <G: OrderedLockGroup, L> Debug for ExtendError<G, L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendError").finish_non_exhaustive()
+    }
+}
+
+impl <G: OrderedLockGroup, L> Display for ExtendError<G, L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the new lock does not sort after every held lock; release the group and lock it again with the new lock included")
+    }
+}
+
+impl <G: OrderedLockGroup, L> Error for ExtendError<G, L> {}
+
+/// A group whose locking order is computed once and reused every time it is locked.
+///
+/// Sorting is skipped when locking so this is useful for groups that are locked very often.
+/// ```
+/// use sortlock::{SortMutex, StaticGroup};
+///
+/// let lock1 = SortMutex::new(0);
+/// let lock2 = SortMutex::new(0);
+///
+/// let group = StaticGroup::new((lock2.lock(), lock1.lock()));
+///
+/// for _ in 0..100 {
+///     let (mut guard2, mut guard1) = group.lock();
+///     *guard1 += 1;
+///     *guard2 += 1;
+/// }
+///
+/// assert_eq!(100, *group.lock().0);
+/// ```
+pub struct StaticGroup<G: NestedLockGroup> {
+    /// The group to lock.
+    group: G,
+    /// The sorted order to lock the group in.
+    order: G::Order,
+}
+
+impl <G: NestedLockGroup> StaticGroup<G> {
+    /// Creates a new `StaticGroup`, computing the order the group will be locked in.
+    ///
+    /// - `group` - The group to lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together.
+    pub fn new(group: G) -> Self {
+        let order = group.acquisition_order();
+
+        check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, group.member_access(*i))));
+
+        Self {
+            group,
+            order,
+        }
+    }
+
+    /// Locks all items in the group in the precomputed order.
+    pub fn lock(&self) -> G::Locked {
+        let mut partial = G::unlocked_partial();
+
+        for (i, _) in self.order.as_ref() {
+            self.group.lock_member(*i, &mut partial);
+        }
+
+        G::complete_partial(partial)
+    }
+
+    /// Gets the order the group is locked in.
+    ///
+    /// Each entry contains the flattened index of a lock within the group and its sort key.
+    pub fn order(&self) -> &[(usize, SortKey)] {
+        self.order.as_ref()
+    }
+
+    /// Gets the group that is locked.
+    pub fn group(&self) -> &G {
+        &self.group
+    }
+
+    /// Consumes this `StaticGroup`, returning the group that is locked.
+    pub fn into_inner(self) -> G {
+        self.group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, panic::{self, AssertUnwindSafe}, vec::Vec};
+
+    use crate::{NestedLockGroup, OrderedLockGroup, SortKey, SortableLock, StaticGroup};
+
+    /// A lock that records when it is locked and unlocked.
+    struct RecordingLock<'l> {
+        key: SortKey,
+        name: &'static str,
+        events: &'l RefCell<Vec<(&'static str, bool)>>,
+    }
+
+    struct RecordingGuard<'l> {
+        name: &'static str,
+        events: &'l RefCell<Vec<(&'static str, bool)>>,
+    }
+
+    impl Drop for RecordingGuard<'_> {
+        fn drop(&mut self) {
+            self.events.borrow_mut().push((self.name, false));
+        }
+    }
+
+    impl <'l> SortableLock for RecordingLock<'l> {
+        type Guard = RecordingGuard<'l>;
+
+        fn sort_key(&self) -> SortKey {
+            self.key
+        }
+
+        fn lock_presorted(&self) -> Self::Guard {
+            self.events.borrow_mut().push((self.name, true));
+
+            RecordingGuard {
+                name: self.name,
+                events: self.events,
+            }
+        }
+    }
+
+    #[test]
+    fn test_release_order() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+
+        (third, first, second).lock_all_guarded().unlock_all();
+
+        assert_eq!(vec![
+            ("first", true),
+            ("second", true),
+            ("third", true),
+            ("third", false),
+            ("second", false),
+            ("first", false),
+        ], *events.borrow());
+    }
+
+    #[test]
+    fn test_nested_order() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+        let fourth = RecordingLock { key: SortKey::new(), name: "fourth", events: &events };
+
+        let guard = ((fourth, first), (third, second)).lock_all_guarded();
+        assert_eq!(["fourth", "first", "third", "second"], [
+            guard.0.0.name,
+            guard.0.1.name,
+            guard.1.0.name,
+            guard.1.1.name,
+        ]);
+        guard.unlock_all();
+
+        assert_eq!(vec![
+            ("first", true),
+            ("second", true),
+            ("third", true),
+            ("fourth", true),
+            ("fourth", false),
+            ("third", false),
+            ("second", false),
+            ("first", false),
+        ], *events.borrow());
+    }
+
+    #[test]
+    fn test_static_group() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+
+        let group = StaticGroup::new((second, first));
+        drop(group.lock());
+        drop(group.lock());
+
+        assert_eq!(vec![
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+        ], *events.borrow());
+    }
+
+    #[test]
+    fn test_extend() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+
+        let guard = second.lock_all_guarded();
+        let Err(error) = guard.extend(first) else {
+            panic!("extended with a lock that sorts before the group");
+        };
+        let (guard, first) = error.into_inner();
+        assert_eq!("first", first.name);
+
+        let Ok(guard) = guard.extend(third) else {
+            panic!("failed to extend with a lock that sorts after the group");
+        };
+        guard.unlock_all();
+
+        assert_eq!(vec![
+            ("second", true),
+            ("third", true),
+            ("third", false),
+            ("second", false),
+        ], *events.borrow());
+    }
+
+    #[test]
+    fn test_order() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+        let third = RecordingLock { key: SortKey::new(), name: "third", events: &events };
+        let keys = [first.key, second.key, third.key];
+
+        let group = ((third, first), second);
+        assert_eq!([(1, keys[0]), (2, keys[1]), (0, keys[2])], group.acquisition_order().as_ref());
+
+        let guard = group.lock_all_guarded();
+        assert_eq!([(1, keys[0]), (2, keys[1]), (0, keys[2])], guard.order());
+    }
+
+    #[test]
+    fn test_unlocked() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+
+        let mut guard = (second, first).lock_all_guarded();
+        guard.unlocked(|| events.borrow_mut().push(("unlocked", true)));
+        drop(guard);
+
+        assert_eq!(vec![
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+            ("unlocked", true),
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+        ], *events.borrow());
+    }
+
+    #[test]
+    fn test_unlocked_panic() {
+        let events = RefCell::new(Vec::new());
+
+        let first = RecordingLock { key: SortKey::new(), name: "first", events: &events };
+        let second = RecordingLock { key: SortKey::new(), name: "second", events: &events };
+
+        let mut guard = (second, first).lock_all_guarded();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            guard.unlocked(|| panic!("unlocked"));
+        }));
+        assert!(result.is_err());
+
+        // The group is locked again so the guard can still be used.
+        assert_eq!(("second", "first"), (guard.0.name, guard.1.name));
+        drop(guard);
+
+        assert_eq!(vec![
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+            ("first", true),
+            ("second", true),
+            ("second", false),
+            ("first", false),
+        ], *events.borrow());
+    }
+}
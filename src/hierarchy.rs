@@ -0,0 +1,270 @@
+use core::fmt::{self, Debug, Display, Formatter};
+
+#[cfg(debug_assertions)]
+use core::cell::RefCell;
+
+use core::ops::{Deref, DerefMut};
+
+#[cfg(debug_assertions)]
+use alloc::vec::Vec;
+
+use crate::{SortKey, SortMutex, SortMutexLocked, SortableLock};
+
+#[cfg(debug_assertions)]
+std::thread_local! {
+    /// The levels of the hierarchical locks held by the current thread.
+    static HELD_LEVELS: RefCell<Vec<u16>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A sortable lock that ensures exclusive access to a resource and belongs to a lock hierarchy.
+///
+/// Each lock has a level, and in debug builds locking panics if the current thread already holds
+/// a hierarchical lock with an equal or higher level. This enforces a locking order in code that
+/// cannot acquire every lock through a single `lock_all`. Locks in the same group are locked from
+/// the lowest level to the highest, so a group may contain locks of different levels.
+/// ```
+/// use sortlock::{HierarchicalMutex, LockGroup};
+///
+/// let config = HierarchicalMutex::with_level(1, "config");
+/// let cache = HierarchicalMutex::with_level(2, "cache");
+///
+/// let config = config.lock().lock_all();
+/// // Locking a higher level while holding a lower level is allowed.
+/// let cache = cache.lock().lock_all();
+/// assert_eq!("cache", *cache);
+/// ```
+///
+/// Locking in the wrong order panics in debug builds:
+/// ```should_panic
+/// # #[cfg(not(debug_assertions))]
+/// # panic!();
+/// use sortlock::{HierarchicalMutex, LockGroup};
+///
+/// let config = HierarchicalMutex::with_level(1, "config");
+/// let cache = HierarchicalMutex::with_level(2, "cache");
+///
+/// let cache = cache.lock().lock_all();
+/// let config = config.lock().lock_all();
+/// ```
+pub struct HierarchicalMutex<T: ?Sized> {
+    /// The level of this lock in the hierarchy.
+    level: u16,
+    /// The lock protecting the value.
+    mutex: SortMutex<T>,
+}
+
+impl <T> HierarchicalMutex<T> {
+    /// Creates a new `HierarchicalMutex` at a level of the hierarchy.
+    ///
+    /// - `level` - The level of the lock. Locks must be acquired in increasing order of level.
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn with_level(level: u16, value: T) -> Self {
+        Self {
+            level,
+            mutex: SortMutex::with_key(value, SortKey::with_level(level)),
+        }
+    }
+}
+
+impl <T: ?Sized> HierarchicalMutex<T> {
+    /// Requests to lock this lock.
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned, or in debug builds if the
+    /// current thread holds a hierarchical lock of an equal or higher level.
+    pub fn lock(&self) -> HierarchicalMutexGuard<'_, T> {
+        HierarchicalMutexGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock without blocking.
+    ///
+    /// As only a single lock is acquired no sorting is required.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned, or in debug builds if the current thread
+    /// holds a hierarchical lock of an equal or higher level. A level violation is a bug in the
+    /// caller rather than contention, so it panics as `lock` does instead of returning `None`,
+    /// which callers would treat as the lock being held and could retry forever.
+    pub fn try_lock(&self) -> Option<HierarchicalMutexLocked<'_, T>> {
+        self.check_level();
+
+        self.mutex.try_lock()
+            .map(|guard| HierarchicalMutexLocked::new(guard, self.level))
+    }
+
+    /// Gets the level of this lock in the hierarchy.
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFETY: This is borrowed mutably so no other references to the value exist.
+        unsafe { &mut *self.mutex.data_ptr() }
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.mutex.sort_key()
+    }
+
+    /// Checks that the current thread holds no hierarchical lock at this level or above.
+    ///
+    /// This does nothing in release builds.
+    ///
+    /// # Panicking
+    /// This method will panic if a lock at this level or above is held.
+    fn check_level(&self) {
+        #[cfg(debug_assertions)]
+        HELD_LEVELS.with(|held| {
+            if let Some(highest) = held.borrow().iter().max().filter(|highest| **highest >= self.level) {
+                panic!("Failed to lock mutex: a lock of level {highest} is held while locking level {}.", self.level);
+            }
+        });
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for HierarchicalMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HierarchicalMutex")
+            .field("level", &self.level)
+            .field("value", &&self.mutex)
+            .finish()
+    }
+}
+
+/// A guard for a `HierarchicalMutex`.
+pub struct HierarchicalMutexGuard<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l HierarchicalMutex<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for HierarchicalMutexGuard<'l, T> {
+    type Guard = HierarchicalMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.check_level();
+
+        HierarchicalMutexLocked::new((&self.lock.mutex).lock_presorted(), self.lock.level)
+    }
+}
+
+/// An acquired lock on a `HierarchicalMutex`.
+///
+/// The lock is released when this guard is dropped. In debug builds the level is recorded as held
+/// by the thread until then, so if the guard is leaked, for example with `mem::forget`, the thread
+/// can never again lock a hierarchical lock of that level or lower.
+pub struct HierarchicalMutexLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    guard: SortMutexLocked<'l, T>,
+    /// The level of the lock.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    level: u16,
+}
+
+impl <'l, T: ?Sized> HierarchicalMutexLocked<'l, T> {
+    /// Creates a new `HierarchicalMutexLocked`, recording the level as held by this thread.
+    ///
+    /// - `guard` - The guard for the internal lock.
+    /// - `level` - The level of the lock.
+    fn new(guard: SortMutexLocked<'l, T>, level: u16) -> Self {
+        #[cfg(debug_assertions)]
+        HELD_LEVELS.with(|held| held.borrow_mut().push(level));
+
+        Self {
+            guard,
+            level,
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for HierarchicalMutexLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl <T: ?Sized> DerefMut for HierarchicalMutexLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl <T: ?Sized> Drop for HierarchicalMutexLocked<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        HELD_LEVELS.with(|held| {
+            let mut held = held.borrow_mut();
+
+            if let Some(index) = held.iter().rposition(|level| *level == self.level) {
+                held.swap_remove(index);
+            }
+        });
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for HierarchicalMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for HierarchicalMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HierarchicalMutex, LockGroup, SortMutex};
+
+    #[test]
+    fn test_group_levels() {
+        let low = HierarchicalMutex::with_level(1, 1);
+        let high = HierarchicalMutex::with_level(5, 2);
+        let other = SortMutex::new(3);
+
+        // Groups are locked from the lowest level regardless of their order.
+        let (high_guard, other_guard, low_guard) = (high.lock(), other.lock(), low.lock()).lock_all();
+        assert_eq!(6, *high_guard + *other_guard + *low_guard);
+        drop((high_guard, other_guard, low_guard));
+
+        let high_guard = high.lock().lock_all();
+        drop(high_guard);
+        let _low_guard = low.lock().lock_all();
+        assert!(high.try_lock().is_some());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Failed to lock mutex: a lock of level")]
+    fn test_wrong_order() {
+        let low = HierarchicalMutex::with_level(1, 1);
+        let high = HierarchicalMutex::with_level(5, 2);
+
+        let _high_guard = high.lock().lock_all();
+        let _low_guard = low.lock().lock_all();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Failed to lock mutex: a lock of level")]
+    fn test_equal_levels() {
+        let lock1 = HierarchicalMutex::with_level(2, 1);
+        let lock2 = HierarchicalMutex::with_level(2, 2);
+
+        let _guards = (lock1.lock(), lock2.lock()).lock_all();
+    }
+}
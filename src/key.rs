@@ -1,9 +1,38 @@
-use core::sync::atomic::Ordering;
+use core::{fmt::{self, Debug, Formatter}, panic::Location, ptr, sync::atomic::Ordering};
 
-use portable_atomic::AtomicU64;
+#[cfg(all(debug_assertions, feature = "std", unix))]
+use std::sync::{Mutex, PoisonError};
 
-/// The next sort key to use.
-static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+#[cfg(all(debug_assertions, feature = "std", unix))]
+use alloc::collections::BTreeMap;
+
+use portable_atomic::{AtomicBool, AtomicPtr, AtomicU64};
+
+use sortlock_keys::{AtomicCount, Count};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The keys in the early band.
+static NEXT_EARLY_KEY: KeyCounter = KeyCounter::new(&sortlock_keys::EARLY_KEYS, sortlock_keys::EARLY_START, sortlock_keys::EARLY_LEN);
+/// The keys created with `SortKey::new`.
+static NEXT_KEY: KeyCounter = KeyCounter::new(&sortlock_keys::NORMAL_KEYS, sortlock_keys::NORMAL_START, sortlock_keys::NORMAL_LEN);
+/// The keys in the late band.
+static NEXT_LATE_KEY: KeyCounter = KeyCounter::new(&sortlock_keys::LATE_KEYS, sortlock_keys::LATE_START, sortlock_keys::LATE_LEN);
+/// Whether keys are reused rather than panicking once a band has been exhausted.
+static WRAP_ON_EXHAUSTION: AtomicBool = AtomicBool::new(false);
+/// The function called with every new key, or null if there is none.
+static OBSERVER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+/// The device and inode of the file each file key was created for, used to catch files with the
+/// same key in debug builds.
+#[cfg(all(debug_assertions, feature = "std", unix))]
+static FILE_KEYS: Mutex<BTreeMap<u64, (u64, u64)>> = Mutex::new(BTreeMap::new());
+
+/// A function called with every new sort key and, when it is known, where the key was created.
+///
+/// The location is the code that created the lock, or the key if it was created directly. It is
+/// `None` for keys assigned when a lock created in a `const` context is first used.
+pub type KeyObserver = fn(SortKey, Option<&'static Location<'static>>);
 
 /// A sort key for sorting locks.
 /// This must be unique to each lock.
@@ -21,9 +50,833 @@ static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
 pub struct SortKey(u64);
 
 impl SortKey {
+    /// The sort key used by group members that do not lock anything.
+    ///
+    /// This is never assigned to a lock.
+    pub(crate) const NONE: SortKey = SortKey(u64::MAX);
+
+    /// The bit set on every manual key.
+    pub(crate) const MANUAL_BIT: u64 = sortlock_keys::MANUAL_BIT;
+
     /// Creates a new unique sort key.
+    ///
+    /// # Panicking
+    /// This method will panic if every key in the band has been used, unless the exhaustion
+    /// policy set with `set_exhaustion` allows a key to be reused.
+    #[track_caller]
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self(NEXT_KEY.fetch_add(1, Ordering::Relaxed))
+        NEXT_KEY.next_key().observed(Some(Location::caller()))
+    }
+
+    /// Sets a function to call with every new sort key.
+    ///
+    /// This allows tools such as leak detectors and lock auditors to track every lock the process
+    /// creates. The observer is called with keys created with `new`, `new_in`, `new_batch`,
+    /// `from_value` and from a `KeySpace`, including those created by lock constructors. Keys
+    /// derived from a name are not reported as the same key can be created many times.
+    /// ```
+    /// use sortlock::{SortKey, SortMutex, LockGroup};
+    ///
+    /// SortKey::set_observer(Some(|key, location| {
+    ///     if let Some(location) = location {
+    ///         println!("Created {key:?} at {location}");
+    ///     }
+    /// }));
+    ///
+    /// let lock = SortMutex::new(1);
+    /// ```
+    ///
+    /// The observer may be called from any thread and must not create sort keys itself.
+    ///
+    /// - `observer` - The function to call, or `None` to stop calling the current observer.
+    pub fn set_observer(observer: Option<KeyObserver>) {
+        let observer = observer.map_or(ptr::null_mut(), |observer| observer as *mut ());
+
+        OBSERVER.store(observer, Ordering::Release);
+    }
+
+    /// Reports this key to the observer if there is one.
+    ///
+    /// - `location` - Where the key was created, if it is known.
+    pub(crate) fn observed(self, location: Option<&'static Location<'static>>) -> Self {
+        let observer = OBSERVER.load(Ordering::Acquire);
+
+        if !observer.is_null() {
+            // SAFETY: Only `KeyObserver`s are stored in `OBSERVER`.
+            let observer = unsafe { core::mem::transmute::<*mut (), KeyObserver>(observer) };
+
+            observer(self, location);
+        }
+
+        self
+    }
+
+    /// Sets what happens when every key in a band has been used.
+    ///
+    /// A band holds at least `2^57` keys and the locks in a hierarchy share `2^46` keys across every
+    /// level, or `u32::MAX` keys with the `narrow-keys` feature, so this only matters for programs
+    /// that create a very large number of locks. The policy applies
+    /// to every band and defaults to `KeyExhaustion::Panic`.
+    /// ```
+    /// use sortlock::{KeyExhaustion, SortKey};
+    ///
+    /// SortKey::set_exhaustion(KeyExhaustion::Wrap);
+    /// ```
+    ///
+    /// - `policy` - The new policy.
+    pub fn set_exhaustion(policy: KeyExhaustion) {
+        WRAP_ON_EXHAUSTION.store(policy == KeyExhaustion::Wrap, Ordering::Relaxed);
+    }
+
+    /// Gets the current exhaustion policy.
+    fn exhaustion() -> KeyExhaustion {
+        if WRAP_ON_EXHAUSTION.load(Ordering::Relaxed) {
+            KeyExhaustion::Wrap
+        } else {
+            KeyExhaustion::Panic
+        }
+    }
+
+    /// Gets a key to use once a band has been exhausted.
+    ///
+    /// - `policy` - The exhaustion policy to follow.
+    /// - `band` - The first key in the band.
+    ///
+    /// # Panicking
+    /// This method will panic if the policy does not allow a key to be reused or there are no free
+    /// keys.
+    #[cold]
+    #[cfg_attr(not(feature = "alloc"), allow(unused_variables))]
+    fn exhausted(policy: KeyExhaustion, band: u64) -> Self {
+        #[cfg(feature = "alloc")]
+        if policy == KeyExhaustion::Wrap {
+            if let Some(key) = Self::take_free(band) {
+                return key;
+            }
+        }
+
+        panic!("Failed to create sort key: every key in the band has been used.");
+    }
+
+    /// Records that this key is no longer used by any lock so it can be given to a new lock.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn free(self) {
+        sortlock_keys::free_key(self.0);
+    }
+
+    /// Takes a key from a band that is no longer used by any lock, if there is one.
+    ///
+    /// - `band` - The first key in the band.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn take_free(band: u64) -> Option<Self> {
+        sortlock_keys::take_free_key(band).map(Self)
+    }
+
+    /// Gets the integer value of this key.
+    ///
+    /// Keys sort in the same order as their values. The value can be recorded in logs, traces or
+    /// crash reports and compared with the value of a key recorded elsewhere.
+    /// ```
+    /// use sortlock::SortKey;
+    ///
+    /// let key = SortKey::new();
+    /// let key2 = SortKey::new();
+    ///
+    /// assert!(key.value() < key2.value());
+    /// ```
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Creates a batch of new unique sort keys with a single allocation.
+    ///
+    /// The keys are consecutive and are returned in sorted order, so locks created from them in
+    /// order, such as the elements of an array, are always locked in that order.
+    /// ```
+    /// use sortlock::{SortKey, SortMutex};
+    ///
+    /// let mut keys = SortKey::new_batch(4);
+    /// let locks: [SortMutex<usize>; 4] = core::array::from_fn(|i| SortMutex::with_key(i, keys.next().unwrap()));
+    ///
+    /// assert!(locks.is_sorted_by_key(SortMutex::sort_key));
+    /// ```
+    ///
+    /// - `len` - The number of keys to create.
+    ///
+    /// # Panicking
+    /// This method will panic if fewer than `len` keys are left in the band. Keys are never reused
+    /// for a batch as reused keys are not consecutive.
+    #[track_caller]
+    pub fn new_batch(len: usize) -> impl ExactSizeIterator<Item = Self> + DoubleEndedIterator {
+        let start = NEXT_KEY.reserve(len as u64)
+            .expect("Failed to create sort keys: not enough keys are left in the band.");
+        let location = Location::caller();
+
+        (0..len).map(move |i| Self(start + i as u64).observed(Some(location)))
+    }
+
+    /// Creates a new unique sort key in a band.
+    ///
+    /// Keys sort by band first, so a category of locks can be made to always lock before or after
+    /// other locks regardless of when they were created. Within a band keys sort in the order they
+    /// were created.
+    /// ```
+    /// use sortlock::{Band, SortKey, SortMutex, LockGroup};
+    ///
+    /// let item = SortMutex::new(1);
+    /// let metadata = SortMutex::with_key(2, SortKey::new_in(Band::Early));
+    ///
+    /// assert!(metadata.sort_key() < item.sort_key());
+    /// ```
+    ///
+    /// - `band` - The band of the key.
+    ///
+    /// # Panicking
+    /// This method will panic if every key in the band has been used, unless the exhaustion
+    /// policy set with `set_exhaustion` allows a key to be reused.
+    #[track_caller]
+    pub fn new_in(band: Band) -> Self {
+        let next = match band {
+            Band::Early => &NEXT_EARLY_KEY,
+            Band::Normal => &NEXT_KEY,
+            Band::Late => &NEXT_LATE_KEY,
+        };
+
+        next.next_key().observed(Some(Location::caller()))
+    }
+
+    /// Creates a sort key from a value chosen by the application.
+    ///
+    /// Manual keys sort by their value, which allows the application to control the order in
+    /// which certain locks are locked regardless of when they were created. Manual keys sort after
+    /// every key created with `new`. Each value must only be used for a single lock at a time, and
+    /// the key should be copied rather than created again for each use. The value can be used
+    /// again once the lock given the key has been dropped.
+    /// ```
+    /// use sortlock::{SortKey, SortMutex, LockGroup};
+    ///
+    /// let lock2 = SortMutex::with_key(2, SortKey::from_value(20));
+    /// let lock1 = SortMutex::with_key(1, SortKey::from_value(10));
+    ///
+    /// assert!(lock1.sort_key() < lock2.sort_key());
+    /// ```
+    ///
+    /// - `value` - The value of the key.
+    ///
+    /// # Panicking
+    /// This method will panic if `value` is not less than `2^61`. In debug builds with the `std`
+    /// feature enabled it will also panic if a manual key with the same value has already been
+    /// created and the lock given that key has not been dropped.
+    #[track_caller]
+    pub fn from_value(value: u64) -> Self {
+        assert!(value < Self::MANUAL_BIT, "Failed to create sort key: manual key values must be less than 2^61.");
+
+        #[cfg(all(debug_assertions, feature = "std"))]
+        if let Some(created) = sortlock_keys::record_manual_key(value, Location::caller()) {
+            panic!("Failed to create sort key: a manual key with the value {value} was already created at {created}.");
+        }
+
+        Self::manual(value).observed(Some(Location::caller()))
+    }
+
+    /// Creates a manual key without checking for duplicates.
+    ///
+    /// - `value` - The value of the key, which must be less than `2^61`.
+    pub(crate) const fn manual(value: u64) -> Self {
+        Self(Self::MANUAL_BIT | value)
+    }
+
+    /// Creates a sort key from the name of a lock.
+    ///
+    /// The key only depends on the name so the same name always produces the same key, across
+    /// runs, processes and machines. This allows keys to be compared when they are logged or
+    /// shared between replicas. Named keys sort after every key created with `new` and before
+    /// every manual key. Names are hashed so the order of named keys is unrelated to the order of
+    /// the names.
+    /// ```
+    /// use sortlock::{SortKey, SortMutex, LockGroup};
+    ///
+    /// let users = SortMutex::with_key(1, SortKey::from_name("db.users"));
+    ///
+    /// assert_eq!(SortKey::from_name("db.users"), users.sort_key());
+    /// assert_ne!(SortKey::from_name("db.orders"), users.sort_key());
+    /// ```
+    ///
+    /// Each name must only be used for a single lock. Different names are very unlikely to produce
+    /// the same key but this is not guaranteed.
+    ///
+    /// - `name` - The name of the lock.
+    pub const fn from_name(name: &str) -> Self {
+        /// The FNV-1a offset basis.
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        /// The FNV-1a prime.
+        const PRIME: u64 = 0x0100_0000_01b3;
+
+        let bytes = name.as_bytes();
+        let mut hash = OFFSET_BASIS;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+
+        Self(sortlock_keys::NAME_BIT | (hash & (sortlock_keys::NAME_BIT - 1)))
+    }
+
+    /// Creates a sort key from the address of a lock.
+    ///
+    /// The address must stay the same for as long as the lock exists, such as when the lock is
+    /// stored in an `Arc`, so no other lock can have the same key. Address keys sort after every
+    /// key in the early band and before every key created with `new`.
+    ///
+    /// - `address` - The address of the lock.
+    /// - `location` - Where the lock was created.
+    ///
+    /// # Panicking
+    /// This method will panic if the address is not below `2^57`, as it could then share a key
+    /// with a lock at another address.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_address(address: usize, location: &'static Location<'static>) -> Self {
+        let address = address as u64;
+
+        assert!(address < sortlock_keys::ADDRESS_BIT, "Failed to create sort key: address keys require addresses below 2^57.");
+
+        Self(sortlock_keys::ADDRESS_BIT | address).observed(Some(location))
+    }
+
+    /// Creates a new sort key for a lock in a hierarchy.
+    ///
+    /// Keys sort by level first so locks in a group are locked from the lowest level to the
+    /// highest. Hierarchical keys sort after every key created with `new`.
+    ///
+    /// - `level` - The level of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if every key for locks in a hierarchy has been used, unless the
+    /// exhaustion policy set with `set_exhaustion` allows a key of the same level to be reused.
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub(crate) fn with_level(level: u16) -> Self {
+        KeyCounter::level(&sortlock_keys::LEVEL_KEYS, level).next_key().observed(Some(Location::caller()))
+    }
+
+    /// Creates a sort key identifying a file.
+    ///
+    /// The key only depends on the device and inode of the file so every process derives the same
+    /// key for the same file. File keys sort after every key created with `new`. Both are hashed
+    /// into the key, so different files are very unlikely to have the same key but this is not
+    /// guaranteed.
+    ///
+    /// - `device` - The ID of the device containing the file.
+    /// - `inode` - The inode number of the file.
+    ///
+    /// # Panicking
+    /// In debug builds this method will panic if a different file was given the same key.
+    #[cfg(all(feature = "std", unix))]
+    pub(crate) fn from_file(device: u64, inode: u64) -> Self {
+        /// Mixes the bits of a value with the finalizer of SplitMix64, which is a bijection.
+        const fn mix(mut value: u64) -> u64 {
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            value ^ (value >> 31)
+        }
+
+        // The largest file key is one less than `NONE`.
+        let key = sortlock_keys::FILE_BIT | (mix(mix(device) ^ inode) % (sortlock_keys::FILE_BIT - 1));
+
+        #[cfg(debug_assertions)]
+        {
+            let mut files = FILE_KEYS.lock().unwrap_or_else(PoisonError::into_inner);
+            let &mut (other_device, other_inode) = files.entry(key).or_insert((device, inode));
+
+            assert!(
+                (other_device, other_inode) == (device, inode),
+                "Failed to create sort key: the file with device {device} and inode {inode} has the same key as the file with device {other_device} and inode {other_inode}."
+            );
+        }
+
+        Self(key)
+    }
+}
+
+/// A band of sort keys.
+///
+/// Keys created with `SortKey::new_in` sort by band first, then in the order they were created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Band {
+    /// Keys that sort before every other key, such as those of global metadata locks.
+    Early,
+    /// The band of keys created with `SortKey::new`.
+    Normal,
+    /// Keys that sort after every key created with `SortKey::new`.
+    Late,
+}
+
+/// What happens when every key in a band has been used.
+///
+/// The policy is set with `SortKey::set_exhaustion`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyExhaustion {
+    /// Panic when a new key is requested.
+    #[default]
+    Panic,
+    /// Reuse the key of a dropped lock in the same band that is known to no longer be in use, such
+    /// as one returned by a dropped `Recycled` lock, and panic if there is none.
+    ///
+    /// Reused keys are not created in any particular order, so the order of locks given reused
+    /// keys is unrelated to when they were created. Without the `alloc` feature no keys are
+    /// recorded as free so this is the same as `Panic`.
+    Wrap,
+}
+
+/// Counts the keys created in a band.
+///
+/// The counts are kept in `sortlock_keys` so they are shared with any other copy of this crate in
+/// the program.
+pub(crate) struct KeyCounter {
+    /// The number of keys created so far.
+    count: &'static AtomicCount,
+    /// The first key in the band.
+    start: u64,
+    /// The number of keys in the band.
+    len: u64,
+}
+
+impl KeyCounter {
+    /// Creates a new `KeyCounter`.
+    ///
+    /// The band is made smaller if the count cannot count every key in it.
+    ///
+    /// - `count` - The number of keys created so far.
+    /// - `start` - The first key in the band.
+    /// - `len` - The number of keys in the band.
+    #[allow(clippy::unnecessary_cast)]
+    pub(crate) const fn new(count: &'static AtomicCount, start: u64, len: u64) -> Self {
+        let max = Count::MAX as u64;
+
+        Self {
+            count,
+            start,
+            len: if len < max { len } else { max },
+        }
+    }
+
+    /// Creates a new `KeyCounter` for a level of the hierarchy.
+    ///
+    /// The count is shared by every level, so the keys of each level are not consecutive.
+    ///
+    /// - `count` - The number of keys created so far across every level.
+    /// - `level` - The level of the keys.
+    #[cfg(feature = "std")]
+    pub(crate) const fn level(count: &'static AtomicCount, level: u16) -> Self {
+        Self::new(count, sortlock_keys::LEVEL_BIT | (level as u64 * sortlock_keys::LEVEL_LEN), sortlock_keys::LEVEL_LEN)
+    }
+
+    /// Reserves consecutive keys, returning the first key or `None` if not enough keys are left.
+    ///
+    /// - `len` - The number of keys to reserve.
+    #[allow(clippy::useless_conversion)]
+    pub(crate) fn reserve(&self, len: u64) -> Option<u64> {
+        if len > self.len {
+            return None;
+        }
+
+        // The length of the band fits in a `Count`, so this cannot fail.
+        let len = Count::try_from(len).ok()?;
+
+        self.count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+            count.checked_add(len).filter(|&end| u64::from(end) <= self.len)
+        })
+            .ok()
+            .map(|count| self.start + u64::from(count))
+    }
+
+    /// Creates a new key, following the current exhaustion policy if the band has been exhausted.
+    pub(crate) fn next_key(&self) -> SortKey {
+        self.next_key_with(SortKey::exhaustion())
+    }
+
+    /// Creates a new key, following an exhaustion policy if the band has been exhausted.
+    ///
+    /// - `policy` - The exhaustion policy to follow.
+    pub(crate) fn next_key_with(&self, policy: KeyExhaustion) -> SortKey {
+        match self.reserve(1) {
+            Some(key) => SortKey(key),
+            None => SortKey::exhausted(policy, self.start),
+        }
+    }
+}
+
+/// A block of consecutive sort keys reserved for a subsystem.
+///
+/// Keys created from a key space sort in the order they were created, and every key from a space
+/// sorts before every key from a space reserved after it. By reserving a key space for each
+/// subsystem at start-up, in the order their locks should be locked, the locks of one subsystem
+/// are always locked before those of the next regardless of when the locks are created.
+/// ```
+/// use sortlock::{KeySpace, SortMutex, LockGroup};
+///
+/// let metadata = KeySpace::reserve(16);
+/// let items = KeySpace::reserve(1024);
+///
+/// let item = SortMutex::with_key(1, items.new_key());
+/// let table = SortMutex::with_key(2, metadata.new_key());
+///
+/// assert!(table.sort_key() < item.sort_key());
+/// ```
+pub struct KeySpace {
+    /// The next key to create from this space.
+    next: AtomicU64,
+    /// The end of this space, which is not part of it.
+    end: u64,
+}
+
+impl KeySpace {
+    /// Reserves a new key space.
+    ///
+    /// The keys sort after every key created with `SortKey::new` or from a key space before this
+    /// call, and before every such key created after it.
+    ///
+    /// - `len` - The number of keys in the space.
+    ///
+    /// # Panicking
+    /// This method will panic if fewer than `len` keys are left in the band of keys created with
+    /// `SortKey::new`.
+    pub fn reserve(len: u64) -> Self {
+        let start = NEXT_KEY.reserve(len)
+            .expect("Failed to reserve key space: not enough keys are left in the band.");
+
+        Self {
+            next: AtomicU64::new(start),
+            end: start + len,
+        }
+    }
+
+    /// Creates a new unique sort key from this space.
+    ///
+    /// # Panicking
+    /// This method will panic if every key in this space has already been created.
+    #[track_caller]
+    pub fn new_key(&self) -> SortKey {
+        let key = self.next.fetch_add(1, Ordering::Relaxed);
+
+        assert!(key < self.end, "Failed to create sort key: every key in the key space has been used.");
+
+        SortKey(key).observed(Some(Location::caller()))
+    }
+
+    /// Gets the number of keys that can still be created from this space.
+    pub fn remaining(&self) -> u64 {
+        self.end.saturating_sub(self.next.load(Ordering::Relaxed))
+    }
+}
+
+impl Debug for KeySpace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeySpace")
+            .field("remaining", &self.remaining())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A sort key that may be assigned when it is first used.
+///
+/// This allows locks to be created in `const` contexts where a key cannot be allocated.
+pub(crate) struct LazySortKey(AtomicU64);
+
+impl LazySortKey {
+    /// The value used to mark a key that has not yet been assigned.
+    const UNASSIGNED: u64 = u64::MAX;
+
+    /// Creates a new `LazySortKey` with an already assigned key.
+    ///
+    /// - `key` - The key to use.
+    pub(crate) const fn new(key: SortKey) -> Self {
+        Self(AtomicU64::new(key.0))
+    }
+
+    /// Creates a new `LazySortKey` that will be assigned when it is first used.
+    pub(crate) const fn unassigned() -> Self {
+        Self(AtomicU64::new(Self::UNASSIGNED))
+    }
+
+    /// Gets the key, assigning a new unique key if one has not yet been assigned.
+    pub(crate) fn get(&self) -> SortKey {
+        let key = self.0.load(Ordering::Relaxed);
+
+        if key != Self::UNASSIGNED {
+            return SortKey(key);
+        }
+
+        let new = NEXT_KEY.next_key();
+
+        match self.0.compare_exchange(Self::UNASSIGNED, new.0, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => new.observed(None),
+            Err(key) => SortKey(key),
+        }
+    }
+}
+
+/// Releases a manual key so its value can be used by a new lock.
+#[cfg(all(debug_assertions, feature = "std"))]
+impl Drop for LazySortKey {
+    fn drop(&mut self) {
+        let key = *self.0.get_mut();
+
+        if key != Self::UNASSIGNED && sortlock_keys::band_start(key) == SortKey::MANUAL_BIT {
+            sortlock_keys::release_manual_key(key & !SortKey::MANUAL_BIT);
+        }
+    }
+}
+
+/// A key is serialized as its value.
+#[cfg(feature = "serde")]
+impl Serialize for SortKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// A key is deserialized from its value.
+///
+/// A deserialized key should only be compared with other keys. Using it as the key of a new lock
+/// may give the lock the same key as an existing lock.
+#[cfg(feature = "serde")]
+impl <'de> Deserialize<'de> for SortKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Self)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Mutex};
+
+    use sortlock_keys::AtomicCount;
+
+    use crate::{Band, KeyExhaustion, KeySpace, LockGroup, SortKey, SortMutex, key::KeyCounter};
+
+    #[test]
+    fn test_manual_keys() {
+        let lock2 = SortMutex::with_key(2, SortKey::from_value(2));
+        let lock1 = SortMutex::with_key(1, SortKey::from_value(1));
+        let lock3 = SortMutex::new(3);
+
+        assert!(lock1.sort_key() < lock2.sort_key());
+        assert!(lock3.sort_key() < lock1.sort_key());
+
+        let (guard2, guard1) = (lock2.lock(), lock1.lock()).lock_all();
+        assert_eq!(3, *guard1 + *guard2);
+    }
+
+    #[test]
+    fn test_key_space() {
+        let space1 = KeySpace::reserve(2);
+        let space2 = KeySpace::reserve(2);
+
+        let lock2 = SortMutex::with_key(2, space2.new_key());
+        let lock1 = SortMutex::with_key(1, space1.new_key());
+        let lock3 = SortMutex::with_key(3, space1.new_key());
+        assert!(lock1.sort_key() < lock3.sort_key());
+        assert!(lock3.sort_key() < lock2.sort_key());
+        assert!(lock2.sort_key() < SortKey::new());
+
+        assert_eq!(0, space1.remaining());
+        assert_eq!(1, space2.remaining());
+        assert!(std::panic::catch_unwind(|| space1.new_key()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_keys() {
+        let lock = SortMutex::new(1);
+        let key = lock.sort_key();
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(key.value().to_string(), json);
+        assert_eq!(key, serde_json::from_str(&json).unwrap());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_address_keys() {
+        let early = SortKey::new_in(Band::Early);
+        let lock1 = SortMutex::new_arc(1);
+        let lock2 = SortMutex::new_arc(2);
+
+        assert_ne!(lock1.sort_key(), lock2.sort_key());
+        assert!(early < lock1.sort_key());
+        assert!(lock1.sort_key() < SortKey::new());
+
+        let (guard2, guard1) = (lock2.lock(), lock1.lock()).lock_all();
+        assert_eq!(3, *guard1 + *guard2);
+    }
+
+    #[test]
+    fn test_key_batch() {
+        let keys: Vec<_> = SortKey::new_batch(3).collect();
+        let next = SortKey::new();
+
+        assert_eq!(3, keys.len());
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(keys[2] < next);
+        assert_eq!(0, SortKey::new_batch(0).len());
+    }
+
+    #[test]
+    fn test_key_bands() {
+        let late = SortMutex::with_key(3, SortKey::new_in(Band::Late));
+        let normal = SortMutex::with_key(2, SortKey::new_in(Band::Normal));
+        let early = SortMutex::with_key(1, SortKey::new_in(Band::Early));
+
+        assert!(early.sort_key() < SortMutex::new(0).sort_key());
+        assert!(normal.sort_key() < SortKey::new());
+        assert!(SortKey::new() < late.sort_key());
+
+        let (guard3, guard1, guard2) = (late.lock(), early.lock(), normal.lock()).lock_all();
+        assert_eq!(6, *guard1 + *guard2 + *guard3);
+    }
+
+    #[test]
+    fn test_key_exhaustion() {
+        static COUNT: AtomicCount = AtomicCount::new(0);
+
+        let counter = KeyCounter::new(&COUNT, 0, 2);
+        let key1 = counter.next_key_with(KeyExhaustion::Panic);
+        let key2 = counter.next_key_with(KeyExhaustion::Panic);
+
+        assert!(key1 < key2);
+        assert_eq!(None, counter.reserve(1));
+        assert!(std::panic::catch_unwind(|| counter.next_key_with(KeyExhaustion::Panic)).is_err());
+
+        #[cfg(feature = "alloc")]
+        {
+            // Only keys from the band of the counter are reused.
+            SortKey::new().free();
+            assert!(std::panic::catch_unwind(|| counter.next_key_with(KeyExhaustion::Wrap)).is_err());
+
+            // Other tests may take free keys at the same time, but never more than eight.
+            (0..8).for_each(|_| SortKey::new_in(Band::Early).free());
+            assert!(counter.next_key_with(KeyExhaustion::Wrap) < SortKey::new_in(Band::Early));
+        }
+    }
+
+    #[cfg(all(feature = "alloc", target_pointer_width = "64"))]
+    #[test]
+    #[should_panic(expected = "Failed to create sort key: address keys")]
+    fn test_high_address() {
+        SortKey::from_address(1 << 57, std::panic::Location::caller());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_level_key_exhaustion() {
+        static COUNT: AtomicCount = AtomicCount::new(0);
+
+        let counter = KeyCounter::level(&COUNT, 3);
+        assert!(counter.reserve(counter.len - 1).is_some());
+
+        // The last key stays within its level rather than wrapping into the next.
+        let last = counter.next_key_with(KeyExhaustion::Panic);
+        assert!(SortKey::with_level(3) < last);
+        assert!(last < SortKey::with_level(4));
+        assert_eq!(None, counter.reserve(1));
+        assert!(std::panic::catch_unwind(|| counter.next_key_with(KeyExhaustion::Panic)).is_err());
+    }
+
+    #[cfg(feature = "narrow-keys")]
+    #[test]
+    fn test_narrow_keys() {
+        static COUNT: AtomicCount = AtomicCount::new(0);
+
+        // The band is cut short at the largest count.
+        let counter = KeyCounter::new(&COUNT, sortlock_keys::LATE_START, sortlock_keys::LATE_LEN);
+
+        assert_eq!(None, counter.reserve(u64::from(u32::MAX) + 1));
+        assert_eq!(Some(sortlock_keys::LATE_START), counter.reserve(u64::from(u32::MAX) - 1));
+        assert_eq!(Some(sortlock_keys::LATE_START + u64::from(u32::MAX) - 1), counter.reserve(1));
+        assert_eq!(None, counter.reserve(1));
+        assert!(std::panic::catch_unwind(|| counter.next_key_with(KeyExhaustion::Panic)).is_err());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_free_key_bands() {
+        let late = SortKey::new_in(Band::Late);
+        let manual = SortKey::manual(1 << 40);
+
+        late.free();
+        manual.free();
+
+        assert_eq!(Some(manual), SortKey::take_free(SortKey::MANUAL_BIT));
+        assert_eq!(Some(late), SortKey::take_free(sortlock_keys::LATE_START));
+        assert_eq!(None, SortKey::take_free(sortlock_keys::LATE_START));
+        assert_eq!(None, SortKey::take_free(SortKey::MANUAL_BIT));
+    }
+
+    #[test]
+    fn test_key_observer() {
+        static CREATED: Mutex<Vec<(SortKey, Option<u32>)>> = Mutex::new(Vec::new());
+
+        SortKey::set_observer(Some(|key, location| {
+            CREATED.lock().unwrap().push((key, location.map(|location| location.line())));
+        }));
+
+        let line = line!();
+        let lock = SortMutex::new(1);
+        let lazy = SortMutex::const_new(2);
+        let lazy_key = lazy.sort_key();
+        SortKey::set_observer(None);
+
+        // Other tests may create keys at the same time.
+        let created = CREATED.lock().unwrap();
+        assert!(created.contains(&(lock.sort_key(), Some(line + 1))));
+        assert!(created.contains(&(lazy_key, None)));
+    }
+
+    #[test]
+    fn test_shared_counters() {
+        // Another copy of this crate has its own counter over the shared count and band layout.
+        let other = KeyCounter::new(&sortlock_keys::NORMAL_KEYS, sortlock_keys::NORMAL_START, sortlock_keys::NORMAL_LEN);
+
+        let keys: Vec<_> = (0..64).flat_map(|_| [SortKey::new(), other.next_key()]).collect();
+
+        assert_eq!(keys.len(), keys.iter().collect::<HashSet<_>>().len());
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_named_keys() {
+        let key = SortKey::from_name("db.users");
+
+        assert_eq!(key, SortKey::from_name("db.users"));
+        assert_ne!(key, SortKey::from_name("db.orders"));
+        assert!(SortKey::new() < key);
+        assert!(key < SortKey::from_value(4));
+
+        // The key must never change between releases.
+        assert_eq!("SortKey(1379123497749416245)", format!("{:?}", key));
+    }
+
+    #[cfg(all(debug_assertions, feature = "std"))]
+    #[test]
+    #[should_panic]
+    fn test_duplicate_manual_keys() {
+        SortKey::from_value(3);
+        SortKey::from_value(3);
+    }
+
+    #[cfg(all(debug_assertions, feature = "std"))]
+    #[test]
+    fn test_manual_key_after_drop() {
+        let lock = SortMutex::with_key(1, SortKey::from_value(5));
+        drop(lock);
+
+        let lock = SortMutex::with_key(2, SortKey::from_value(5));
+        assert!(std::panic::catch_unwind(|| SortKey::from_value(5)).is_err());
+        assert_eq!(2, *lock.lock().lock_all());
+    }
+}
@@ -42,19 +42,229 @@
 //! 
 //! # Feature Flags
 //! To support `no-std` environments this crate can fall back to using `spin`'s `Mutex` and `RwLock` types. This can be done by disabiling the `std` feature.
+//!
+//! The `alloc` feature, which is enabled by `std`, adds APIs that require an allocator such as
+//! owned guards and lock groups stored in a `Vec`.
+//!
+//! The `derive` feature adds a derive macro for `LockGroup` so that a struct of requests can be
+//! locked together.
+//!
+//! The `either` feature allows `Either` to be used to choose between two different types of lock.
+//!
+//! The `lock_api` feature adds `RawSortMutex` and the `Sorted` adapter so that locks built with the
+//! `lock_api` crate, such as those from `parking_lot`, can be sorted with other locks.
+//!
+//! The `parking_lot` feature adds `ParkingLotBackend`, a `SortMutex` backend that is never poisoned and
+//! supports locking with a timeout and fair unlocking. It also enables the `lock_api` feature so
+//! `parking_lot`'s reader-writer lock can be sorted with `Sorted<parking_lot::RawRwLock>`.
+//!
+//! The `critical-section` feature adds `CriticalSectionBackend`, a `SortMutex` backend for bare-metal
+//! targets that holds a critical section while locked so locks can be shared with interrupt handlers.
+//!
+//! The `tokio` feature adds async locks such as `SortAsyncMutex`, `SortAsyncRwLock`,
+//! `SortAsyncCondvar` and `SortAsyncSemaphore`, built on `tokio`'s synchronization primitives, that
+//! can be locked in a group from within a task using `AsyncLockGroup`.
+//!
+//! The `async-lock` feature adds the same async locks built on the `async-lock` crate instead, for
+//! projects that use other executors such as `smol`. If both features are enabled `tokio` is used.
+//!
+//! The `smol` and `async-std` features enable `async-lock` and add `lock_all_timeout` to
+//! `AsyncLockGroup` using `async-io`'s timer, which both executors run on, so these projects have
+//! the same API as with `tokio`. With the `tokio` feature also enabled, `tokio`'s timer is used
+//! within a `tokio` runtime and `async-io`'s timer elsewhere.
+//!
+//! The `embassy` feature adds `SortEmbassyMutex`, an async lock built on `embassy-sync`'s `Mutex`
+//! that can be locked in a group with `AsyncLockGroup` from embedded firmware.
+//!
+//! The `log` feature adds `lock_all_watched` and `lock_all_watchdog` to `AsyncLockGroup`, which
+//! log a warning with the pending keys when a group has waited longer than a threshold. Where a
+//! `SortAsyncMutex` is holding up the group the warning also names where its holder requested it.
+//!
+//! The `serde` feature implements `Serialize` and `Deserialize` for `SortKey` so keys can be recorded
+//! in logs, traces and crash reports and correlated later.
+//!
+//! The `narrow-keys` feature counts the keys created in each band with a `u32` instead of a `u64`,
+//! for small targets where 64-bit atomics are emulated with locks. This limits each band to
+//! `u32::MAX` keys, after which `SortKey::new` follows the policy set with `SortKey::set_exhaustion`.
+//!
+//! A function can be registered with `SortKey::set_observer` to be called with every new sort key
+//! and the location of the code that created it, so tools such as leak detectors and lock auditors
+//! can track every lock the process creates.
+//!
+//! The `diagnostics` feature records where each `SortMutex` and `SortRwLock` was created, available
+//! from `created_at` and included in the panic when a poisoned lock is locked, so the lock behind a
+//! key can be identified.
+//!
+//! A `SortMutex` can be given a name with `new_named`, which is included in its `Debug` output and in
+//! the panic when it is poisoned so the lock can be identified while triaging.
+//!
+//! Sort keys are counted in the `sortlock-keys` crate, which every version of `sortlock` depends on.
+//! Cargo links a single copy of it, so when several versions of `sortlock` end up in one program their
+//! locks still get unique keys and are locked in a consistent order.
 
 #![cfg_attr(not(test), no_std)]
 
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// Allows code generated by the derive macros to refer to this crate from within it.
+#[cfg(feature = "derive")]
+extern crate self as sortlock;
+
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, vec::Vec};
+
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+#[cfg(feature = "either")]
+use either::Either;
+
 mod mutex;
+mod backend;
 mod key;
 mod rwlock;
-
-pub use key::SortKey;
-pub use mutex::{SortMutex, SortMutexGuard};
-pub use rwlock::{SortRwLock, SortReadGuard, SortWriteGuard};
+mod group;
+mod async_group;
+mod macros;
+mod semaphore;
+mod once;
+mod seqlock;
+mod fair;
+mod spinlock;
+mod multi;
+mod external;
+mod static_key;
+#[cfg(feature = "std")]
+mod condvar;
+#[cfg(feature = "std")]
+mod reentrant;
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+mod map;
+#[cfg(feature = "std")]
+mod striped;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod hierarchy;
+#[cfg(all(feature = "std", unix))]
+mod file;
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod futex;
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod pi;
+#[cfg(feature = "alloc")]
+mod dynamic;
+#[cfg(feature = "alloc")]
+mod rcu;
+#[cfg(feature = "alloc")]
+mod recycle;
+#[cfg(feature = "lock_api")]
+mod raw;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_mutex;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_rwlock;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_condvar;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_fair;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_semaphore;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+mod async_held;
+#[cfg(feature = "embassy")]
+mod embassy;
+
+pub use key::{Band, KeyExhaustion, KeyObserver, KeySpace, SortKey};
+pub use backend::{MutexBackend, DefaultBackend, PoisonBackend, SpinBackend};
+#[cfg(feature = "std")]
+pub use backend::StdBackend;
+#[cfg(feature = "parking_lot")]
+pub use backend::ParkingLotBackend;
+#[cfg(feature = "critical-section")]
+pub use backend::{CriticalSectionBackend, CriticalSectionBackendGuard};
+pub use group::{ChainedOrder, ExtendError, GroupGuard, StaticGroup};
+use group::FlatOrder;
+pub use async_group::{AsyncSortableLock, AsyncLockGroup, AsyncNestedLockGroup, LockTimeoutError};
+#[cfg(all(feature = "alloc", any(feature = "tokio", feature = "async-lock")))]
+pub use async_group::LockFuture;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+pub use async_mutex::{SortAsyncMutex, SortAsyncMutexGuard, SortAsyncMutexLocked, MappedSortAsyncMutexLocked};
+#[cfg(all(feature = "alloc", any(feature = "tokio", feature = "async-lock")))]
+pub use async_mutex::{OwnedSortAsyncMutexGuard, OwnedSortAsyncMutexLocked};
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+pub use async_condvar::SortAsyncCondvar;
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+pub use async_fair::{SortAsyncFairMutex, SortAsyncFairMutexGuard, SortAsyncFairMutexLocked};
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+pub use async_semaphore::{SortAsyncSemaphore, SortAsyncSemaphoreGuard, SortAsyncSemaphoreLocked};
+#[cfg(any(feature = "tokio", feature = "async-lock"))]
+pub use async_rwlock::{
+    SortAsyncRwLock, SortAsyncReadGuard, SortAsyncWriteGuard, SortAsyncReadLocked, SortAsyncWriteLocked,
+    MappedSortAsyncReadLocked, MappedSortAsyncWriteLocked
+};
+#[cfg(feature = "embassy")]
+pub use embassy::{SortEmbassyMutex, SortEmbassyMutexGuard, SortEmbassyMutexLocked};
+#[cfg(feature = "alloc")]
+pub use dynamic::DynSortableLock;
+#[cfg(feature = "alloc")]
+pub use rcu::{SortRcu, SortRcuUpdateGuard, SortRcuUpdateLocked};
+#[cfg(feature = "alloc")]
+pub use recycle::{Recycled, RecycledValue};
+#[cfg(feature = "derive")]
+pub use sortlock_derive::LockGroup;
+#[cfg(feature = "lock_api")]
+pub use raw::{RawSortMutex, Sorted, SortedReadGuard, SortedWriteGuard};
+pub use mutex::{SortMutex, SortMutexGuard, SortMutexLocked, MappedSortMutexLocked};
+#[cfg(feature = "alloc")]
+pub use mutex::{OwnedSortMutexGuard, OwnedSortMutexLocked};
+pub use rwlock::{
+    SortRwLock, SortReadGuard, SortWriteGuard, SortUpgradableReadGuard, SortRecursiveReadGuard,
+    SortReadLocked, SortWriteLocked, SortUpgradableReadLocked, SortRecursiveReadLocked,
+    MappedSortReadLocked, MappedSortWriteLocked
+};
+pub use once::{SortOnceLock, SortOnceGuard, SortLazyLock};
+pub use multi::{MultiLock, MultiLockValues};
+pub use external::{SortableExternal, SortableExternalGuard, SortableExternalLocked};
+pub use static_key::{StaticSortableLock, StaticLockGroup, StaticSortMutex, StaticSortMutexGuard};
+pub use fair::{FairBackend, FairBackendGuard, SortFairMutex, SortFairMutexGuard, SortFairMutexLocked};
+pub use spinlock::{SortSpinMutex, SortSpinMutexGuard, SortSpinMutexLocked};
+pub use seqlock::{NoUninit, SortSeqLock, SortSeqWriteGuard, SortSeqWriteLocked};
+pub use semaphore::{SortSemaphore, SortSemaphoreGuard, SortSemaphoreLocked};
+#[cfg(feature = "std")]
+pub use condvar::SortCondvar;
+#[cfg(all(feature = "std", unix))]
+pub use file::{SortFileLock, SortFileLockGuard, SortFileLocked};
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use futex::{FutexBackend, FutexBackendGuard};
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use pi::{PiBackend, PiBackendGuard, SortPiMutex, SortPiMutexGuard, SortPiMutexLocked};
+#[cfg(feature = "std")]
+pub use reentrant::{SortReentrantMutex, SortReentrantMutexGuard, SortReentrantMutexLocked};
+#[cfg(feature = "std")]
+pub use map::{SortLockMap, Entry, OccupiedEntry, VacantEntry, LockedEntry};
+#[cfg(feature = "std")]
+pub use hierarchy::{HierarchicalMutex, HierarchicalMutexGuard, HierarchicalMutexLocked};
+#[cfg(feature = "std")]
+pub use registry::LockRegistry;
+#[cfg(feature = "std")]
+pub use striped::{SortStripedLock, SortStripedLocked};
+#[cfg(feature = "std")]
+pub use sharded::{
+    SortShardedLock, SortShardedReadGuard, SortShardedWriteGuard, SortShardedReadLocked, SortShardedWriteLocked
+};
+#[cfg(feature = "std")]
+pub use rwlock::{SortCheckedReadGuard, SortCheckedWriteGuard};
+#[cfg(feature = "alloc")]
+pub use rwlock::{OwnedSortReadGuard, OwnedSortWriteGuard, OwnedSortReadLocked, OwnedSortWriteLocked};
 
 /// A lock that can be locked in a way that ensures that multiple locks are always locked in the
 /// same order..
@@ -70,6 +280,190 @@ pub trait SortableLock {
     /// This method assumes that lock sorting has already been done.
     /// `lock_all` from `LockGroup` should be used if you want to lock with sorting. 
     fn lock_presorted(&self) -> Self::Guard;
+
+    /// Gets the kind of access this request has to its lock.
+    ///
+    /// This is used to detect groups containing requests for the same lock that can never be
+    /// locked together. By default requests are assumed to be exclusive.
+    fn access(&self) -> LockAccess {
+        LockAccess::Exclusive
+    }
+}
+
+/// The kind of access a request has to its lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockAccess {
+    /// Access that can be shared with other shared and upgradable requests.
+    Shared,
+    /// Shared access that excludes other upgradable requests.
+    Upgradable,
+    /// Access that excludes all other requests.
+    Exclusive,
+}
+
+/// Runs a closure and then runs a second closure, even if the first one panics.
+///
+/// This is used by `unlocked` to reacquire locks so a guard is never left without its lock.
+///
+/// - `f` - The closure to run.
+/// - `then` - The closure to run afterwards.
+fn finally<R, F: FnOnce() -> R, T: FnOnce()>(f: F, then: T) -> R {
+    /// Runs the closure when dropped.
+    struct Finally<T: FnOnce()>(Option<T>);
+
+    impl <T: FnOnce()> Drop for Finally<T> {
+        fn drop(&mut self) {
+            if let Some(then) = self.0.take() {
+                then();
+            }
+        }
+    }
+
+    let _finally = Finally(Some(then));
+
+    f()
+}
+
+/// Panics if a group contains requests for the same lock that can never be locked together.
+///
+/// Locking such a group would otherwise deadlock, for example when a group contains both a read
+/// and a write request for the same `SortRwLock`.
+///
+/// - `sorted` - The sort key and access of each request in the group in sorted order.
+fn check_conflicts<I: IntoIterator<Item = (SortKey, LockAccess)>>(sorted: I) {
+    let mut previous = None;
+    let mut upgradable = false;
+
+    for (key, access) in sorted {
+        if previous.is_some_and(|(previous_key, _)| previous_key == key) {
+            let conflict = match (previous.map(|(_, access)| access), access) {
+                (Some(LockAccess::Exclusive), _) | (_, LockAccess::Exclusive) => true,
+                (_, LockAccess::Upgradable) => upgradable,
+                _ => false,
+            };
+
+            if conflict {
+                panic!("Failed to lock group: the group contains conflicting requests for the same lock. Use a single write request instead.");
+            }
+        } else {
+            upgradable = false;
+        }
+
+        upgradable |= access == LockAccess::Upgradable;
+        previous = Some((key, access));
+    }
+}
+
+/// A lock that is only locked if it is present.
+///
+/// This allows a lock to conditionally be part of a group. Locking `None` does nothing.
+/// ```
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let needs_lock2 = false;
+///
+/// let (guard1, guard2) = (lock1.lock(), needs_lock2.then(|| lock2.lock())).lock_all();
+/// assert_eq!(1, *guard1);
+/// assert!(guard2.is_none());
+/// ```
+impl <L: SortableLock> SortableLock for Option<L> {
+    type Guard = Option<L::Guard>;
+
+    fn sort_key(&self) -> SortKey {
+        self.as_ref().map_or(SortKey::NONE, SortableLock::sort_key)
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.as_ref().map(SortableLock::lock_presorted)
+    }
+
+    fn access(&self) -> LockAccess {
+        self.as_ref().map_or(LockAccess::Shared, SortableLock::access)
+    }
+}
+
+/// Either of two locks.
+///
+/// This allows one of two different types of lock to be chosen at runtime without changing the
+/// type of the group.
+/// ```
+/// use either::Either;
+/// use sortlock::{SortMutex, SortRwLock, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortRwLock::new(2);
+/// let lock3 = SortMutex::new(3);
+///
+/// let request = if true { Either::Left(lock1.lock()) } else { Either::Right(lock2.read()) };
+///
+/// let (guard, guard3) = (request, lock3.lock()).lock_all();
+/// assert_eq!(4, *guard + *guard3);
+/// ```
+#[cfg(feature = "either")]
+impl <L: SortableLock, R: SortableLock> SortableLock for Either<L, R> {
+    type Guard = Either<L::Guard, R::Guard>;
+
+    fn sort_key(&self) -> SortKey {
+        either::for_both!(self, lock => lock.sort_key())
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.as_ref()
+            .map_either(SortableLock::lock_presorted, SortableLock::lock_presorted)
+    }
+
+    fn access(&self) -> LockAccess {
+        either::for_both!(self, lock => lock.access())
+    }
+}
+
+/// A group member that locks nothing.
+impl SortableLock for () {
+    type Guard = ();
+
+    fn sort_key(&self) -> SortKey {
+        SortKey::NONE
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {}
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A group member that passes a plain value through without locking anything.
+///
+/// This allows generic code to place values that are not locks in a group. The value is cloned
+/// when the group is locked so this is best suited to `Copy` values such as references.
+/// ```
+/// use sortlock::{SortMutex, LockGroup, Plain};
+///
+/// let lock = SortMutex::new(1);
+///
+/// let (guard, value) = (lock.lock(), Plain(2)).lock_all();
+/// assert_eq!(3, *guard + value);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Plain<T>(pub T);
+
+impl <T: Clone> SortableLock for Plain<T> {
+    type Guard = T;
+
+    fn sort_key(&self) -> SortKey {
+        SortKey::NONE
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.0.clone()
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
 }
 
 /// A group of values that can be locked.
@@ -81,7 +475,36 @@ pub trait LockGroup {
     ///
     /// The locking order will be consistent regardless of the order of the locks within in this
     /// group.
+    ///
+    /// # Panicking
+    /// This method will panic if the group contains requests for the same lock that can never be
+    /// held together, such as a read and a write request for the same `SortRwLock`, as locking
+    /// them would deadlock.
     fn lock_all(self) -> Self::Locked;
+
+    /// Lock all items in the group, run a closure with the locked group and then unlock them.
+    ///
+    /// The locking order is the same as `lock_all`. The locked group is dropped when the closure
+    /// returns, so its locks are released before this method returns unless the closure moves a
+    /// guard out of the group, such as by swapping it with another guard or taking it from an
+    /// `Option`.
+    /// ```
+    /// use sortlock::{SortMutex, LockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let sum = (lock1.lock(), lock2.lock()).lock_all_with(|(guard1, guard2)| {
+    ///     **guard1 += 1;
+    ///     **guard1 + **guard2
+    /// });
+    /// assert_eq!(4, sum);
+    /// ```
+    ///
+    /// - `f` - The closure to run with the locked group.
+    fn lock_all_with<R, F: FnOnce(&mut Self::Locked) -> R>(self, f: F) -> R where Self: Sized {
+        f(&mut self.lock_all())
+    }
 }
 
 impl <T: SortableLock> LockGroup for T {
@@ -92,362 +515,469 @@ impl <T: SortableLock> LockGroup for T {
     }
 }
 
-impl <T1: SortableLock, T2: SortableLock> LockGroup for (T1, T2) {
-    type Locked = (T1::Guard, T2::Guard);
+/// A group of values that can be locked while recording the order in which they were locked.
+///
+/// This allows a locked group to be released in the reverse of the order it was locked in.
+pub trait OrderedLockGroup: LockGroup {
+    /// The order in which the members of the group were locked.
+    ///
+    /// Each entry contains the index of a member within the group and its sort key.
+    type Order: AsRef<[(usize, SortKey)]> + AsMut<[(usize, SortKey)]>;
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [(0, self.0.sort_key()), (1, self.1.sort_key())];
+    /// Lock all items in the group, returning the locked group and the order it was locked in.
+    ///
+    /// The locking order will be consistent regardless of the order of the locks within in this
+    /// group.
+    fn lock_ordered(&self) -> (Self::Locked, Self::Order);
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+    /// Release a locked group in the reverse of the order it was locked in.
+    ///
+    /// - `locked` - The locked group.
+    /// - `order` - The order the group was locked in.
+    fn release_ordered(locked: Self::Locked, order: &Self::Order);
+
+    /// Lock all items in the group, returning a `GroupGuard` that releases them in the reverse of
+    /// the order they were locked in.
+    /// ```
+    /// use sortlock::{SortMutex, OrderedLockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let mut guard = (lock1.lock(), lock2.lock()).lock_all_guarded();
+    /// *guard.0 += *guard.1;
+    /// guard.unlock_all();
+    /// ```
+    fn lock_all_guarded(self) -> GroupGuard<Self> where Self: Sized {
+        GroupGuard::new(self)
+    }
+}
 
-        let mut guards = (None, None);
+impl <T: SortableLock> OrderedLockGroup for T {
+    type Order = [(usize, SortKey); 1];
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+    fn lock_ordered(&self) -> (Self::Locked, Self::Order) {
+        let key = self.sort_key();
 
-        (guards.0.unwrap(), guards.1.unwrap())
+        (self.lock_presorted(), [(0, key)])
+    }
+
+    fn release_ordered(locked: Self::Locked, _order: &Self::Order) {
+        drop(locked);
     }
 }
 
-impl <T1: SortableLock, T2: SortableLock, T3: SortableLock> LockGroup for (T1, T2, T3) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard);
+impl <L: SortableLock, const N: usize> LockGroup for [L; N] {
+    type Locked = [L::Guard; N];
 
     fn lock_all(self) -> Self::Locked {
-        let mut locks = [(0, self.0.sort_key()), (1, self.1.sort_key()), (2, self.2.sort_key())];
+        self.lock_ordered().0
+    }
+}
+
+impl <L: SortableLock, const N: usize> OrderedLockGroup for [L; N] {
+    type Order = [(usize, SortKey); N];
+
+    fn lock_ordered(&self) -> (Self::Locked, Self::Order) {
+        let mut locks = core::array::from_fn(|i| (i, self[i].sort_key()));
 
         locks.sort_unstable_by_key(|(_, key)| *key);
+        check_conflicts(locks.iter().map(|(i, key)| (*key, self[*i].access())));
 
-        let mut guards = (None, None, None);
+        let mut guards = core::array::from_fn::<_, N, _>(|_| None);
 
         for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                _ => unreachable!(),
-            }
+            guards[i] = Some(self[i].lock_presorted());
         }
 
-        (guards.0.unwrap(), guards.1.unwrap(), guards.2.unwrap())
+        (guards.map(Option::unwrap), locks)
+    }
+
+    fn release_ordered(locked: Self::Locked, order: &Self::Order) {
+        let mut guards = locked.map(Some);
+
+        for (i, _) in order.iter().rev() {
+            drop(guards[*i].take());
+        }
     }
 }
 
-impl <T1: SortableLock, T2: SortableLock, T3: SortableLock, T4: SortableLock> LockGroup for (T1, T2, T3, T4) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard);
+/// A group that can be nested within a tuple group.
+///
+/// Rather than locking the nested group as a whole, its locks take part in the same sort as every
+/// other lock in the outer group. This means nesting groups cannot change the order locks are
+/// locked in.
+/// ```
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+/// let lock3 = SortMutex::new(3);
+///
+/// // Locked in the order lock1, lock2, lock3.
+/// let ((guard3, guard1), guard2) = ((lock3.lock(), lock1.lock()), lock2.lock()).lock_all();
+/// assert_eq!(6, *guard1 + *guard2 + *guard3);
+/// ```
+///
+/// Each lock in a nested group is referred to by its index when the group is flattened.
+pub trait NestedLockGroup: OrderedLockGroup<Order: FlatOrder> {
+    /// The number of locks in the group once flattened.
+    const LEN: usize;
+
+    /// The group while only some of its locks have been locked.
+    type Partial;
+
+    /// Gets the sort keys of every lock in the group in flattened order.
+    fn unsorted_order(&self) -> Self::Order;
+
+    /// Creates a group state where none of the locks have been locked.
+    fn unlocked_partial() -> Self::Partial;
+
+    /// Locks a single lock in the group.
+    ///
+    /// This method assumes that lock sorting has already been done.
+    ///
+    /// - `index` - The flattened index of the lock.
+    /// - `partial` - The group state to store the guard in.
+    fn lock_member(&self, index: usize, partial: &mut Self::Partial);
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key())
-        ];
+    /// Gets the kind of access a single lock in the group has.
+    ///
+    /// - `index` - The flattened index of the lock.
+    fn member_access(&self, index: usize) -> LockAccess;
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+    /// Converts a group state where all locks have been locked into the locked group.
+    ///
+    /// - `partial` - The group state.
+    fn complete_partial(partial: Self::Partial) -> Self::Locked;
 
-        let mut guards = (None, None, None, None);
+    /// Converts a locked group into a group state so that its locks can be released individually.
+    ///
+    /// - `locked` - The locked group.
+    fn split_locked(locked: Self::Locked) -> Self::Partial;
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+    /// Releases a single lock in the group.
+    ///
+    /// - `index` - The flattened index of the lock.
+    /// - `partial` - The group state containing the guard.
+    fn release_member(index: usize, partial: &mut Self::Partial);
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap()
-        )
+    /// Gets the order the group would be locked in without locking it.
+    ///
+    /// Each entry contains the flattened index of a lock within the group and its sort key.
+    /// ```
+    /// use sortlock::{SortMutex, NestedLockGroup};
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let order = (lock2.lock(), lock1.lock()).acquisition_order();
+    /// assert_eq!(vec![1, 0], order.as_ref().iter().map(|(i, _)| *i).collect::<Vec<_>>());
+    /// ```
+    fn acquisition_order(&self) -> Self::Order {
+        let mut order = self.unsorted_order();
+
+        order.as_mut().sort_unstable_by_key(|(_, key)| *key);
+
+        order
     }
 }
 
-impl <T1: SortableLock, T2: SortableLock, T3: SortableLock, T4: SortableLock, T5: SortableLock> LockGroup for (T1, T2, T3, T4, T5) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard);
+impl <T: SortableLock> NestedLockGroup for T {
+    const LEN: usize = 1;
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-        ];
+    type Partial = Option<T::Guard>;
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+    fn unsorted_order(&self) -> Self::Order {
+        [(0, self.sort_key())]
+    }
 
-        let mut guards = (None, None, None, None, None);
+    fn unlocked_partial() -> Self::Partial {
+        None
+    }
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+    fn lock_member(&self, _index: usize, partial: &mut Self::Partial) {
+        *partial = Some(self.lock_presorted());
+    }
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap()
-        )
+    fn member_access(&self, _index: usize) -> LockAccess {
+        self.access()
+    }
+
+    fn complete_partial(partial: Self::Partial) -> Self::Locked {
+        partial.expect("all locks are locked")
+    }
+
+    fn split_locked(locked: Self::Locked) -> Self::Partial {
+        Some(locked)
+    }
+
+    fn release_member(_index: usize, partial: &mut Self::Partial) {
+        drop(partial.take());
     }
 }
 
-impl <T1: SortableLock, T2: SortableLock, T3: SortableLock, T4: SortableLock, T5: SortableLock, T6: SortableLock> LockGroup for (T1, T2, T3, T4, T5, T6) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard, T6::Guard);
+#[cfg(feature = "alloc")]
+impl <L: SortableLock> LockGroup for Vec<L> {
+    type Locked = Vec<L::Guard>;
 
     fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-            (5, self.5.sort_key())
-        ];
+        self.lock_ordered().0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <L: SortableLock> OrderedLockGroup for Vec<L> {
+    type Order = Vec<(usize, SortKey)>;
+
+    fn lock_ordered(&self) -> (Self::Locked, Self::Order) {
+        let mut locks: Vec<_> = self.iter()
+            .map(SortableLock::sort_key)
+            .enumerate()
+            .collect();
 
         locks.sort_unstable_by_key(|(_, key)| *key);
+        check_conflicts(locks.iter().map(|(i, key)| (*key, self[*i].access())));
 
-        let mut guards = (None, None, None, None, None, None);
+        let mut guards: Vec<_> = self.iter().map(|_| None).collect();
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                5 => guards.5 = Some(self.5.lock_presorted()),
-                _ => unreachable!(),
-            }
+        for (i, _) in &locks {
+            guards[*i] = Some(self[*i].lock_presorted());
         }
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap(),
-            guards.5.unwrap(),
-        )
+        (guards.into_iter().map(Option::unwrap).collect(), locks)
     }
-}
 
-impl <
-    T1: SortableLock, T2: SortableLock, T3: SortableLock,
-    T4: SortableLock, T5: SortableLock, T6: SortableLock,
-    T7: SortableLock> LockGroup for (T1, T2, T3, T4, T5, T6, T7) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard, T6::Guard, T7::Guard);
+    fn release_ordered(locked: Self::Locked, order: &Self::Order) {
+        let mut guards: Vec<_> = locked.into_iter().map(Some).collect();
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-            (5, self.5.sort_key()),
-            (6, self.6.sort_key()),
-        ];
+        for (i, _) in order.iter().rev() {
+            drop(guards[*i].take());
+        }
+    }
+}
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+/// Locks every request in the map, returning the guards under the same keys.
+///
+/// The locks are locked in sorted order regardless of the order of the map.
+/// ```
+/// use std::collections::HashMap;
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let guards = HashMap::from([("a", lock1.lock()), ("b", lock2.lock())]).lock_all();
+/// assert_eq!(3, *guards["a"] + *guards["b"]);
+/// ```
+#[cfg(feature = "std")]
+impl <K: Eq + Hash, L: SortableLock, S: BuildHasher + Default> LockGroup for HashMap<K, L, S> {
+    type Locked = HashMap<K, L::Guard, S>;
 
-        let mut guards = (None, None, None, None, None, None, None);
+    fn lock_all(self) -> Self::Locked {
+        let mut locks: Vec<_> = self.into_iter()
+            .map(|(key, lock)| (lock.sort_key(), key, lock))
+            .collect();
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                5 => guards.5 = Some(self.5.lock_presorted()),
-                6 => guards.6 = Some(self.6.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+        locks.sort_unstable_by_key(|(sort_key, _, _)| *sort_key);
+        check_conflicts(locks.iter().map(|(sort_key, _, lock)| (*sort_key, lock.access())));
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap(),
-            guards.5.unwrap(),
-            guards.6.unwrap(),
-        )
+        locks.into_iter()
+            .map(|(_, key, lock)| (key, lock.lock_presorted()))
+            .collect()
     }
 }
 
-impl <
-    T1: SortableLock, T2: SortableLock, T3: SortableLock,
-    T4: SortableLock, T5: SortableLock, T6: SortableLock,
-    T7: SortableLock, T8: SortableLock> LockGroup for (T1, T2, T3, T4, T5, T6, T7, T8) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard, T6::Guard, T7::Guard, T8::Guard);
+/// Locks every request in the map, returning the guards under the same keys.
+///
+/// The locks are locked in sorted order rather than in the order of the map's keys.
+/// ```
+/// use std::collections::BTreeMap;
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let guards = BTreeMap::from([("a", lock2.lock()), ("b", lock1.lock())]).lock_all();
+/// assert_eq!(vec![2, 1], guards.values().map(|guard| **guard).collect::<Vec<_>>());
+/// ```
+#[cfg(feature = "alloc")]
+impl <K: Ord, L: SortableLock> LockGroup for BTreeMap<K, L> {
+    type Locked = BTreeMap<K, L::Guard>;
 
     fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-            (5, self.5.sort_key()),
-            (6, self.6.sort_key()),
-            (7, self.7.sort_key()),
-        ];
+        let mut locks: Vec<_> = self.into_iter()
+            .map(|(key, lock)| (lock.sort_key(), key, lock))
+            .collect();
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+        locks.sort_unstable_by_key(|(sort_key, _, _)| *sort_key);
+        check_conflicts(locks.iter().map(|(sort_key, _, lock)| (*sort_key, lock.access())));
+
+        locks.into_iter()
+            .map(|(_, key, lock)| (key, lock.lock_presorted()))
+            .collect()
+    }
+}
 
-        let mut guards = (None, None, None, None, None, None, None, None);
+/// Lock all requests produced by an iterator, returning the guards in the order of the iterator.
+///
+/// This is useful when the locks to acquire are only known at runtime. The locks are locked in
+/// sorted order in the same way as `lock_all`.
+/// ```
+/// use sortlock::{SortMutex, sort_and_lock};
+///
+/// let locks = [SortMutex::new(1), SortMutex::new(2), SortMutex::new(3)];
+///
+/// let guards = sort_and_lock(locks.iter().rev().map(SortMutex::lock));
+/// assert_eq!(vec![3, 2, 1], guards.iter().map(|guard| **guard).collect::<Vec<_>>());
+/// ```
+///
+/// - `requests` - The requests to lock.
+#[cfg(feature = "alloc")]
+pub fn sort_and_lock<L: SortableLock, I: IntoIterator<Item = L>>(requests: I) -> Vec<L::Guard> {
+    requests.into_iter()
+        .collect::<Vec<_>>()
+        .lock_all()
+}
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                5 => guards.5 = Some(self.5.lock_presorted()),
-                6 => guards.6 = Some(self.6.lock_presorted()),
-                7 => guards.7 = Some(self.7.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+/// Builds the type of the joined order of a tuple's members.
+macro_rules! chained_order {
+    ($a:ty) => { $a };
+    ($a:ty, $($rest:ty),+) => { ChainedOrder<$a, chained_order!($($rest),+)> };
+}
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap(),
-            guards.5.unwrap(),
-            guards.6.unwrap(),
-            guards.7.unwrap(),
-        )
-    }
+/// Joins the orders of a tuple's members.
+macro_rules! chain_orders {
+    ($a:expr) => { $a };
+    ($a:expr, $($rest:expr),+) => { ChainedOrder::new($a, chain_orders!($($rest),+)) };
 }
 
-impl <
-    T1: SortableLock, T2: SortableLock, T3: SortableLock,
-    T4: SortableLock, T5: SortableLock, T6: SortableLock,
-    T7: SortableLock, T8: SortableLock, T9: SortableLock> LockGroup for (T1, T2, T3, T4, T5, T6, T7, T8, T9) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard, T6::Guard, T7::Guard, T8::Guard, T9::Guard);
+/// Implements `LockGroup`, `OrderedLockGroup` and `NestedLockGroup` for tuples of
+/// `NestedLockGroup`s.
+macro_rules! tuple_lock_group {
+    ($(($($t:ident $i:tt),+);)+) => {
+        $(
+            impl <$($t: NestedLockGroup),+> LockGroup for ($($t,)+) {
+                type Locked = ($($t::Locked,)+);
+
+                fn lock_all(self) -> Self::Locked {
+                    self.lock_ordered().0
+                }
+            }
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-            (5, self.5.sort_key()),
-            (6, self.6.sort_key()),
-            (7, self.7.sort_key()),
-            (8, self.8.sort_key()),
-        ];
+            impl <$($t: NestedLockGroup),+> OrderedLockGroup for ($($t,)+) {
+                type Order = chained_order!($(<$t as OrderedLockGroup>::Order),+);
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+                fn lock_ordered(&self) -> (Self::Locked, Self::Order) {
+                    let order = self.acquisition_order();
 
-        let mut guards = (None, None, None, None, None, None, None, None, None);
+                    check_conflicts(order.as_ref().iter().map(|(i, key)| (*key, self.member_access(*i))));
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                5 => guards.5 = Some(self.5.lock_presorted()),
-                6 => guards.6 = Some(self.6.lock_presorted()),
-                7 => guards.7 = Some(self.7.lock_presorted()),
-                8 => guards.8 = Some(self.8.lock_presorted()),
-                _ => unreachable!(),
+                    let mut partial = Self::unlocked_partial();
+
+                    for (i, _) in order.as_ref() {
+                        self.lock_member(*i, &mut partial);
+                    }
+
+                    (Self::complete_partial(partial), order)
+                }
+
+                fn release_ordered(locked: Self::Locked, order: &Self::Order) {
+                    let mut partial = Self::split_locked(locked);
+
+                    for (i, _) in order.as_ref().iter().rev() {
+                        Self::release_member(*i, &mut partial);
+                    }
+                }
             }
-        }
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap(),
-            guards.5.unwrap(),
-            guards.6.unwrap(),
-            guards.7.unwrap(),
-            guards.8.unwrap(),
-        )
-    }
-}
+            impl <$($t: NestedLockGroup),+> NestedLockGroup for ($($t,)+) {
+                const LEN: usize = 0 $(+ $t::LEN)+;
 
-impl <
-    T1: SortableLock, T2: SortableLock, T3: SortableLock,
-    T4: SortableLock, T5: SortableLock, T6: SortableLock,
-    T7: SortableLock, T8: SortableLock, T9: SortableLock,
-    T10: SortableLock> LockGroup for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10) {
-    type Locked = (T1::Guard, T2::Guard, T3::Guard, T4::Guard, T5::Guard, T6::Guard, T7::Guard, T8::Guard, T9::Guard, T10::Guard);
+                type Partial = ($($t::Partial,)+);
 
-    fn lock_all(self) -> Self::Locked {
-        let mut locks = [
-            (0, self.0.sort_key()),
-            (1, self.1.sort_key()),
-            (2, self.2.sort_key()),
-            (3, self.3.sort_key()),
-            (4, self.4.sort_key()),
-            (5, self.5.sort_key()),
-            (6, self.6.sort_key()),
-            (7, self.7.sort_key()),
-            (8, self.8.sort_key()),
-            (9, self.9.sort_key()),
-        ];
+                fn unsorted_order(&self) -> Self::Order {
+                    let mut order = chain_orders!($(self.$i.unsorted_order()),+);
 
-        locks.sort_unstable_by_key(|(_, key)| *key);
+                    for (i, (index, _)) in order.as_mut().iter_mut().enumerate() {
+                        *index = i;
+                    }
 
-        let mut guards = (None, None, None, None, None, None, None, None, None, None);
+                    order
+                }
 
-        for (i, _) in locks {
-            match i {
-                0 => guards.0 = Some(self.0.lock_presorted()),
-                1 => guards.1 = Some(self.1.lock_presorted()),
-                2 => guards.2 = Some(self.2.lock_presorted()),
-                3 => guards.3 = Some(self.3.lock_presorted()),
-                4 => guards.4 = Some(self.4.lock_presorted()),
-                5 => guards.5 = Some(self.5.lock_presorted()),
-                6 => guards.6 = Some(self.6.lock_presorted()),
-                7 => guards.7 = Some(self.7.lock_presorted()),
-                8 => guards.8 = Some(self.8.lock_presorted()),
-                9 => guards.9 = Some(self.9.lock_presorted()),
-                _ => unreachable!(),
-            }
-        }
+                fn unlocked_partial() -> Self::Partial {
+                    ($($t::unlocked_partial(),)+)
+                }
 
-        (
-            guards.0.unwrap(),
-            guards.1.unwrap(),
-            guards.2.unwrap(),
-            guards.3.unwrap(),
-            guards.4.unwrap(),
-            guards.5.unwrap(),
-            guards.6.unwrap(),
-            guards.7.unwrap(),
-            guards.8.unwrap(),
-            guards.9.unwrap(),
-        )
-    }
+                fn lock_member(&self, index: usize, partial: &mut Self::Partial) {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.lock_member(index, &mut partial.$i);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                fn member_access(&self, index: usize) -> LockAccess {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return self.$i.member_access(index);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+
+                fn complete_partial(partial: Self::Partial) -> Self::Locked {
+                    ($($t::complete_partial(partial.$i),)+)
+                }
+
+                fn split_locked(locked: Self::Locked) -> Self::Partial {
+                    ($($t::split_locked(locked.$i),)+)
+                }
+
+                fn release_member(index: usize, partial: &mut Self::Partial) {
+                    let mut index = index;
+
+                    $(
+                        if index < $t::LEN {
+                            return $t::release_member(index, &mut partial.$i);
+                        }
+
+                        index -= $t::LEN;
+                    )+
+
+                    unreachable!()
+                }
+            }
+        )+
+    };
 }
 
+tuple_lock_group! {
+    (T1 0, T2 1);
+    (T1 0, T2 1, T3 2);
+    (T1 0, T2 1, T3 2, T4 3);
+    (T1 0, T2 1, T3 2, T4 3, T5 4);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13, T15 14);
+    (T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12, T14 13, T15 14, T16 15);
+}
@@ -0,0 +1,49 @@
+/// Requests and locks a group of locks, returning the guards in the order they were listed.
+///
+/// Each lock is preceded by the method used to request it, such as `lock` for a `SortMutex` or
+/// `read` and `write` for a `SortRwLock`. Any number of locks can be listed and they are always
+/// locked in sorted order. The guards are returned as a tuple, even when there is only one lock.
+/// ```
+/// use sortlock::{lock_all, SortMutex, SortRwLock};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortRwLock::new(2);
+/// let lock3 = SortRwLock::new(3);
+///
+/// let (mut guard1, guard2, mut guard3) = lock_all!(lock lock1, read lock2, write lock3);
+/// *guard1 += *guard2;
+/// *guard3 += *guard2;
+/// assert_eq!(8, *guard1 + *guard3);
+/// ```
+#[macro_export]
+macro_rules! lock_all {
+    ($($mode:ident $lock:expr),+ $(,)?) => {
+        $crate::__lock_all_unnest!(
+            $crate::LockGroup::lock_all($crate::__lock_all_nest!($($lock.$mode()),+));
+            [];
+            $($mode)+
+        )
+    };
+}
+
+/// Nests requests into pairs so that any number of requests can be locked as one group.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lock_all_nest {
+    ($last:expr) => { $last };
+    ($first:expr, $($rest:expr),+) => { ($first, $crate::__lock_all_nest!($($rest),+)) };
+}
+
+/// Flattens guards nested by `__lock_all_nest` into a single tuple.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lock_all_unnest {
+    ($value:expr; [$($done:ident)*]; $last:tt) => {{
+        let guard = $value;
+        ($($done,)* guard,)
+    }};
+    ($value:expr; [$($done:ident)*]; $first:tt $($rest:tt)+) => {{
+        let (guard, rest) = $value;
+        $crate::__lock_all_unnest!(rest; [$($done)* guard]; $($rest)+)
+    }};
+}
@@ -0,0 +1,555 @@
+use core::{borrow::Borrow, fmt::{self, Debug, Formatter}, hash::{BuildHasher, Hash}, ops::{Deref, DerefMut}};
+
+use std::{collections::{HashMap, hash_map::RandomState}, sync::Arc};
+
+use crate::{LockGroup, OwnedSortMutexLocked, SortKey, SortMutex, SortRwLock, SortWriteLocked};
+
+/// A concurrent map where every value is protected by its own lock.
+///
+/// Each value is stored in its own `SortMutex` so entries can be locked independently. Several
+/// entries can be locked at once in sorted order with `lock_many`, avoiding deadlocks between
+/// threads locking the same entries in different orders.
+/// ```
+/// use sortlock::SortLockMap;
+///
+/// let accounts = SortLockMap::new();
+/// accounts.insert("alice", 100);
+/// accounts.insert("bob", 50);
+///
+/// let [from, to] = accounts.lock_many(["alice", "bob"]);
+/// let (mut from, mut to) = (from.unwrap(), to.unwrap());
+/// *from -= 25;
+/// *to += 25;
+/// drop((from, to));
+///
+/// assert_eq!(75, *accounts.lock("alice").unwrap());
+/// ```
+///
+/// The structure of the map is protected by a separate lock that is only held while looking up
+/// or changing entries, so locking an entry never blocks changes to other entries. The structure
+/// and an entry can be locked together with `entry`, which allows entries to be inserted or
+/// removed without racing with other threads.
+///
+/// No thread waits for an entry while holding the structural lock. An entry that is held
+/// elsewhere is waited for with the structure unlocked, so a thread holding an entry can still
+/// change the structure of the map.
+pub struct SortLockMap<K, V, S = RandomState> {
+    /// The entries of the map.
+    map: SortRwLock<HashMap<K, Arc<SortMutex<V>>, S>>,
+}
+
+impl <K, V> SortLockMap<K, V> {
+    /// Creates a new empty `SortLockMap`.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl <K, V, S> SortLockMap<K, V, S> {
+    /// Creates a new empty `SortLockMap` using a hasher.
+    ///
+    /// - `hasher` - The hasher used to hash keys.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            map: SortRwLock::new(HashMap::with_hasher(hasher)),
+        }
+    }
+
+    /// Gets the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.read().lock_all().len()
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.read().lock_all().is_empty()
+    }
+
+    /// Gets the sort key of the lock protecting the structure of the map.
+    pub fn sort_key(&self) -> SortKey {
+        self.map.sort_key()
+    }
+}
+
+impl <K: Eq + Hash, V, S: BuildHasher> SortLockMap<K, V, S> {
+    /// Inserts a value, returning the lock of the value previously stored under the key.
+    ///
+    /// The previous lock is removed from the map but any guards for it remain valid.
+    ///
+    /// - `key` - The key to insert the value under.
+    /// - `value` - The value to insert.
+    pub fn insert(&self, key: K, value: V) -> Option<Arc<SortMutex<V>>> {
+        self.map.write().lock_all().insert(key, Arc::new(SortMutex::new(value)))
+    }
+
+    /// Removes an entry, returning its lock.
+    ///
+    /// - `key` - The key of the entry to remove.
+    pub fn remove<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<Arc<SortMutex<V>>> where K: Borrow<Q> {
+        self.map.write().lock_all().remove(key)
+    }
+
+    /// Gets the lock of an entry.
+    ///
+    /// - `key` - The key of the entry.
+    pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<Arc<SortMutex<V>>> where K: Borrow<Q> {
+        self.map.read().lock_all().get(key).cloned()
+    }
+
+    /// Returns `true` if the map contains an entry for a key.
+    ///
+    /// - `key` - The key to check.
+    pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.map.read().lock_all().contains_key(key)
+    }
+
+    /// Locks a single entry.
+    ///
+    /// Returns `None` if the map has no entry for the key.
+    ///
+    /// - `key` - The key of the entry.
+    pub fn lock<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<OwnedSortMutexLocked<V>> where K: Borrow<Q> {
+        self.get(key).map(SortMutex::lock_owned)
+    }
+
+    /// Locks several entries in sorted order.
+    ///
+    /// The guards are returned in the same order as the keys. Each guard is `None` if the map has
+    /// no entry for its key.
+    ///
+    /// - `keys` - The keys of the entries to lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the same key is given more than once.
+    pub fn lock_many<Q: ?Sized + Eq + Hash, const N: usize>(&self, keys: [&Q; N]) -> [Option<OwnedSortMutexLocked<V>>; N] where K: Borrow<Q> {
+        let requests = {
+            let map = self.map.read().lock_all();
+
+            keys.map(|key| map.get(key).cloned().map(SortMutex::lock_owned_request))
+        };
+
+        requests.lock_all()
+    }
+
+    /// Locks the structure of the map to inspect or change the entry for a key.
+    ///
+    /// The structure stays locked until the returned entry is dropped, so no other thread can
+    /// insert or remove entries in the meantime. Other methods of the map block until then, so
+    /// they must not be called by the thread holding the entry. Locking an entry held by another
+    /// thread unlocks the structure while waiting for it.
+    /// ```
+    /// use sortlock::{Entry, SortLockMap};
+    ///
+    /// let map = SortLockMap::new();
+    ///
+    /// *map.entry("a").or_insert(1) += 1;
+    /// *map.entry("a").or_insert(1) += 1;
+    /// assert_eq!(3, *map.lock("a").unwrap());
+    ///
+    /// if let Entry::Occupied(entry) = map.entry("a") {
+    ///     let guard = entry.lock().unwrap();
+    ///     assert_eq!(3, *guard);
+    ///
+    ///     // The entry is removed while it is still locked.
+    ///     let value = guard.remove();
+    ///     assert_eq!(3, *value);
+    /// }
+    /// assert!(map.is_empty());
+    /// ```
+    ///
+    /// - `key` - The key of the entry.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        self.entry_in(self.map.write().lock_all(), key)
+    }
+
+    /// Gets the entry for a key from the locked structure of the map.
+    ///
+    /// - `map` - The locked structure of the map.
+    /// - `key` - The key of the entry.
+    fn entry_in<'l>(&'l self, map: SortWriteLocked<'l, HashMap<K, Arc<SortMutex<V>>, S>>, key: K) -> Entry<'l, K, V, S> {
+        if map.contains_key(&key) {
+            Entry::Occupied(OccupiedEntry {
+                owner: self,
+                map,
+                key,
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                map,
+                key,
+            })
+        }
+    }
+
+    /// Locks an entry together with the structure of the map.
+    ///
+    /// Returns `None` if the map has no entry for the key.
+    ///
+    /// - `key` - The key of the entry.
+    pub fn lock_entry(&self, key: K) -> Option<LockedEntry<'_, K, V, S>> {
+        match self.entry(key) {
+            Entry::Occupied(entry) => entry.lock().ok(),
+            Entry::Vacant(_) => None,
+        }
+    }
+}
+
+/// The entry for a key in a `SortLockMap`.
+///
+/// The structure of the map is locked until the entry is dropped.
+pub enum Entry<'l, K, V, S = RandomState> {
+    /// An entry that has a value.
+    Occupied(OccupiedEntry<'l, K, V, S>),
+    /// An entry without a value.
+    Vacant(VacantEntry<'l, K, V, S>),
+}
+
+impl <'l, K: Eq + Hash, V, S: BuildHasher> Entry<'l, K, V, S> {
+    /// Locks the entry, inserting a value first if it is vacant.
+    ///
+    /// - `value` - The value to insert if the entry is vacant.
+    pub fn or_insert(self, value: V) -> LockedEntry<'l, K, V, S> where K: Clone {
+        self.or_insert_with(|| value)
+    }
+
+    /// Locks the entry, inserting the result of a closure first if it is vacant.
+    ///
+    /// - `f` - The closure that creates the value to insert.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> LockedEntry<'l, K, V, S> where K: Clone {
+        let entry = match self {
+            Entry::Occupied(entry) => entry.lock(),
+            Entry::Vacant(entry) => Err(entry),
+        };
+
+        entry.unwrap_or_else(|entry| entry.insert(f()))
+    }
+
+    /// Gets the key of the entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+/// An entry in a `SortLockMap` that has a value.
+pub struct OccupiedEntry<'l, K, V, S = RandomState> {
+    /// The map the entry is in.
+    owner: &'l SortLockMap<K, V, S>,
+    /// The locked structure of the map.
+    map: SortWriteLocked<'l, HashMap<K, Arc<SortMutex<V>>, S>>,
+    /// The key of the entry.
+    key: K,
+}
+
+impl <'l, K: Eq + Hash, V, S: BuildHasher> OccupiedEntry<'l, K, V, S> {
+    /// Gets the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Gets the lock of the entry.
+    pub fn get(&self) -> &Arc<SortMutex<V>> {
+        &self.map[&self.key]
+    }
+
+    /// Locks the entry, returning it with the structure of the map locked.
+    ///
+    /// If the entry is held by another thread the structure is unlocked while waiting for it, as
+    /// that thread may be waiting for the structure. The structure is then locked again, so the
+    /// entry may have been removed in the meantime, in which case the vacant entry is returned.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock of the entry is poisoned.
+    pub fn lock(self) -> Result<LockedEntry<'l, K, V, S>, VacantEntry<'l, K, V, S>> {
+        let OccupiedEntry { owner, mut map, key } = self;
+
+        loop {
+            let lock = match map.get(&key) {
+                Some(lock) => lock.clone(),
+                None => return Err(VacantEntry {
+                    map,
+                    key,
+                }),
+            };
+
+            if let Some(guard) = lock.clone().try_lock_owned() {
+                return Ok(LockedEntry {
+                    map,
+                    key,
+                    guard,
+                });
+            }
+
+            drop(map);
+            let guard = lock.clone().lock_owned();
+            map = owner.map.write().lock_all();
+
+            // The entry may have been replaced while the structure was unlocked.
+            if map.get(&key).is_some_and(|current| Arc::ptr_eq(current, &lock)) {
+                return Ok(LockedEntry {
+                    map,
+                    key,
+                    guard,
+                });
+            }
+        }
+    }
+
+    /// Removes the entry, returning its lock.
+    pub fn remove(mut self) -> Arc<SortMutex<V>> {
+        self.map.remove(&self.key)
+            .expect("Occupied entries always have a value.")
+    }
+}
+
+/// An entry in a `SortLockMap` without a value.
+pub struct VacantEntry<'l, K, V, S = RandomState> {
+    /// The locked structure of the map.
+    map: SortWriteLocked<'l, HashMap<K, Arc<SortMutex<V>>, S>>,
+    /// The key of the entry.
+    key: K,
+}
+
+impl <'l, K: Eq + Hash, V, S: BuildHasher> VacantEntry<'l, K, V, S> {
+    /// Gets the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Inserts a value and locks it while keeping the structure of the map locked.
+    ///
+    /// - `value` - The value to insert.
+    pub fn insert(self, value: V) -> LockedEntry<'l, K, V, S> where K: Clone {
+        let lock = Arc::new(SortMutex::new(value));
+        let guard = lock.clone().try_lock_owned()
+            .expect("New locks are never held elsewhere.");
+
+        let mut map = self.map;
+        map.insert(self.key.clone(), lock);
+
+        LockedEntry {
+            map,
+            key: self.key,
+            guard,
+        }
+    }
+}
+
+impl <K: Debug, V, S> Debug for VacantEntry<'_, K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A locked entry in a `SortLockMap` along with the locked structure of the map.
+///
+/// This dereferences to the value of the entry.
+pub struct LockedEntry<'l, K, V, S = RandomState> {
+    /// The locked structure of the map.
+    map: SortWriteLocked<'l, HashMap<K, Arc<SortMutex<V>>, S>>,
+    /// The key of the entry.
+    key: K,
+    /// The guard for the entry.
+    guard: OwnedSortMutexLocked<V>,
+}
+
+impl <K: Eq + Hash, V, S: BuildHasher> LockedEntry<'_, K, V, S> {
+    /// Gets the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Removes the entry from the map while it is still locked.
+    ///
+    /// The structure of the map is unlocked and the guard for the removed entry is returned.
+    pub fn remove(mut self) -> OwnedSortMutexLocked<V> {
+        self.map.remove(&self.key);
+
+        self.guard
+    }
+
+    /// Unlocks the structure of the map while keeping the entry locked.
+    pub fn unlock_map(self) -> OwnedSortMutexLocked<V> {
+        self.guard
+    }
+}
+
+impl <K, V, S> Deref for LockedEntry<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.guard
+    }
+}
+
+impl <K, V, S> DerefMut for LockedEntry<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.guard
+    }
+}
+
+impl <K, V, S: Default> Default for SortLockMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl <K: Debug, V: Debug, S> Debug for SortLockMap<K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.map.read().lock_all().iter())
+            .finish()
+    }
+}
+
+impl <K: Eq + Hash, V> FromIterator<(K, V)> for SortLockMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = iter.into_iter()
+            .map(|(key, value)| (key, Arc::new(SortMutex::new(value))))
+            .collect();
+
+        Self {
+            map: SortRwLock::new(map),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use crate::{Entry, SortLockMap};
+
+    #[test]
+    fn test_insert_remove() {
+        let map = SortLockMap::new();
+        assert!(map.insert("a", 1).is_none());
+        assert!(map.insert("a", 2).is_some());
+        assert_eq!(1, map.len());
+
+        let guard = map.lock("a").unwrap();
+        assert!(map.remove("a").is_some());
+        assert_eq!(2, *guard);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_lock_many() {
+        let map: SortLockMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+
+        let [b, c, a] = map.lock_many(["b", "c", "a"]);
+        assert_eq!(2, *b.unwrap());
+        assert!(c.is_none());
+        assert_eq!(1, *a.unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lock_many_duplicate() {
+        let map: SortLockMap<_, _> = [("a", 1)].into_iter().collect();
+
+        let _ = map.lock_many(["a", "a"]);
+    }
+
+    #[test]
+    fn test_entry() {
+        let map = SortLockMap::new();
+
+        let entry = map.entry(1).or_insert(2);
+        assert_eq!(1, *entry.key());
+        let guard = entry.unlock_map();
+        assert!(map.get(&1).unwrap().try_lock().is_none());
+        drop(guard);
+
+        let guard = map.lock_entry(1).unwrap().remove();
+        assert_eq!(2, *guard);
+        assert!(map.lock_entry(1).is_none());
+    }
+
+    #[test]
+    fn test_entry_threads() {
+        let map = Arc::new(SortLockMap::new());
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let map = map.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    *map.entry("count").or_insert(0) += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *map.lock("count").unwrap());
+    }
+
+    #[test]
+    fn test_entry_held_elsewhere() {
+        let map = Arc::new(SortLockMap::new());
+        map.insert("a", 1);
+
+        let guard = map.lock("a").unwrap();
+
+        let waiter = map.clone();
+        let handle = thread::spawn(move || *waiter.entry("a").or_insert(0) += 1);
+
+        // The structure must not stay locked while the other thread waits for the entry.
+        thread::sleep(Duration::from_millis(50));
+        map.insert("b", 2);
+        drop(guard);
+
+        handle.join().unwrap();
+        assert_eq!(2, *map.lock("a").unwrap());
+
+        // An entry removed while it is waited for is vacant once it is acquired.
+        let guard = map.lock("a").unwrap();
+
+        let waiter = map.clone();
+        let handle = thread::spawn(move || match waiter.entry("a") {
+            Entry::Occupied(entry) => entry.lock().is_err(),
+            Entry::Vacant(_) => true,
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        map.remove("a");
+        drop(guard);
+
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn test_transfers() {
+        let map: Arc<SortLockMap<_, _>> = Arc::new((0..4).map(|i| (i, 100)).collect());
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let map = map.clone();
+
+            thread::spawn(move || {
+                for j in 0..100 {
+                    let to = (i + j % 3 + 1) % 4;
+                    let [mut from, mut to] = map.lock_many([&i, &to]).map(Option::unwrap);
+
+                    *from -= 1;
+                    *to += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total: i32 = map.lock_many([&0, &1, &2, &3]).into_iter()
+            .map(|guard| *guard.unwrap())
+            .sum();
+        assert_eq!(400, total);
+    }
+}
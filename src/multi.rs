@@ -0,0 +1,212 @@
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{LockGroup, SortMutex, SortMutexLocked};
+
+/// A container owning several values that are each protected by their own lock.
+///
+/// This packages a set of related resources that are locked together in different combinations.
+/// Each value is stored in its own `SortMutex` and methods such as `lock_ab` lock a combination of
+/// the values in sorted order. Values are named `a`, `b`, `c` and `d` in the order they are given.
+/// ```
+/// use sortlock::MultiLock;
+///
+/// let accounts = MultiLock::new((100, 50, 0));
+///
+/// let (mut from, mut to) = accounts.lock_ac();
+/// *from -= 25;
+/// *to += 25;
+/// drop((from, to));
+///
+/// let (a, b, c) = accounts.lock();
+/// assert_eq!((75, 50, 25), (*a, *b, *c));
+/// ```
+///
+/// Other combinations can be locked with `lock_with`, which can also combine the values with
+/// other locks.
+pub struct MultiLock<T: MultiLockValues> {
+    /// The locks protecting each value.
+    locks: T::Locks,
+}
+
+impl <T: MultiLockValues> MultiLock<T> {
+    /// Creates a new `MultiLock`.
+    ///
+    /// - `values` - The values to protect.
+    pub fn new(values: T) -> Self {
+        Self {
+            locks: values.into_locks(),
+        }
+    }
+
+    /// Locks every value.
+    pub fn lock(&self) -> T::Locked<'_> {
+        T::lock_locks(&self.locks)
+    }
+
+    /// Gets the locks protecting each value.
+    pub fn locks(&self) -> &T::Locks {
+        &self.locks
+    }
+
+    /// Locks a group of requests built from the locks protecting each value.
+    /// ```
+    /// use sortlock::{MultiLock, SortMutex};
+    ///
+    /// let values = MultiLock::new((1, 2));
+    /// let other = SortMutex::new(3);
+    ///
+    /// let (a, other) = values.lock_with(|(a, _)| (a.lock(), other.lock()));
+    /// assert_eq!(4, *a + *other);
+    /// ```
+    ///
+    /// - `f` - The closure that builds the group to lock.
+    pub fn lock_with<'l, G: LockGroup, F: FnOnce(&'l T::Locks) -> G>(&'l self, f: F) -> G::Locked {
+        f(&self.locks).lock_all()
+    }
+}
+
+impl <T: MultiLockValues> Debug for MultiLock<T> where T::Locks: Debug {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MultiLock")
+            .field(&self.locks)
+            .finish()
+    }
+}
+
+mod private {
+    /// Prevents `MultiLockValues` from being implemented outside this crate.
+    pub trait Sealed {}
+}
+
+/// A tuple of values that can be stored in a `MultiLock`.
+///
+/// This is implemented for tuples of 2 to 4 values.
+pub trait MultiLockValues: private::Sealed {
+    /// The tuple of locks protecting each value.
+    type Locks;
+    /// The tuple of guards for every value once locked.
+    type Locked<'l> where Self: 'l;
+
+    /// Places each value behind its own lock.
+    fn into_locks(self) -> Self::Locks;
+
+    /// Locks every lock in sorted order.
+    ///
+    /// - `locks` - The locks to lock.
+    fn lock_locks(locks: &Self::Locks) -> Self::Locked<'_>;
+}
+
+/// Implements `MultiLockValues` and the locking methods of `MultiLock` for a tuple.
+macro_rules! multi_lock {
+    (
+        ($($T:ident $index:tt),+);
+        singles: [$($single:ident => $S:ident $single_index:tt),+];
+        groups: [$($group:ident => ($($G:ident $group_index:tt),+)),+]
+    ) => {
+        impl <$($T),+> private::Sealed for ($($T,)+) {}
+
+        impl <$($T),+> MultiLockValues for ($($T,)+) {
+            type Locks = ($(SortMutex<$T>,)+);
+            type Locked<'l> = ($(SortMutexLocked<'l, $T>,)+) where Self: 'l;
+
+            fn into_locks(self) -> Self::Locks {
+                ($(SortMutex::new(self.$index),)+)
+            }
+
+            fn lock_locks(locks: &Self::Locks) -> Self::Locked<'_> {
+                ($(locks.$index.lock(),)+).lock_all()
+            }
+        }
+
+        impl <$($T),+> MultiLock<($($T,)+)> {
+            $(
+                #[doc = concat!("Locks the value at index ", stringify!($single_index), ".")]
+                pub fn $single(&self) -> SortMutexLocked<'_, $S> {
+                    self.locks.$single_index.lock().lock_all()
+                }
+            )+
+
+            $(
+                #[doc = concat!("Locks the values at indices ", stringify!($($group_index),+), " in sorted order.")]
+                pub fn $group(&self) -> ($(SortMutexLocked<'_, $G>,)+) {
+                    ($(self.locks.$group_index.lock(),)+).lock_all()
+                }
+            )+
+        }
+    };
+}
+
+multi_lock!(
+    (A 0, B 1);
+    singles: [lock_a => A 0, lock_b => B 1];
+    groups: [lock_ab => (A 0, B 1)]
+);
+multi_lock!(
+    (A 0, B 1, C 2);
+    singles: [lock_a => A 0, lock_b => B 1, lock_c => C 2];
+    groups: [
+        lock_ab => (A 0, B 1), lock_ac => (A 0, C 2), lock_bc => (B 1, C 2),
+        lock_abc => (A 0, B 1, C 2)
+    ]
+);
+multi_lock!(
+    (A 0, B 1, C 2, D 3);
+    singles: [lock_a => A 0, lock_b => B 1, lock_c => C 2, lock_d => D 3];
+    groups: [
+        lock_ab => (A 0, B 1), lock_ac => (A 0, C 2), lock_ad => (A 0, D 3),
+        lock_bc => (B 1, C 2), lock_bd => (B 1, D 3), lock_cd => (C 2, D 3),
+        lock_abc => (A 0, B 1, C 2), lock_abd => (A 0, B 1, D 3),
+        lock_acd => (A 0, C 2, D 3), lock_bcd => (B 1, C 2, D 3),
+        lock_abcd => (A 0, B 1, C 2, D 3)
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::MultiLock;
+
+    #[test]
+    fn test_multi_lock() {
+        let values = MultiLock::new((1, 2, 3, 4));
+
+        *values.lock_a() += 1;
+        let (b, d) = values.lock_bd();
+        assert_eq!(6, *b + *d);
+        drop((b, d));
+
+        let (a, b, c, d) = values.lock_abcd();
+        assert_eq!((2, 2, 3, 4), (*a, *b, *c, *d));
+    }
+
+    #[test]
+    fn test_multi_lock_threads() {
+        let accounts = Arc::new(MultiLock::new((100, 100)));
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let accounts = accounts.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let (mut a, mut b) = accounts.lock_ab();
+
+                    if i % 2 == 0 {
+                        *a -= 1;
+                        *b += 1;
+                    } else {
+                        *a += 1;
+                        *b -= 1;
+                    }
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let (a, b) = accounts.lock();
+        assert_eq!(200, *a + *b);
+    }
+}
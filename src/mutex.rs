@@ -1,12 +1,24 @@
-use core::fmt::{self, Debug, Display, Formatter};
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, marker::PhantomData, mem, ops::{Deref, DerefMut}, ptr::{self, NonNull}};
 
 #[cfg(feature = "std")]
-use std::sync::{Mutex, MutexGuard};
+use std::sync::Mutex;
 
-#[cfg(not(feature = "std"))]
-use spin::{Mutex, MutexGuard};
+#[cfg(any(feature = "parking_lot", all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+use std::time::{Duration, Instant};
 
-use crate::{LockGroup, SortKey, SortableLock};
+#[cfg(any(feature = "alloc", feature = "diagnostics"))]
+use core::panic::Location;
+
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+use crate::{DefaultBackend, LockGroup, MutexBackend, SortKey, SortableLock, finally, key::LazySortKey};
+#[cfg(feature = "std")]
+use crate::PoisonBackend;
+#[cfg(feature = "parking_lot")]
+use crate::ParkingLotBackend;
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+use crate::FutexBackend;
 
 /// A sortable lock that ensures exclusive access to a resource. 
 /// This is a sortable version of rust's `Mutex` type.
@@ -43,22 +55,288 @@ use crate::{LockGroup, SortKey, SortableLock};
 /// println!("{}", *guard1);
 /// println!("{}", *guard2);
 /// ```
-pub struct SortMutex<T> {
+///
+/// Unsized values such as slices and trait objects can be stored behind a pointer:
+/// ```
+/// use std::sync::Arc;
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock: Arc<SortMutex<[u32]>> = Arc::new(SortMutex::new([1, 2, 3]));
+///
+/// lock.lock().lock_all()[0] = 4;
+/// ```
+///
+/// The internal lock is provided by a `MutexBackend`, which can be chosen for each lock with
+/// `with_backend`.
+pub struct SortMutex<T: ?Sized, B = DefaultBackend> {
     /// The internal mutex.
-    mutex: Mutex<T>,
+    mutex: B,
     /// The sort key for this lock.
-    key: SortKey,
+    key: LazySortKey,
+    /// The name of this lock, if it has one.
+    name: Option<&'static str>,
+    /// Where this lock was created.
+    #[cfg(feature = "diagnostics")]
+    created: &'static Location<'static>,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
 }
 
+unsafe impl <T: ?Sized + Send, B: Send> Send for SortMutex<T, B> {}
+unsafe impl <T: ?Sized + Send, B: Sync> Sync for SortMutex<T, B> {}
+
 impl <T> SortMutex<T> {
     /// Creates a new `SortLock`.
     ///
     /// - `value` - The value of the lock.
+    #[track_caller]
     pub fn new(value: T) -> Self {
+        Self::with_backend(value, DefaultBackend::new())
+    }
+
+    /// Creates a new `SortMutex` with a name.
+    ///
+    /// The name identifies the lock in its `Debug` output and in the panic when it is poisoned,
+    /// where its key alone would not say which lock it is.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = SortMutex::new_named("connection_pool", 4);
+    ///
+    /// assert_eq!(Some("connection_pool"), lock.name());
+    /// assert_eq!("SortMutex { name: \"connection_pool\", data: 4 }", format!("{lock:?}"));
+    /// ```
+    ///
+    /// - `name` - The name of the lock.
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new_named(name: &'static str, value: T) -> Self {
+        Self {
+            name: Some(name),
+            ..Self::new(value)
+        }
+    }
+
+    /// Creates a new `SortMutex` with a given sort key.
+    ///
+    /// This allows the order of the lock to be controlled with a key such as one from
+    /// `SortKey::from_value`. The key must not be used by any other lock.
+    ///
+    /// - `value` - The value of the lock.
+    /// - `key` - The sort key of the lock.
+    #[track_caller]
+    pub fn with_key(value: T, key: SortKey) -> Self {
+        Self::const_with_key(value, key)
+    }
+
+    /// Creates a new `SortMutex` with a given sort key in a `const` context.
+    /// ```
+    /// use sortlock::{SortKey, SortMutex, LockGroup};
+    ///
+    /// static USERS: SortMutex<u32> = SortMutex::const_with_key(0, SortKey::from_name("db.users"));
+    ///
+    /// assert_eq!(SortKey::from_name("db.users"), USERS.sort_key());
+    /// ```
+    ///
+    /// - `value` - The value of the lock.
+    /// - `key` - The sort key of the lock.
+    #[track_caller]
+    pub const fn const_with_key(value: T, key: SortKey) -> Self {
+        Self {
+            mutex: DefaultBackend::new(),
+            key: LazySortKey::new(key),
+            name: None,
+            #[cfg(feature = "diagnostics")]
+            created: Location::caller(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortMutex` in an `Arc` with a sort key derived from its address.
+    ///
+    /// As the lock never moves while it is in the `Arc` its address identifies it, so no key has
+    /// to be taken from the global counter. This avoids contention on the counter when many locks
+    /// are created at once, and the key is reused once the allocation is freed.
+    ///
+    /// - `value` - The value of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is allocated at an address of `2^57` or above.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn new_arc(value: T) -> Arc<Self> {
+        let location = Location::caller();
+
+        Arc::new_cyclic(|lock| {
+            #[allow(unused_mut)]
+            let mut mutex = Self::with_key(value, SortKey::from_address(lock.as_ptr() as usize, location));
+
+            #[cfg(feature = "diagnostics")]
+            {
+                mutex.created = location;
+            }
+
+            mutex
+        })
+    }
+
+    /// Creates a new `SortMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used rather than when it is created. This
+    /// allows the lock to be placed in a `static`.
+    /// ```
+    /// use sortlock::{SortMutex, LockGroup};
+    ///
+    /// static LOCK: SortMutex<u32> = SortMutex::const_new(0);
+    ///
+    /// *LOCK.lock().lock_all() += 1;
+    /// ```
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub const fn const_new(value: T) -> Self {
+        Self::const_with_backend(value, DefaultBackend::new())
+    }
+}
+
+impl <T, B: MutexBackend> SortMutex<T, B> {
+    /// Creates a new `SortMutex` using a backend for the internal lock.
+    /// ```
+    /// use sortlock::{SortMutex, SpinBackend, LockGroup};
+    ///
+    /// let lock = SortMutex::with_backend(1, SpinBackend::new());
+    ///
+    /// assert_eq!(1, *lock.lock().lock_all());
+    /// ```
+    ///
+    /// - `value` - The value of the lock.
+    /// - `backend` - The unlocked backend to use.
+    #[track_caller]
+    pub fn with_backend(value: T, backend: B) -> Self {
+        Self {
+            mutex: backend,
+            key: LazySortKey::new(SortKey::new()),
+            name: None,
+            #[cfg(feature = "diagnostics")]
+            created: Location::caller(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortMutex` using a backend for the internal lock in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used.
+    ///
+    /// - `value` - The value of the lock.
+    /// - `backend` - The unlocked backend to use.
+    #[track_caller]
+    pub const fn const_with_backend(value: T, backend: B) -> Self {
         Self {
-            mutex: Mutex::new(value),
-            key: SortKey::new()
+            mutex: backend,
+            key: LazySortKey::unassigned(),
+            name: None,
+            #[cfg(feature = "diagnostics")]
+            created: Location::caller(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub(crate) fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Sets the value of this lock.
+    ///
+    /// The lock is only held while the value is replaced. The old value is dropped after the lock
+    /// is released.
+    ///
+    /// - `value` - The new value.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Replaces the value of this lock, returning the old value.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = SortMutex::new(1);
+    ///
+    /// assert_eq!(1, lock.replace(2));
+    /// assert_eq!(2, lock.replace(3));
+    /// ```
+    ///
+    /// - `value` - The new value.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn replace(&self, value: T) -> T {
+        mem::replace(&mut *self.lock().lock_all(), value)
+    }
+
+    /// Takes the value of this lock, leaving the default value in its place.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn take(&self) -> T where T: Default {
+        self.replace(T::default())
+    }
+
+    /// Gets a clone of the value of this lock.
+    ///
+    /// The lock is only held while the value is cloned.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = SortMutex::new(vec![1, 2]);
+    ///
+    /// assert_eq!(vec![1, 2], lock.get_cloned());
+    /// ```
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn get_cloned(&self) -> T where T: Clone {
+        self.lock().lock_all().clone()
+    }
+
+    /// Swaps the values of this lock and another lock.
+    ///
+    /// Both locks are locked in sorted order so this cannot deadlock with another sorted lock of
+    /// the same locks. Swapping a lock with itself does nothing.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock1 = SortMutex::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// lock1.swap(&lock2);
+    /// assert_eq!(2, lock1.take());
+    /// assert_eq!(1, lock2.take());
+    /// ```
+    ///
+    /// - `other` - The lock to swap values with.
+    ///
+    /// # Panicking
+    /// This method will panic if either lock is poisoned.
+    pub fn swap(&self, other: &Self) {
+        if ptr::eq(self, other) {
+            return;
         }
+
+        let (mut guard1, mut guard2) = (self.lock(), other.lock()).lock_all();
+        mem::swap(&mut *guard1, &mut *guard2);
+    }
+}
+
+impl <T: ?Sized, B: MutexBackend> SortMutex<T, B> {
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
     }
 
     /// Requests to lock this lock.
@@ -66,61 +344,759 @@ impl <T> SortMutex<T> {
     ///
     /// # Panicking
     /// The guard will panic when locked if this lock becomes poisoned.
-    pub fn lock(&self) -> SortMutexGuard<T> {
+    pub fn lock(&self) -> SortMutexGuard<'_, T, B> {
         SortMutexGuard {
             lock: self
         }
     }
+
+    /// Attempts to lock this lock without blocking.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently held elsewhere.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn try_lock(&self) -> Option<SortMutexLocked<'_, T, B>> {
+        self.check_poison();
+
+        self.mutex.try_lock()
+            .map(|guard| SortMutexLocked::new(guard, self))
+    }
+
+    /// Locks this lock, runs a closure with the value and then unlocks it.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// The lock is always released before this method returns.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = SortMutex::new(vec![1, 2]);
+    ///
+    /// let len = lock.update(|value| {
+    ///     value.push(3);
+    ///     value.len()
+    /// });
+    /// assert_eq!(3, len);
+    /// ```
+    ///
+    /// - `f` - The closure to run with the value.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut *self.lock().lock_all())
+    }
+
+    /// Gets a raw pointer to the value protected by this lock.
+    ///
+    /// The lock is not acquired so the pointer must only be dereferenced while the lock is held,
+    /// or while no other references to the value can exist.
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is the key used to order this lock when it is locked as part of a group. A lock
+    /// created with `const_new` is assigned its key on the first call to this method.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Gets where this lock was created.
+    ///
+    /// This is the code that called the constructor, which identifies the lock when only its key
+    /// or address is known.
+    /// ```
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = SortMutex::new(1);
+    ///
+    /// assert_eq!(line!() - 2, lock.created_at().line());
+    /// ```
+    #[cfg(feature = "diagnostics")]
+    pub fn created_at(&self) -> &'static Location<'static> {
+        self.created
+    }
+
+    /// Acquires the internal lock.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    fn lock_backend(&self) -> B::Guard<'_> {
+        self.check_poison();
+
+        self.mutex.lock()
+    }
+
+    /// Gets the name of this lock, if it was created with one.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Panics with the name of this lock and where it was created if it is poisoned.
+    ///
+    /// If neither is known this does nothing and the backend reports the poisoning.
+    fn check_poison(&self) {
+        let identified = self.name.is_some() || cfg!(feature = "diagnostics");
+
+        if identified && self.mutex.is_poisoned() {
+            panic!("Failed to lock mutex: the mutex {} is poisoned.", Identity(self));
+        }
+    }
+
+    /// Locks this lock through an `Arc`, returning a guard that keeps the lock alive.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// The returned guard is not bound to a borrow of the lock so it can be stored in other
+    /// structures.
+    /// ```
+    /// use std::sync::Arc;
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = Arc::new(SortMutex::new(1));
+    ///
+    /// let mut guard = lock.clone().lock_owned();
+    /// *guard += 1;
+    /// assert_eq!(2, *guard);
+    /// ```
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn lock_owned(self: Arc<Self>) -> OwnedSortMutexLocked<T, B> where B: 'static {
+        let guard = self.lock_backend();
+
+        Self::owned(guard, &self)
+    }
+
+    /// Attempts to lock this lock through an `Arc` without blocking.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently held elsewhere.
+    /// ```
+    /// use std::sync::Arc;
+    /// use sortlock::SortMutex;
+    ///
+    /// let lock = Arc::new(SortMutex::new(1));
+    ///
+    /// let guard = lock.clone().try_lock_owned().unwrap();
+    /// assert!(lock.clone().try_lock_owned().is_none());
+    /// ```
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn try_lock_owned(self: Arc<Self>) -> Option<OwnedSortMutexLocked<T, B>> where B: 'static {
+        self.check_poison();
+
+        let guard = self.mutex.try_lock()?;
+
+        Some(Self::owned(guard, &self))
+    }
+
+    /// Creates an owned guard from a guard for the internal mutex.
+    ///
+    /// - `guard` - The guard for the internal mutex.
+    /// - `this` - The lock the guard was acquired from.
+    #[cfg(feature = "alloc")]
+    fn owned(guard: B::Guard<'_>, this: &Arc<Self>) -> OwnedSortMutexLocked<T, B> where B: 'static {
+        let guard = mem::ManuallyDrop::new(guard);
+        // SAFETY: The returned guard keeps the lock alive until after the internal guard is
+        // dropped, and the original guard is never dropped.
+        let guard = unsafe { mem::transmute_copy::<B::Guard<'_>, B::Guard<'static>>(&guard) };
+
+        OwnedSortMutexLocked {
+            _guard: guard,
+            lock: this.clone(),
+        }
+    }
+
+    /// Requests to lock this lock through an `Arc`.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Once locked this produces an `OwnedSortMutexLocked` so the locked group is not bound to a
+    /// borrow of any of its locks.
+    /// ```
+    /// use std::sync::Arc;
+    /// use sortlock::{SortMutex, LockGroup};
+    ///
+    /// let lock1 = Arc::new(SortMutex::new(1));
+    /// let lock2 = Arc::new(SortMutex::new(2));
+    ///
+    /// let (guard1, guard2) = (lock1.lock_owned_request(), lock2.lock_owned_request()).lock_all();
+    ///
+    /// // The guards keep the locks alive.
+    /// assert_eq!(3, *guard1 + *guard2);
+    /// ```
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn lock_owned_request(self: Arc<Self>) -> OwnedSortMutexGuard<T, B> where B: 'static {
+        OwnedSortMutexGuard {
+            lock: self
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl <T: ?Sized, B: PoisonBackend> SortMutex<T, B> {
+    /// Checks whether this lock is poisoned.
+    ///
+    /// A lock is poisoned when a thread panics while holding it. Locking a poisoned lock panics.
+    /// Only locks using a `PoisonBackend`, such as `StdBackend` and `FutexBackend`, can be
+    /// poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.mutex.is_poisoned()
+    }
+
+    /// Clears the poisoned state of this lock.
+    ///
+    /// This should only be called once the value has been checked or restored to a valid state.
+    /// ```
+    /// use std::{panic, sync::Arc, thread};
+    /// use sortlock::{SortMutex, LockGroup};
+    ///
+    /// let lock = Arc::new(SortMutex::new(1));
+    ///
+    /// let poisoner = lock.clone();
+    /// let _ = thread::spawn(move || {
+    ///     let _guard = poisoner.lock().lock_all();
+    ///     panic!("poison the lock");
+    /// }).join();
+    ///
+    /// assert!(lock.is_poisoned());
+    /// lock.clear_poison();
+    /// assert_eq!(1, *lock.lock().lock_all());
+    /// ```
+    pub fn clear_poison(&self) {
+        self.mutex.clear_poison();
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl <T: ?Sized> SortMutex<T, ParkingLotBackend> {
+    /// Attempts to lock this lock, giving up after a timeout.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock could not be acquired in time.
+    /// ```
+    /// use std::time::Duration;
+    /// use sortlock::{ParkingLotBackend, SortMutex};
+    ///
+    /// let lock = SortMutex::with_backend(1, ParkingLotBackend::new());
+    ///
+    /// let guard = lock.try_lock_for(Duration::from_millis(10)).unwrap();
+    /// assert!(lock.try_lock_for(Duration::from_millis(10)).is_none());
+    /// ```
+    ///
+    /// - `timeout` - The longest time to wait for.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<SortMutexLocked<'_, T, ParkingLotBackend>> {
+        self.mutex.try_lock_for(timeout)
+            .map(|guard| SortMutexLocked::new(guard, self))
+    }
+
+    /// Attempts to lock this lock, giving up at a deadline.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock could not be acquired in time.
+    ///
+    /// - `deadline` - The time to stop waiting at.
+    pub fn try_lock_until(&self, deadline: Instant) -> Option<SortMutexLocked<'_, T, ParkingLotBackend>> {
+        self.mutex.try_lock_until(deadline)
+            .map(|guard| SortMutexLocked::new(guard, self))
+    }
+}
+
+#[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl <T: ?Sized> SortMutex<T, FutexBackend> {
+    /// Attempts to lock this lock, giving up after a timeout.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock could not be acquired in time.
+    ///
+    /// - `timeout` - The longest time to wait for.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<SortMutexLocked<'_, T, FutexBackend>> {
+        self.mutex.try_lock_for(timeout)
+            .map(|guard| SortMutexLocked::new(guard, self))
+    }
+
+    /// Attempts to lock this lock, giving up at a deadline.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock could not be acquired in time.
+    ///
+    /// - `deadline` - The time to stop waiting at.
+    pub fn try_lock_until(&self, deadline: Instant) -> Option<SortMutexLocked<'_, T, FutexBackend>> {
+        self.mutex.try_lock_until(deadline)
+            .map(|guard| SortMutexLocked::new(guard, self))
+    }
+}
+
+/// Describes which lock a `SortMutex` is by its name and where it was created.
+struct Identity<'l, T: ?Sized, B>(&'l SortMutex<T, B>);
+
+impl <T: ?Sized, B> Display for Identity<'_, T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(name) = self.0.name {
+            write!(f, "{name:?}")?;
+        }
+
+        #[cfg(feature = "diagnostics")]
+        {
+            let separator = if self.0.name.is_some() { " " } else { "" };
+
+            write!(f, "{separator}created at {}", self.0.created)?;
+        }
+
+        Ok(())
+    }
 }
 
-impl <T: Debug> Debug for SortMutex<T> {
+/// A named lock is formatted with its name. Otherwise only the value is formatted.
+impl <T: ?Sized + Debug, B: MutexBackend> Debug for SortMutex<T, B> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        self.lock().lock_all().fmt(f)
+        match self.name {
+            Some(name) => f.debug_struct("SortMutex")
+                .field("name", &name)
+                .field("data", &&*self.lock().lock_all())
+                .finish(),
+            None => self.lock().lock_all().fmt(f),
+        }
     }
 }
 
-impl <T: Display> Display for SortMutex<T> {
+impl <T: ?Sized + Display, B: MutexBackend> Display for SortMutex<T, B> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.lock().lock_all().fmt(f)
     }
 }
 
 impl <T: Default> Default for SortMutex<T> {
+    #[track_caller]
     fn default() -> Self {
         Self::new(T::default())
     }
 }
 
+impl <T> From<T> for SortMutex<T> {
+    #[track_caller]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Adopts the value of an existing `std` mutex.
+///
+/// # Panicking
+/// This will panic if the mutex is poisoned.
+#[cfg(feature = "std")]
+impl <T> From<Mutex<T>> for SortMutex<T> {
+    #[track_caller]
+    fn from(mutex: Mutex<T>) -> Self {
+        Self::new(mutex.into_inner().expect("Failed to lock mutex: mutex is poisoned."))
+    }
+}
+
+/// Allows a mutex to be locked as part of a group without calling `lock` first.
+/// ```
+/// use sortlock::{SortMutex, LockGroup};
+///
+/// let lock1 = SortMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (guard1, guard2) = (&lock1, &lock2).lock_all();
+/// assert_eq!(3, *guard1 + *guard2);
+/// ```
+impl <'l, T: ?Sized, B: MutexBackend> SortableLock for &'l SortMutex<T, B> {
+    type Guard = SortMutexLocked<'l, T, B>;
+
+    fn sort_key(&self) -> SortKey {
+        SortMutex::sort_key(self)
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortMutexLocked::new(self.lock_backend(), self)
+    }
+}
+
 /// A guard for a `SortMutex`.
-pub struct SortMutexGuard<'l, T> {
+pub struct SortMutexGuard<'l, T: ?Sized, B = DefaultBackend> {
     /// The lock this request references.
-    lock: &'l SortMutex<T>,
+    lock: &'l SortMutex<T, B>,
 }
 
-impl <'l, T> SortableLock for SortMutexGuard<'l, T> {
-    type Guard = MutexGuard<'l, T>;
+impl <'l, T: ?Sized, B: MutexBackend> SortableLock for SortMutexGuard<'l, T, B> {
+    type Guard = SortMutexLocked<'l, T, B>;
 
     fn sort_key(&self) -> SortKey {
-        self.lock.key
+        self.lock.sort_key()
     }
 
-    #[cfg(feature = "std")]
     fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.lock()
-            .expect("Failed to lock mutex: mutex is poisoned.")
+        SortMutexLocked::new(self.lock.lock_backend(), self.lock)
+    }
+}
+
+/// A guard for a `SortMutex` that owns a reference to the lock.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortMutexGuard<T: ?Sized, B = DefaultBackend> {
+    /// The lock this request references.
+    lock: Arc<SortMutex<T, B>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized, B: MutexBackend + 'static> SortableLock for OwnedSortMutexGuard<T, B> {
+    type Guard = OwnedSortMutexLocked<T, B>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
     }
-    
-    #[cfg(not(feature = "std"))]
+
     fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.lock()
+        self.lock.clone().lock_owned()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{any::Any, sync::Arc, thread};
+/// An acquired lock on a `SortMutex`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortMutexLocked<'l, T: ?Sized, B: MutexBackend + 'l = DefaultBackend> {
+    /// The guard for the internal mutex.
+    ///
+    /// This is only `None` while the lock is temporarily released by `unlocked`.
+    guard: Option<B::Guard<'l>>,
+    /// The lock this guard references.
+    lock: &'l SortMutex<T, B>,
+}
 
-    use crate::{LockGroup, SortMutex};
+unsafe impl <T: ?Sized + Sync, B: MutexBackend + Sync> Sync for SortMutexLocked<'_, T, B> {}
+
+impl <'l, T: ?Sized, B: MutexBackend> SortMutexLocked<'l, T, B> {
+    /// Creates a new `SortMutexLocked` from an acquired internal guard.
+    ///
+    /// - `guard` - The guard for the internal mutex.
+    /// - `lock` - The lock the guard was acquired from.
+    fn new(guard: B::Guard<'l>, lock: &'l SortMutex<T, B>) -> Self {
+        Self {
+            guard: Some(guard),
+            lock,
+        }
+    }
+
+    /// Temporarily unlocks this lock while running a closure and then locks it again.
+    ///
+    /// This is useful for releasing a lock around a blocking call. The lock is reacquired on its own
+    /// without sorting.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortMutex, SortMutexLocked, LockGroup};
+    ///
+    /// let lock = SortMutex::new(1);
+    ///
+    /// let mut guard = lock.lock().lock_all();
+    /// SortMutexLocked::unlocked(&mut guard, || {
+    ///     // The lock can be acquired elsewhere while unlocked.
+    ///     assert!(lock.try_lock().is_some());
+    /// });
+    /// *guard += 1;
+    /// ```
+    ///
+    /// - `f` - The closure to run while unlocked.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock becomes poisoned. If the closure panics the lock is
+    /// acquired again before the panic continues.
+    ///
+    /// # Deadlocks
+    /// Relocking ignores the sort order, so this can deadlock if the thread holds other locks, such
+    /// as the rest of a group, that another thread locks in sorted order together with this one.
+    /// Use `GroupGuard::unlocked` to release and reacquire a whole group instead.
+    pub fn unlocked<R, F: FnOnce() -> R>(this: &mut Self, f: F) -> R {
+        this.guard = None;
+
+        let result = finally(f, || this.guard = Some(this.lock.mutex.lock_ignoring_poison()));
+
+        if this.lock.mutex.is_poisoned() {
+            this.lock.check_poison();
+            panic!("Failed to lock mutex: mutex is poisoned.");
+        }
+
+        result
+    }
+
+    /// Leaks this guard, keeping the lock locked forever and returning a reference to the value.
+    ///
+    /// This is useful for locks in static or leaked storage that are locked once and never
+    /// released. The lock is never unlocked so any later attempt to lock it will block forever.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortMutex, SortMutexLocked, LockGroup};
+    ///
+    /// static LOCK: SortMutex<i32> = SortMutex::const_new(1);
+    ///
+    /// let value: &'static mut i32 = SortMutexLocked::leak(LOCK.lock().lock_all());
+    /// *value += 1;
+    /// assert!(LOCK.try_lock().is_none());
+    /// ```
+    pub fn leak(this: Self) -> &'l mut T {
+        let lock = this.lock;
+
+        mem::forget(this);
+
+        // SAFETY: The guard was forgotten so the mutex is never unlocked and this is the only
+        // reference to the value.
+        unsafe { &mut *lock.data.get() }
+    }
+
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortMutex, SortMutexLocked, LockGroup};
+    ///
+    /// let lock = SortMutex::new((1, 2));
+    ///
+    /// let mut guard = SortMutexLocked::map(lock.lock().lock_all(), |value| &mut value.1);
+    /// *guard += 1;
+    /// drop(guard);
+    ///
+    /// assert_eq!((1, 3), *lock.lock().lock_all());
+    /// ```
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortMutexLocked<'l, U, B> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortMutexLocked {
+            _guard: this.guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortMutexLocked<'l, U, B>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortMutexLocked {
+                    _guard: this.guard,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl <T: ?Sized> SortMutexLocked<'_, T, ParkingLotBackend> {
+    /// Unlocks this lock fairly, passing it directly to a waiting thread if there is one.
+    ///
+    /// This prevents a thread that repeatedly relocks the lock from starving other threads.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn unlock_fair(mut this: Self) {
+        if let Some(guard) = this.guard.take() {
+            ParkingLotBackend::unlock_fair(guard);
+        }
+    }
+}
+
+impl <T: ?Sized, B: MutexBackend> Deref for SortMutexLocked<'_, T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized, B: MutexBackend> DerefMut for SortMutexLocked<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug, B: MutexBackend> Debug for SortMutexLocked<'_, T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display, B: MutexBackend> Display for SortMutexLocked<'_, T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A guard for a component of a value locked by a `SortMutex`.
+///
+/// This is created with `SortMutexLocked::map`. The lock is released when this guard is dropped.
+///
+/// Like `&mut T`, the guard is invariant over `T`, so it cannot be used to store a shorter lived
+/// reference in the locked value.
+/// ```compile_fail
+/// use sortlock::MappedSortMutexLocked;
+///
+/// fn shorten<'a, 'l>(guard: MappedSortMutexLocked<'l, &'static str>) -> MappedSortMutexLocked<'l, &'a str> {
+///     guard
+/// }
+/// ```
+pub struct MappedSortMutexLocked<'l, T: ?Sized, B: MutexBackend + 'l = DefaultBackend> {
+    /// The guard for the internal mutex.
+    _guard: Option<B::Guard<'l>>,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Makes the guard invariant over `T`, as it gives mutable access to the value.
+    _marker: PhantomData<&'l mut T>,
+}
+
+unsafe impl <T: ?Sized + Sync, B: MutexBackend> Sync for MappedSortMutexLocked<'_, T, B> {}
+
+impl <'l, T: ?Sized, B: MutexBackend> MappedSortMutexLocked<'l, T, B> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortMutexLocked<'l, U, B> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortMutexLocked {
+            _guard: this._guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortMutexLocked<'l, U, B>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortMutexLocked {
+                    _guard: this._guard,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized, B: MutexBackend> Deref for MappedSortMutexLocked<'_, T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized, B: MutexBackend> DerefMut for MappedSortMutexLocked<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl <T: ?Sized + Debug, B: MutexBackend> Debug for MappedSortMutexLocked<'_, T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display, B: MutexBackend> Display for MappedSortMutexLocked<'_, T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired lock on a `SortMutex` that owns a reference to the lock.
+///
+/// This is created with `SortMutex::lock_owned`. The lock is released when this guard is dropped.
+///
+/// The guard is only `Send` if the guard of its backend is, so with `StdBackend`, like the
+/// standard library's guards, it must be dropped on the thread that created it. A lock that must
+/// be released on another thread, such as by a spawned thread, can use a backend with guards that
+/// are `Send` such as `SpinBackend` or `FairBackend`.
+/// ```compile_fail
+/// use std::{sync::Arc, thread};
+/// use sortlock::{SortMutex, StdBackend};
+///
+/// let guard = Arc::new(SortMutex::with_backend(1, StdBackend::new())).lock_owned();
+///
+/// thread::spawn(move || drop(guard));
+/// ```
+#[cfg(feature = "alloc")]
+pub struct OwnedSortMutexLocked<T: ?Sized, B: MutexBackend + 'static = DefaultBackend> {
+    /// The guard for the internal mutex.
+    ///
+    /// This must be dropped before `lock`.
+    _guard: B::Guard<'static>,
+    /// The lock this guard references.
+    lock: Arc<SortMutex<T, B>>,
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl <T: ?Sized + Sync, B: MutexBackend + Sync> Sync for OwnedSortMutexLocked<T, B> {}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized, B: MutexBackend> OwnedSortMutexLocked<T, B> {
+    /// Gets the lock this guard references.
+    pub fn lock(this: &Self) -> &Arc<SortMutex<T, B>> {
+        &this.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized, B: MutexBackend> Deref for OwnedSortMutexLocked<T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized, B: MutexBackend> DerefMut for OwnedSortMutexLocked<T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The mutex is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Debug, B: MutexBackend> Debug for OwnedSortMutexLocked<T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Display, B: MutexBackend> Display for OwnedSortMutexLocked<T, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::Any, panic::{self, AssertUnwindSafe}, sync::Arc, thread};
+
+    use crate::{LockGroup, SortMutex, SortMutexGuard, SortMutexLocked, SortableLock, SpinBackend};
 
     #[test]
     fn test_lock2() {
@@ -131,6 +1107,575 @@ mod tests {
 
         println!("{} {}", guard1, guard2);
     }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = SortMutex::new(1);
+
+        let guard = lock.try_lock().unwrap();
+        assert_eq!(1, *guard);
+        assert!(lock.try_lock().is_none());
+
+        drop(guard);
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_lock_owned() {
+        struct Holder {
+            guard: crate::OwnedSortMutexLocked<i32>,
+        }
+
+        let lock = Arc::new(SortMutex::new(1));
+
+        let mut holder = Holder {
+            guard: lock.clone().lock_owned(),
+        };
+        *holder.guard += 1;
+        assert!(lock.try_lock().is_none());
+        drop(holder);
+
+        assert_eq!(2, *lock.lock().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_lock_owned_send() {
+        let lock = Arc::new(SortMutex::with_backend(1, SpinBackend::new()));
+
+        let mut guard = lock.clone().lock_owned();
+        *guard += 1;
+
+        thread::spawn(move || *guard += 1).join().unwrap();
+
+        assert_eq!(3, *lock.lock().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_owned_group() {
+        let lock1 = Arc::new(SortMutex::new(1));
+        let lock2 = Arc::new(SortMutex::new(2));
+
+        let mut guards = (lock2.clone().lock_owned_request(), lock1.clone().lock_owned_request()).lock_all();
+        *guards.0 += 1;
+        *guards.1 += 1;
+        assert!(lock1.try_lock().is_none());
+        assert!(lock2.try_lock().is_none());
+        drop(guards);
+
+        assert_eq!(2, *lock1.lock().lock_all());
+        assert_eq!(3, *lock2.lock().lock_all());
+    }
+
+    #[test]
+    fn test_set_replace_take() {
+        let lock = SortMutex::new(vec![1]);
+
+        lock.set(vec![2]);
+        assert_eq!(vec![2], lock.replace(vec![3]));
+        assert_eq!(vec![3], lock.take());
+        assert!(lock.lock().lock_all().is_empty());
+    }
+
+    #[test]
+    fn test_unlocked() {
+        let lock = SortMutex::new(1);
+
+        let mut guard = lock.lock().lock_all();
+        let value = SortMutexLocked::unlocked(&mut guard, || {
+            lock.set(2);
+            3
+        });
+        *guard += value;
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(5, *lock.lock().lock_all());
+    }
+
+    #[test]
+    fn test_unlocked_panic() {
+        let lock = SortMutex::new(1);
+
+        let mut guard = lock.lock().lock_all();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            SortMutexLocked::unlocked(&mut guard, || panic!("unlocked"));
+        }));
+        assert!(result.is_err());
+
+        // The lock is held again so the guard is still the only access to the value.
+        assert!(lock.try_lock().is_none());
+        *guard += 1;
+        drop(guard);
+
+        assert_eq!(2, *lock.lock().lock_all());
+    }
+
+    #[test]
+    fn test_leak() {
+        let lock: &'static SortMutex<i32> = Box::leak(Box::new(SortMutex::new(1)));
+
+        let value = SortMutexLocked::leak(lock.lock().lock_all());
+        *value += 1;
+        assert_eq!(2, *value);
+        assert!(lock.try_lock().is_none());
+    }
+
+    #[test]
+    fn test_swap() {
+        let lock1 = SortMutex::new(1);
+        let lock2 = SortMutex::new(2);
+
+        lock1.swap(&lock2);
+        lock2.swap(&lock1);
+        lock2.swap(&lock1);
+        lock1.swap(&lock1);
+
+        assert_eq!(2, *lock1.lock().lock_all());
+        assert_eq!(1, *lock2.lock().lock_all());
+    }
+
+    #[test]
+    fn test_get_cloned() {
+        let lock = SortMutex::new(vec![1, 2]);
+
+        let value = lock.get_cloned();
+        lock.update(|value| value.push(3));
+
+        assert_eq!(vec![1, 2], value);
+        assert_eq!(vec![1, 2, 3], lock.get_cloned());
+    }
+
+    #[test]
+    fn test_data_ptr() {
+        let lock = SortMutex::new(1);
+
+        let guard = lock.lock().lock_all();
+        assert_eq!(&*guard as *const i32, lock.data_ptr().cast_const());
+    }
+
+    #[test]
+    fn test_from() {
+        let lock = SortMutex::from(1);
+        assert_eq!(1, *lock.lock().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_std() {
+        let lock: SortMutex<i32> = std::sync::Mutex::new(2).into();
+        assert_eq!(2, *lock.lock().lock_all());
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let lock1 = SortMutex::new(1);
+        let lock2 = SortMutex::new(2);
+
+        assert_eq!(lock1.sort_key(), lock1.lock().sort_key());
+        assert!(lock1.sort_key() < lock2.sort_key());
+    }
+
+    #[cfg(all(feature = "diagnostics", feature = "std"))]
+    #[test]
+    fn test_created_at() {
+        let line = line!();
+        let lock = Arc::new(SortMutex::new(1));
+        let registered = crate::LockRegistry::get_or_create::<u32>("test_created_at");
+
+        assert_eq!(file!(), lock.created_at().file());
+        assert_eq!(line + 1, lock.created_at().line());
+        assert_eq!(line + 2, registered.created_at().line());
+
+        let poisoner = lock.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().lock_all();
+            panic!("poison the lock");
+        }).join();
+
+        let error = thread::spawn(move || *lock.lock().lock_all()).join().unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains(&format!("{}:{}", file!(), line + 1)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_named_lock() {
+        let lock = Arc::new(SortMutex::new_named("test_named_lock", vec![1]));
+        assert_eq!(Some("test_named_lock"), lock.name());
+        assert_eq!(None, SortMutex::new(1).name());
+        assert_eq!("SortMutex { name: \"test_named_lock\", data: [1] }", format!("{lock:?}"));
+        assert_eq!("[1]", format!("{:?}", SortMutex::new(vec![1])));
+
+        let poisoner = lock.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().lock_all();
+            panic!("poison the lock");
+        }).join();
+
+        let error = thread::spawn(move || lock.lock().lock_all().len()).join().unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains("\"test_named_lock\""));
+    }
+
+    #[test]
+    fn test_lock16() {
+        let locks: [SortMutex<usize>; 16] = core::array::from_fn(SortMutex::new);
+
+        let guards = (
+            locks[15].lock(), locks[14].lock(), locks[13].lock(), locks[12].lock(),
+            locks[11].lock(), locks[10].lock(), locks[9].lock(), locks[8].lock(),
+            locks[7].lock(), locks[6].lock(), locks[5].lock(), locks[4].lock(),
+            locks[3].lock(), locks[2].lock(), locks[1].lock(), locks[0].lock(),
+        ).lock_all();
+
+        assert_eq!(15, *guards.0);
+        assert_eq!(0, *guards.15);
+    }
+
+    #[test]
+    fn test_lock_array() {
+        let locks: [SortMutex<usize>; 4] = core::array::from_fn(SortMutex::new);
+
+        let mut guards = [locks[2].lock(), locks[0].lock(), locks[3].lock(), locks[1].lock()].lock_all();
+        *guards[0] += 10;
+
+        assert_eq!([12, 0, 3, 1], guards.each_ref().map(|guard| **guard));
+        drop(guards);
+
+        assert_eq!(12, *locks[2].lock().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_lock_vec() {
+        let locks: Vec<_> = (0..4).map(SortMutex::new).collect();
+
+        let requests = [2, 0, 3, 1].into_iter().map(|i| locks[i].lock()).collect::<Vec<_>>();
+        let mut guards = requests.lock_all();
+        *guards[0] += 10;
+
+        assert_eq!(vec![12, 0, 3, 1], guards.iter().map(|guard| **guard).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_lock_option() {
+        let lock1 = SortMutex::new(1);
+        let lock2 = SortMutex::new(2);
+
+        let (guard1, guard2, guard3) = (Some(lock2.lock()), None::<SortMutexGuard<'_, i32>>, Some(lock1.lock())).lock_all();
+        assert_eq!(Some(2), guard1.as_deref().copied());
+        assert!(guard2.is_none());
+        assert_eq!(Some(1), guard3.as_deref().copied());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_lock_hash_map() {
+        use std::collections::HashMap;
+
+        let locks: Vec<_> = (0..4).map(SortMutex::new).collect();
+
+        let requests: HashMap<_, _> = [3, 1, 2].into_iter().map(|i| (i, locks[i].lock())).collect();
+        let guards = requests.lock_all();
+
+        assert_eq!(3, guards.len());
+        assert!(guards.iter().all(|(i, guard)| *i == **guard));
+        assert!(locks[0].try_lock().is_some());
+        assert!(locks[1].try_lock().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_lock_btree_map() {
+        use std::collections::BTreeMap;
+
+        let locks: Vec<_> = (0..4).map(SortMutex::new).collect();
+
+        let requests: BTreeMap<_, _> = [3, 1, 2].into_iter().map(|i| (i, locks[i].lock())).collect();
+        let guards = requests.lock_all();
+
+        assert_eq!(vec![1, 2, 3], guards.values().map(|guard| **guard).collect::<Vec<_>>());
+        assert!(locks[0].try_lock().is_some());
+        assert!(locks[3].try_lock().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "derive")]
+    fn test_derive() {
+        use crate::{SortReadGuard, SortRwLock};
+
+        #[derive(LockGroup)]
+        struct Requests<'l> {
+            count: SortMutexGuard<'l, u32>,
+            name: SortReadGuard<'l, &'static str>,
+        }
+
+        #[derive(LockGroup)]
+        struct Many<'l>(
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+            SortMutexGuard<'l, usize>, SortMutexGuard<'l, usize>,
+        );
+
+        let count = SortMutex::new(0);
+        let name = SortRwLock::new("name");
+
+        let mut locked = Requests { count: count.lock(), name: name.read() }.lock_all();
+        *locked.count += 1;
+        assert_eq!("name", *locked.name);
+        drop(locked);
+
+        assert_eq!(1, *count.lock().lock_all());
+
+        let locks: [SortMutex<usize>; 17] = core::array::from_fn(SortMutex::new);
+        let [l0, l1, l2, l3, l4, l5, l6, l7, l8, l9, l10, l11, l12, l13, l14, l15, l16] = &locks;
+
+        let locked = Many(
+            l16.lock(), l15.lock(), l14.lock(), l13.lock(), l12.lock(), l11.lock(), l10.lock(),
+            l9.lock(), l8.lock(), l7.lock(), l6.lock(), l5.lock(), l4.lock(), l3.lock(), l2.lock(),
+            l1.lock(), l0.lock(),
+        ).lock_all();
+        assert_eq!(16, *locked.0);
+        assert_eq!(0, *locked.16);
+    }
+
+    #[test]
+    fn test_lock_all_macro() {
+        let locks: [SortMutex<usize>; 18] = core::array::from_fn(SortMutex::new);
+        let [l0, l1, l2, l3, l4, l5, l6, l7, l8, l9, l10, l11, l12, l13, l14, l15, l16, l17] = &locks;
+
+        let (guard,) = crate::lock_all!(lock l3);
+        assert_eq!(3, *guard);
+        drop(guard);
+
+        let guards = crate::lock_all!(
+            lock l17, lock l16, lock l15, lock l14, lock l13, lock l12, lock l11, lock l10, lock l9,
+            lock l8, lock l7, lock l6, lock l5, lock l4, lock l3, lock l2, lock l1, lock l0,
+        );
+        assert_eq!(17, *guards.0);
+        assert_eq!(0, *guards.17);
+    }
+
+    #[test]
+    fn test_lock_ref() {
+        let lock1 = SortMutex::new(1);
+        let lock2 = SortMutex::new(2);
+
+        let (mut guard2, guard1) = (&lock2, lock1.lock()).lock_all();
+        *guard2 += *guard1;
+        drop((guard1, guard2));
+
+        assert_eq!(3, *(&lock2).lock_all());
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let lock = SortMutex::new(1);
+        let value = String::from("value");
+
+        let ((), guard, value) = ((), lock.lock(), crate::Plain(&value)).lock_all();
+        assert_eq!(1, *guard);
+        assert_eq!("value", value);
+    }
+
+    #[test]
+    fn test_mixed_backends() {
+        let lock1 = Arc::new(SortMutex::with_backend(0, SpinBackend::new()));
+        let lock2 = Arc::new(SortMutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let lock1 = lock1.clone();
+            let lock2 = lock2.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    if i % 2 == 0 {
+                        let (mut guard1, mut guard2) = (lock1.lock(), lock2.lock()).lock_all();
+                        *guard1 += 1;
+                        *guard2 += 1;
+                    } else {
+                        let (mut guard2, mut guard1) = (lock2.lock(), lock1.lock()).lock_all();
+                        *guard1 += 1;
+                        *guard2 += 1;
+                    }
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *SortMutexLocked::map(lock1.lock().lock_all(), |value| value));
+        assert_eq!(400, *lock2.lock().lock_all());
+    }
+
+    #[cfg(feature = "parking_lot")]
+    #[test]
+    fn test_parking_lot_backend() {
+        let lock = Arc::new(SortMutex::with_backend(0, crate::ParkingLotBackend::new()));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let mut guard = lock.lock().lock_all();
+                    *guard += 1;
+                    SortMutexLocked::unlock_fair(guard);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let guard = lock.try_lock_for(std::time::Duration::from_millis(10)).unwrap();
+        assert_eq!(400, *guard);
+        assert!(lock.try_lock_for(std::time::Duration::from_millis(10)).is_none());
+    }
+
+    #[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn test_futex_poison() {
+        let lock = Arc::new(SortMutex::with_backend(1, crate::FutexBackend::new()));
+
+        let poisoner = lock.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().lock_all();
+            panic!("poison the lock");
+        }).join();
+
+        assert!(lock.is_poisoned());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| lock.lock().lock_all())).is_err());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| lock.try_lock())).is_err());
+
+        lock.clear_poison();
+        assert_eq!(1, *lock.lock().lock_all());
+    }
+
+    #[cfg(all(feature = "std", target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn test_futex_backend() {
+        let lock = Arc::new(SortMutex::with_backend(0, crate::FutexBackend::new()));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    *lock.lock().lock_all() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let guard = lock.lock().lock_all();
+        assert_eq!(4000, *guard);
+
+        let waiter = lock.clone();
+        let timed_out = thread::spawn(move || waiter.try_lock_for(std::time::Duration::from_millis(20)).is_none());
+        assert!(timed_out.join().unwrap());
+        drop(guard);
+
+        assert!(lock.try_lock_for(std::time::Duration::from_millis(20)).is_some());
+    }
+
+    #[cfg(feature = "critical-section")]
+    #[test]
+    fn test_critical_section_backend() {
+        let lock1 = Arc::new(SortMutex::with_backend(0, crate::CriticalSectionBackend::new()));
+        let lock2 = Arc::new(SortMutex::with_backend(0, crate::CriticalSectionBackend::new()));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock1 = lock1.clone();
+            let lock2 = lock2.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let (mut guard1, mut guard2) = (lock1.lock(), lock2.lock()).lock_all();
+                    *guard1 += 1;
+                    *guard2 += 1;
+
+                    // Guards may be released in any order.
+                    drop(guard1);
+                    assert!(lock1.try_lock().is_some());
+                    drop(guard2);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let guard = lock1.lock().lock_all();
+        assert_eq!(400, *guard);
+        assert!(lock1.try_lock().is_none());
+    }
+
+    #[test]
+    fn test_update() {
+        let lock = SortMutex::new(1);
+
+        assert_eq!(2, lock.update(|value| {
+            *value += 1;
+            *value
+        }));
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test]
+    fn test_unsized() {
+        let lock1: Box<SortMutex<[i32]>> = Box::new(SortMutex::new([1, 2, 3]));
+        let lock2: Arc<SortMutex<dyn ToString + Send>> = Arc::new(SortMutex::new(4));
+
+        let (mut guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all();
+        guard1[0] = 5;
+        assert_eq!("4", guard2.to_string());
+        drop((guard1, guard2));
+
+        assert_eq!([5, 2, 3], *lock1.lock().lock_all());
+    }
+
+    #[test]
+    fn test_const_new() {
+        static LOCK1: SortMutex<i32> = SortMutex::const_new(1);
+        static LOCK2: SortMutex<i32> = SortMutex::const_new(2);
+
+        let (guard2, guard1) = (LOCK2.lock(), LOCK1.lock()).lock_all();
+        assert_eq!(3, *guard1 + *guard2);
+        drop((guard1, guard2));
+
+        let key1 = LOCK1.lock().sort_key();
+        let key2 = LOCK2.lock().sort_key();
+        assert_ne!(key1, key2);
+        assert_eq!(key1, LOCK1.lock().sort_key());
+    }
+
+    #[test]
+    fn test_map() {
+        let lock = SortMutex::new((1, vec![1, 2, 3]));
+
+        let guard = lock.lock().lock_all();
+        let guard = SortMutexLocked::try_map(guard, |value| value.1.get_mut(5)).unwrap_err();
+        let mut guard = SortMutexLocked::map(guard, |value| value.1.as_mut_slice());
+        guard[0] = 4;
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(4, lock.lock().lock_all().1[0]);
+    }
    
     #[test]
     fn test_deadlock() -> Result<(), Box<dyn Any + Send + 'static>> {
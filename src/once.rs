@@ -0,0 +1,338 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Formatter}, ops::Deref};
+
+#[cfg(feature = "std")]
+use std::sync::OnceLock as Once;
+
+#[cfg(not(feature = "std"))]
+use spin::Once;
+
+use crate::{LockAccess, SortKey, SortableLock, key::LazySortKey};
+
+/// A sortable cell that can be written to only once.
+/// This is a sortable version of rust's `OnceLock` type.
+///
+/// Initialization blocks other threads that try to read the value, so it behaves like a lock.
+/// Requesting initialization with `get_or_init_request` allows it to be sorted with other locks
+/// through the use of `lock_all`, so the value can be initialized and read alongside them.
+///
+/// The initializer runs while the group holds every lock sorted before this one. It must not lock
+/// any lock in the group or any lock that sorts before this one, as that can deadlock.
+/// ```
+/// use sortlock::{SortMutex, SortOnceLock, LockGroup};
+///
+/// let config = SortOnceLock::new();
+/// let lock = SortMutex::new(1);
+///
+/// let (value, guard) = (config.get_or_init_request(|| 2), lock.lock()).lock_all();
+/// assert_eq!(3, *value + *guard);
+/// assert_eq!(Some(&2), config.get());
+/// ```
+pub struct SortOnceLock<T> {
+    /// The internal cell.
+    once: Once<T>,
+    /// The sort key for this lock.
+    key: LazySortKey,
+}
+
+impl <T> SortOnceLock<T> {
+    /// Creates a new empty `SortOnceLock`.
+    #[track_caller]
+    pub fn new() -> Self {
+        Self {
+            once: Once::new(),
+            key: LazySortKey::new(SortKey::new()),
+        }
+    }
+
+    /// Creates a new empty `SortOnceLock` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    /// ```
+    /// use sortlock::SortOnceLock;
+    ///
+    /// static VALUE: SortOnceLock<u32> = SortOnceLock::const_new();
+    ///
+    /// assert_eq!(1, *VALUE.get_or_init(|| 1));
+    /// ```
+    pub const fn const_new() -> Self {
+        Self {
+            once: Once::new(),
+            key: LazySortKey::unassigned(),
+        }
+    }
+
+    /// Gets the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        self.once.get()
+    }
+
+    /// Gets a mutable reference to the value if it has been initialized.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.once.get_mut()
+    }
+
+    /// Gets the value, initializing it with a closure if it has not been initialized.
+    ///
+    /// If another thread is initializing the value this blocks until it has finished. As only a
+    /// single lock is involved no sorting is performed, so the closure should not lock any locks
+    /// that may be held by a thread waiting on this lock.
+    ///
+    /// - `f` - The closure that creates the value.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        #[cfg(feature = "std")]
+        return self.once.get_or_init(f);
+        #[cfg(not(feature = "std"))]
+        return self.once.call_once(f);
+    }
+
+    /// Requests to get the value, initializing it with a closure if it has not been initialized.
+    ///
+    /// The closure is only called if the value has not been initialized when the request is locked.
+    /// It runs while the group holds every lock sorted before this one, so it must not lock any
+    /// lock in the group or any lock that sorts before this one.
+    ///
+    /// - `f` - The closure that creates the value.
+    pub fn get_or_init_request<F: Fn() -> T>(&self, f: F) -> SortOnceGuard<'_, T, F> {
+        SortOnceGuard {
+            lock: self,
+            init: f,
+        }
+    }
+
+    /// Sets the value if it has not been initialized.
+    ///
+    /// If the value has already been initialized the given value is returned.
+    /// ```
+    /// use sortlock::SortOnceLock;
+    ///
+    /// let lock = SortOnceLock::new();
+    ///
+    /// assert_eq!(Ok(()), lock.set(1));
+    /// assert_eq!(Err(2), lock.set(2));
+    /// assert_eq!(Some(&1), lock.get());
+    /// ```
+    ///
+    /// - `value` - The value to set.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+
+        self.get_or_init(|| value.take().expect("The value is only taken once."));
+
+        match value {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Consumes this lock and returns the value if it has been initialized.
+    pub fn into_inner(self) -> Option<T> {
+        #[cfg(feature = "std")]
+        return self.once.into_inner();
+        #[cfg(not(feature = "std"))]
+        return self.once.try_into_inner();
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+}
+
+impl <T: Debug> Debug for SortOnceLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SortOnceLock")
+            .field(&self.get())
+            .finish()
+    }
+}
+
+impl <T> Default for SortOnceLock<T> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T> From<T> for SortOnceLock<T> {
+    fn from(value: T) -> Self {
+        let lock = Self::new();
+        let _ = lock.set(value);
+
+        lock
+    }
+}
+
+/// A guard for a `SortOnceLock`.
+///
+/// This requests the value of the lock, initializing it if needed.
+pub struct SortOnceGuard<'l, T, F> {
+    /// The lock this guard references.
+    lock: &'l SortOnceLock<T>,
+    /// The closure that creates the value.
+    init: F,
+}
+
+impl <'l, T, F: Fn() -> T> SortableLock for SortOnceGuard<'l, T, F> {
+    type Guard = &'l T;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.get_or_init(&self.init)
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A sortable value that is initialized when it is first accessed.
+/// This is a sortable version of rust's `LazyLock` type.
+///
+/// A reference to the lock can be placed in a group with `lock_all` to initialize it in sorted
+/// order with other locks. When initialized this way the initializer runs while the group holds
+/// every lock sorted before this one, so it must not lock any lock in the group or any lock that
+/// sorts before this one, as that can deadlock.
+/// ```
+/// use sortlock::{SortLazyLock, SortMutex, LockGroup};
+///
+/// static LOCK: SortMutex<u32> = SortMutex::const_new(1);
+/// static VALUE: SortLazyLock<u32> = SortLazyLock::new(|| *LOCK.lock().lock_all() + 1);
+///
+/// assert_eq!(2, *VALUE);
+///
+/// let (value, guard) = (&VALUE, LOCK.lock()).lock_all();
+/// assert_eq!(3, *value + *guard);
+/// ```
+pub struct SortLazyLock<T, F = fn() -> T> {
+    /// The initialized value.
+    once: SortOnceLock<T>,
+    /// The closure that creates the value.
+    ///
+    /// This is taken by the thread that initializes the value.
+    init: UnsafeCell<Option<F>>,
+}
+
+unsafe impl <T: Send + Sync, F: Send> Sync for SortLazyLock<T, F> {}
+
+impl <T, F: FnOnce() -> T> SortLazyLock<T, F> {
+    /// Creates a new `SortLazyLock`.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `f` - The closure that creates the value.
+    pub const fn new(f: F) -> Self {
+        Self {
+            once: SortOnceLock::const_new(),
+            init: UnsafeCell::new(Some(f)),
+        }
+    }
+
+    /// Gets the value, initializing it if it has not been initialized.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    ///
+    /// # Panicking
+    /// This function will panic if a previous initialization panicked.
+    pub fn force(this: &Self) -> &T {
+        this.once.get_or_init(|| {
+            // SAFETY: Only the thread initializing the value can reach this point.
+            let init = unsafe { (*this.init.get()).take() };
+
+            init.expect("Failed to initialize lazy lock: a previous initialization panicked.")()
+        })
+    }
+
+    /// Gets the value if it has been initialized.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn get(this: &Self) -> Option<&T> {
+        this.once.get()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn sort_key(this: &Self) -> SortKey {
+        this.once.sort_key()
+    }
+}
+
+impl <T, F: FnOnce() -> T> Deref for SortLazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl <T: Debug, F> Debug for SortLazyLock<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SortLazyLock")
+            .field(&self.once.get())
+            .finish()
+    }
+}
+
+impl <T: Default> Default for SortLazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl <'l, T, F: FnOnce() -> T> SortableLock for &'l SortLazyLock<T, F> {
+    type Guard = &'l T;
+
+    fn sort_key(&self) -> SortKey {
+        SortLazyLock::sort_key(self)
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortLazyLock::force(self)
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{LockGroup, SortLazyLock, SortMutex, SortOnceLock};
+
+    #[test]
+    fn test_once_lock() {
+        let lock = SortOnceLock::new();
+        assert_eq!(None, lock.get());
+
+        assert_eq!(1, *lock.get_or_init(|| 1));
+        assert_eq!(1, *lock.get_or_init(|| 2));
+        assert_eq!(Some(1), lock.into_inner());
+    }
+
+    #[test]
+    fn test_once_lock_group() {
+        let lock1 = SortOnceLock::new();
+        let lock2 = SortMutex::new(2);
+
+        let (value1, guard2, value2) = (lock1.get_or_init_request(|| 1), lock2.lock(), lock1.get_or_init_request(|| 3)).lock_all();
+        assert_eq!(1, *value1);
+        assert_eq!(1, *value2);
+        assert_eq!(2, *guard2);
+    }
+
+    #[test]
+    fn test_lazy_lock() {
+        let calls = AtomicUsize::new(0);
+        let lock = SortLazyLock::new(|| calls.fetch_add(1, Ordering::Relaxed) + 1);
+        assert_eq!(None, SortLazyLock::get(&lock));
+
+        assert_eq!(1, *lock);
+        assert_eq!(1, *(&lock).lock_all());
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,255 @@
+use core::{fmt::{self, Debug, Display, Formatter}, marker::PhantomData, ops::Deref, ptr, sync::atomic::{AtomicU32, Ordering}};
+
+use std::io;
+
+use crate::{LockGroup, MutexBackend, SortMutex, SortMutexGuard, SortMutexLocked};
+
+std::thread_local! {
+    /// The kernel thread ID of the current thread.
+    static THREAD_ID: u32 = unsafe { libc::gettid() as u32 };
+}
+
+/// A sortable lock that ensures exclusive access to a resource with priority inheritance.
+///
+/// While a thread holds this lock it runs with the priority of the highest priority thread
+/// waiting for it. This prevents a low priority thread holding the lock from delaying a high
+/// priority thread indefinitely, which is important for realtime threads. This uses the
+/// priority inheritance futex operations of the Linux kernel so it is only available on Linux.
+/// ```
+/// use sortlock::{SortMutex, SortPiMutex, LockGroup};
+///
+/// let lock1 = SortPiMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (mut guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+///
+/// This is a `SortMutex` using a `PiBackend`, which it derefs to. Unlike `SortMutex` this lock
+/// is never poisoned.
+pub struct SortPiMutex<T: ?Sized> {
+    /// The internal lock.
+    mutex: SortMutex<T, PiBackend>,
+}
+
+impl <T> SortPiMutex<T> {
+    /// Creates a new `SortPiMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::with_backend(value, PiBackend::new()),
+        }
+    }
+
+    /// Creates a new `SortPiMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::const_with_backend(value, PiBackend::new()),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortPiMutex<T> {
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+}
+
+impl <T: ?Sized> Deref for SortPiMutex<T> {
+    type Target = SortMutex<T, PiBackend>;
+
+    fn deref(&self) -> &SortMutex<T, PiBackend> {
+        &self.mutex
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortPiMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortPiMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortPiMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortPiMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A guard for a `SortPiMutex`.
+pub type SortPiMutexGuard<'l, T> = SortMutexGuard<'l, T, PiBackend>;
+
+/// An acquired lock on a `SortPiMutex`.
+///
+/// The lock is released when this guard is dropped. The guard must be dropped on the thread that
+/// created it as the kernel tracks the owner of the lock.
+pub type SortPiMutexLocked<'l, T> = SortMutexLocked<'l, T, PiBackend>;
+
+/// A backend using a Linux priority inheritance futex.
+///
+/// While a thread holds the lock it runs with the priority of the highest priority thread
+/// waiting for it. Guards must be dropped on the thread that created them as the kernel tracks
+/// the owner of the lock. This backend is never poisoned.
+/// ```
+/// use sortlock::{PiBackend, SortMutex, LockGroup};
+///
+/// let lock = SortMutex::with_backend(1, PiBackend::new());
+///
+/// assert_eq!(1, *lock.lock().lock_all());
+/// ```
+#[derive(Debug, Default)]
+pub struct PiBackend {
+    /// The futex word. This holds the thread ID of the owner or zero when unlocked.
+    futex: AtomicU32,
+}
+
+impl PiBackend {
+    /// Creates a new unlocked `PiBackend`.
+    pub const fn new() -> Self {
+        Self {
+            futex: AtomicU32::new(0),
+        }
+    }
+
+    /// Creates a guard for a lock held by the current thread.
+    fn guard(&self) -> PiBackendGuard<'_> {
+        PiBackendGuard {
+            backend: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Unlocks the futex, waking the highest priority waiter if there is one.
+    fn unlock(&self) {
+        let thread = THREAD_ID.with(|id| *id);
+
+        if self.futex.compare_exchange(thread, 0, Ordering::Release, Ordering::Relaxed).is_ok() {
+            return;
+        }
+
+        // SAFETY: The futex word lives as long as the backend and is held by the current thread.
+        unsafe { libc::syscall(libc::SYS_futex, self.futex.as_ptr(), libc::FUTEX_UNLOCK_PI | libc::FUTEX_PRIVATE_FLAG) };
+    }
+}
+
+unsafe impl MutexBackend for PiBackend {
+    type Guard<'l> = PiBackendGuard<'l>;
+
+    /// Locks the futex, asking the kernel to boost the owner while waiting.
+    ///
+    /// # Panicking
+    /// This method will panic if the kernel rejects the lock, such as when the current thread
+    /// already holds it.
+    fn lock(&self) -> Self::Guard<'_> {
+        if let Some(guard) = self.try_lock() {
+            return guard;
+        }
+
+        loop {
+            // SAFETY: The futex word lives as long as the backend and is only used as a futex.
+            let result = unsafe { libc::syscall(libc::SYS_futex, self.futex.as_ptr(), libc::FUTEX_LOCK_PI | libc::FUTEX_PRIVATE_FLAG, 0, ptr::null::<libc::timespec>()) };
+
+            if result == 0 {
+                return self.guard();
+            }
+
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(libc::EINTR) {
+                panic!("Failed to lock mutex: {error}.");
+            }
+        }
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        let thread = THREAD_ID.with(|id| *id);
+
+        self.futex.compare_exchange(0, thread, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.guard())
+    }
+}
+
+/// A held lock on a `PiBackend`.
+///
+/// The lock is released when this guard is dropped.
+#[derive(Debug)]
+pub struct PiBackendGuard<'l> {
+    /// The backend this guard references.
+    backend: &'l PiBackend,
+    /// Prevents the guard from being sent to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl Sync for PiBackendGuard<'_> {}
+
+impl Drop for PiBackendGuard<'_> {
+    fn drop(&mut self) {
+        self.backend.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortPiMutex};
+
+    #[test]
+    fn test_try_lock() {
+        let lock = SortPiMutex::new(1);
+
+        let guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(1, *lock.try_lock().unwrap());
+    }
+
+    #[test]
+    fn test_pi_threads() {
+        let lock = Arc::new(SortPiMutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    *lock.lock().lock_all() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(4000, *lock.lock().lock_all());
+    }
+}
@@ -0,0 +1,359 @@
+use core::{fmt::{self, Debug, Formatter}, sync::atomic::{AtomicBool, Ordering}};
+
+use lock_api::{GuardSend, Mutex, MutexGuard, RawMutex, RawRwLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{LockAccess, SortKey, SortableLock, key::LazySortKey};
+
+/// A raw sortable lock for use with the `lock_api` crate.
+///
+/// This allows `lock_api::Mutex<RawSortMutex, T>` to be used with code that is generic over
+/// `lock_api` while still being sorted with other locks. A reference to such a mutex is a request
+/// to lock it, so it can be locked in a group with `lock_all`.
+/// ```
+/// use lock_api::RawMutex;
+/// use sortlock::{RawSortMutex, SortMutex, LockGroup};
+///
+/// static LOCK1: lock_api::Mutex<RawSortMutex, i32> = lock_api::Mutex::const_new(RawSortMutex::INIT, 1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (mut guard1, guard2) = (&LOCK1, lock2.lock()).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct RawSortMutex {
+    /// Whether the lock is held.
+    locked: AtomicBool,
+    /// The sort key for this lock.
+    key: LazySortKey,
+}
+
+impl RawSortMutex {
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+}
+
+unsafe impl RawMutex for RawSortMutex {
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+        key: LazySortKey::unassigned(),
+    };
+
+    type GuardMarker = GuardSend;
+
+    fn lock(&self) {
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            while self.locked.load(Ordering::Relaxed) {
+                #[cfg(feature = "std")]
+                std::thread::yield_now();
+                #[cfg(not(feature = "std"))]
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+
+    fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+impl Debug for RawSortMutex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawSortMutex")
+            .field("locked", &self.is_locked())
+            .field("key", &self.sort_key())
+            .finish()
+    }
+}
+
+impl <'l, T: ?Sized> SortableLock for &'l Mutex<RawSortMutex, T> {
+    type Guard = MutexGuard<'l, RawSortMutex, T>;
+
+    fn sort_key(&self) -> SortKey {
+        // SAFETY: The raw lock is only used to read its sort key.
+        unsafe { self.raw() }.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock()
+    }
+}
+
+/// An adapter that attaches a sort key to any `lock_api` raw lock.
+///
+/// This allows locks from other crates such as `parking_lot` to be sorted with other locks
+/// without replacing them. Wrapping a raw mutex makes references to a `lock_api::Mutex` sortable
+/// requests, and wrapping a raw reader-writer lock allows `read` and `write` requests to be made
+/// for a `lock_api::RwLock`.
+/// ```
+/// use sortlock::{LockGroup, SortMutex, Sorted};
+///
+/// type Mutex<T> = lock_api::Mutex<Sorted<parking_lot::RawMutex>, T>;
+/// type RwLock<T> = lock_api::RwLock<Sorted<parking_lot::RawRwLock>, T>;
+///
+/// let lock1: Mutex<i32> = Mutex::new(1);
+/// let lock2: RwLock<i32> = RwLock::new(2);
+/// let lock3 = SortMutex::new(3);
+///
+/// let (mut guard1, guard2, guard3) = (&lock1, Sorted::read(&lock2), lock3.lock()).lock_all();
+/// *guard1 += *guard2 + *guard3;
+/// assert_eq!(6, *guard1);
+/// ```
+pub struct Sorted<R> {
+    /// The wrapped raw lock.
+    raw: R,
+    /// The sort key for this lock.
+    key: LazySortKey,
+}
+
+impl <R> Sorted<R> {
+    /// Creates a new `Sorted` wrapping a raw lock.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `raw` - The raw lock to wrap.
+    pub const fn new(raw: R) -> Self {
+        Self {
+            raw,
+            key: LazySortKey::unassigned(),
+        }
+    }
+
+    /// Gets the wrapped raw lock.
+    pub fn inner(&self) -> &R {
+        &self.raw
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+}
+
+impl <R: RawRwLock> Sorted<R> {
+    /// Requests to lock a reader-writer lock for reading.
+    ///
+    /// - `lock` - The lock to read.
+    pub fn read<T: ?Sized>(lock: &RwLock<Self, T>) -> SortedReadGuard<'_, R, T> {
+        SortedReadGuard {
+            lock
+        }
+    }
+
+    /// Requests to lock a reader-writer lock for writing.
+    ///
+    /// - `lock` - The lock to write.
+    pub fn write<T: ?Sized>(lock: &RwLock<Self, T>) -> SortedWriteGuard<'_, R, T> {
+        SortedWriteGuard {
+            lock
+        }
+    }
+}
+
+unsafe impl <R: RawMutex> RawMutex for Sorted<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self::new(R::INIT);
+
+    type GuardMarker = R::GuardMarker;
+
+    fn lock(&self) {
+        self.raw.lock();
+    }
+
+    fn try_lock(&self) -> bool {
+        self.raw.try_lock()
+    }
+
+    unsafe fn unlock(&self) {
+        // SAFETY: The caller ensures the lock is held in the current context.
+        unsafe { self.raw.unlock() }
+    }
+
+    fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+}
+
+unsafe impl <R: RawRwLock> RawRwLock for Sorted<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self::new(R::INIT);
+
+    type GuardMarker = R::GuardMarker;
+
+    fn lock_shared(&self) {
+        self.raw.lock_shared();
+    }
+
+    fn try_lock_shared(&self) -> bool {
+        self.raw.try_lock_shared()
+    }
+
+    unsafe fn unlock_shared(&self) {
+        // SAFETY: The caller ensures a shared lock is held in the current context.
+        unsafe { self.raw.unlock_shared() }
+    }
+
+    fn lock_exclusive(&self) {
+        self.raw.lock_exclusive();
+    }
+
+    fn try_lock_exclusive(&self) -> bool {
+        self.raw.try_lock_exclusive()
+    }
+
+    unsafe fn unlock_exclusive(&self) {
+        // SAFETY: The caller ensures an exclusive lock is held in the current context.
+        unsafe { self.raw.unlock_exclusive() }
+    }
+
+    fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+}
+
+impl <R: Debug> Debug for Sorted<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sorted")
+            .field("raw", &self.raw)
+            .field("key", &self.sort_key())
+            .finish()
+    }
+}
+
+impl <'l, R: RawMutex, T: ?Sized> SortableLock for &'l Mutex<Sorted<R>, T> {
+    type Guard = MutexGuard<'l, Sorted<R>, T>;
+
+    fn sort_key(&self) -> SortKey {
+        // SAFETY: The raw lock is only used to read its sort key.
+        unsafe { self.raw() }.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock()
+    }
+}
+
+/// A read guard for a `lock_api::RwLock` using a `Sorted` raw lock.
+pub struct SortedReadGuard<'l, R: RawRwLock, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l RwLock<Sorted<R>, T>,
+}
+
+impl <'l, R: RawRwLock, T: ?Sized> SortableLock for SortedReadGuard<'l, R, T> {
+    type Guard = RwLockReadGuard<'l, Sorted<R>, T>;
+
+    fn sort_key(&self) -> SortKey {
+        // SAFETY: The raw lock is only used to read its sort key.
+        unsafe { self.lock.raw() }.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.read()
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A write guard for a `lock_api::RwLock` using a `Sorted` raw lock.
+pub struct SortedWriteGuard<'l, R: RawRwLock, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l RwLock<Sorted<R>, T>,
+}
+
+impl <'l, R: RawRwLock, T: ?Sized> SortableLock for SortedWriteGuard<'l, R, T> {
+    type Guard = RwLockWriteGuard<'l, Sorted<R>, T>;
+
+    fn sort_key(&self) -> SortKey {
+        // SAFETY: The raw lock is only used to read its sort key.
+        unsafe { self.lock.raw() }.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use lock_api::{Mutex, MutexGuard, RwLock};
+
+    use crate::{LockGroup, RawSortMutex, Sorted};
+
+    #[test]
+    fn test_mapped_guard() {
+        let lock: Mutex<RawSortMutex, (i32, i32)> = Mutex::new((1, 2));
+
+        let mut second = MutexGuard::map((&lock).lock_all(), |(_, second)| second);
+        *second += 1;
+        drop(second);
+
+        assert_eq!((1, 3), *lock.lock());
+    }
+
+    #[test]
+    fn test_raw_threads() {
+        let lock1: Arc<Mutex<RawSortMutex, i32>> = Arc::new(Mutex::new(0));
+        let lock2: Arc<Mutex<RawSortMutex, i32>> = Arc::new(Mutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let lock1 = lock1.clone();
+            let lock2 = lock2.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let (mut guard1, mut guard2) = if i % 2 == 0 {
+                        (&*lock1, &*lock2).lock_all()
+                    } else {
+                        let (guard2, guard1) = (&*lock2, &*lock1).lock_all();
+                        (guard1, guard2)
+                    };
+
+                    *guard1 += 1;
+                    *guard2 += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!((400, 400), (*lock1.lock(), *lock2.lock()));
+    }
+
+    #[test]
+    fn test_sorted_rwlock() {
+        let lock: RwLock<Sorted<parking_lot::RawRwLock>, i32> = RwLock::new(1);
+        let other: Mutex<Sorted<parking_lot::RawMutex>, i32> = Mutex::new(2);
+
+        let (guard1, guard2, guard3) = (Sorted::read(&lock), Sorted::read(&lock), &other).lock_all();
+        assert_eq!(4, *guard1 + *guard2 + *guard3);
+        drop((guard1, guard2, guard3));
+
+        *Sorted::write(&lock).lock_all() += 1;
+        assert_eq!(2, *lock.read());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sorted_conflicting_requests() {
+        let lock: RwLock<Sorted<parking_lot::RawRwLock>, i32> = RwLock::new(1);
+
+        let _guards = (Sorted::read(&lock), Sorted::write(&lock)).lock_all();
+    }
+}
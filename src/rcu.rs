@@ -0,0 +1,306 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Formatter}, ops::Deref, sync::atomic::{AtomicUsize, Ordering}};
+
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+#[cfg(not(feature = "std"))]
+use spin::{Mutex, MutexGuard};
+
+use alloc::sync::Arc;
+
+use crate::{SortKey, SortableLock, key::LazySortKey};
+
+/// A sortable read-copy-update cell for values that are read far more often than written.
+///
+/// Reading takes a snapshot of the current value without taking any lock, so readers never
+/// block and never take part in lock ordering. Publishing a new value goes through an internal
+/// lock that can be sorted with other locks through the use of `lock_all`.
+/// ```
+/// use sortlock::{SortMutex, SortRcu, SortRcuUpdateLocked, LockGroup};
+///
+/// let config = SortRcu::new(String::from("old"));
+/// let lock = SortMutex::new(1);
+///
+/// let snapshot = config.read();
+///
+/// let (mut update, guard) = (config.update(), lock.lock()).lock_all();
+/// SortRcuUpdateLocked::publish(&mut update, format!("new {}", *guard));
+/// drop(update);
+///
+/// // Existing snapshots are unaffected by updates.
+/// assert_eq!("old", *snapshot);
+/// assert_eq!("new 1", *config.read());
+/// ```
+///
+/// The previous value is kept alive until the next update, so a snapshot may keep a value alive
+/// for longer than expected.
+pub struct SortRcu<T> {
+    /// The internal mutex held while updating.
+    mutex: Mutex<()>,
+    /// The index of the slot holding the current value.
+    current: AtomicUsize,
+    /// The number of readers currently taking a snapshot from each slot.
+    readers: [AtomicUsize; 2],
+    /// The current and previous values.
+    slots: [UnsafeCell<Arc<T>>; 2],
+    /// The sort key for this lock.
+    key: LazySortKey,
+}
+
+unsafe impl <T: Send + Sync> Send for SortRcu<T> {}
+unsafe impl <T: Send + Sync> Sync for SortRcu<T> {}
+
+impl <T> SortRcu<T> {
+    /// Creates a new `SortRcu`.
+    ///
+    /// - `value` - The initial value.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        let value = Arc::new(value);
+
+        Self {
+            mutex: Mutex::new(()),
+            current: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            slots: [UnsafeCell::new(value.clone()), UnsafeCell::new(value)],
+            key: LazySortKey::new(SortKey::new()),
+        }
+    }
+
+    /// Takes a snapshot of the current value without blocking.
+    ///
+    /// The snapshot is not affected by later updates.
+    pub fn read(&self) -> Arc<T> {
+        loop {
+            let index = self.current.load(Ordering::SeqCst);
+            self.readers[index].fetch_add(1, Ordering::SeqCst);
+
+            // An updater only writes to a slot that is not current once it has no readers, so the
+            // slot can be read if it is still current after registering as a reader.
+            if self.current.load(Ordering::SeqCst) == index {
+                // SAFETY: The slot is not written while this reader is registered.
+                let value = unsafe { (*self.slots[index].get()).clone() };
+                self.readers[index].fetch_sub(1, Ordering::Release);
+
+                return value;
+            }
+
+            self.readers[index].fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    /// Requests to lock this cell for updating.
+    pub fn update(&self) -> SortRcuUpdateGuard<'_, T> {
+        SortRcuUpdateGuard {
+            rcu: self
+        }
+    }
+
+    /// Attempts to lock this cell for updating without blocking.
+    ///
+    /// As only a single lock is locked no sorting is required.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn try_update(&self) -> Option<SortRcuUpdateLocked<'_, T>> {
+        self.try_lock_raw()
+            .map(|guard| SortRcuUpdateLocked {
+                _guard: guard,
+                rcu: self,
+            })
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Replaces the current value.
+    ///
+    /// The internal mutex must be held before calling this.
+    ///
+    /// - `value` - The new value.
+    fn publish_raw(&self, value: Arc<T>) {
+        let next = 1 - self.current.load(Ordering::Relaxed);
+
+        while self.readers[next].load(Ordering::SeqCst) != 0 {
+            #[cfg(feature = "std")]
+            std::thread::yield_now();
+            #[cfg(not(feature = "std"))]
+            core::hint::spin_loop();
+        }
+
+        // SAFETY: The mutex is held so no other updater exists and the slot is not current and
+        // has no readers.
+        unsafe { *self.slots[next].get() = value };
+
+        self.current.store(next, Ordering::SeqCst);
+    }
+
+    /// Locks the internal mutex.
+    #[cfg(feature = "std")]
+    fn lock_raw(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+            .expect("Failed to lock mutex: mutex is poisoned.")
+    }
+
+    /// Locks the internal mutex.
+    #[cfg(not(feature = "std"))]
+    fn lock_raw(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+    }
+
+    /// Attempts to lock the internal mutex without blocking.
+    #[cfg(feature = "std")]
+    fn try_lock_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        match self.mutex.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(_)) => panic!("Failed to lock mutex: mutex is poisoned."),
+        }
+    }
+
+    /// Attempts to lock the internal mutex without blocking.
+    #[cfg(not(feature = "std"))]
+    fn try_lock_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        self.mutex.try_lock()
+    }
+}
+
+impl <T: Debug> Debug for SortRcu<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.read().fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortRcu<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortRcu<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// An update guard for a `SortRcu`.
+pub struct SortRcuUpdateGuard<'l, T> {
+    /// The cell this guard references.
+    rcu: &'l SortRcu<T>,
+}
+
+impl <'l, T> SortableLock for SortRcuUpdateGuard<'l, T> {
+    type Guard = SortRcuUpdateLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.rcu.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortRcuUpdateLocked {
+            _guard: self.rcu.lock_raw(),
+            rcu: self.rcu,
+        }
+    }
+}
+
+/// An acquired update lock on a `SortRcu`.
+///
+/// This dereferences to the current value. Readers are not blocked while this guard is held.
+pub struct SortRcuUpdateLocked<'l, T> {
+    /// The guard for the internal mutex.
+    _guard: MutexGuard<'l, ()>,
+    /// The cell this guard references.
+    rcu: &'l SortRcu<T>,
+}
+
+impl <T> SortRcuUpdateLocked<'_, T> {
+    /// Publishes a new value.
+    ///
+    /// Readers see the new value as soon as this returns. Existing snapshots keep the old value.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    ///
+    /// - `value` - The new value.
+    pub fn publish(this: &mut Self, value: T) {
+        this.rcu.publish_raw(Arc::new(value));
+    }
+
+    /// Gets a snapshot of the current value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn snapshot(this: &Self) -> Arc<T> {
+        this.current().clone()
+    }
+
+    /// Gets the current value.
+    fn current(&self) -> &Arc<T> {
+        let index = self.rcu.current.load(Ordering::Relaxed);
+
+        // SAFETY: Only the holder of the mutex writes to the slots and it only writes through
+        // `publish`, which requires a mutable reference.
+        unsafe { &*self.rcu.slots[index].get() }
+    }
+}
+
+impl <T> Deref for SortRcuUpdateLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.current()
+    }
+}
+
+impl <T: Debug> Debug for SortRcuUpdateLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortRcu, SortRcuUpdateLocked};
+
+    #[test]
+    fn test_publish() {
+        let rcu = SortRcu::new(1);
+        let snapshot = rcu.read();
+
+        let mut guard = rcu.update().lock_all();
+        SortRcuUpdateLocked::publish(&mut guard, 2);
+        assert_eq!(2, *guard);
+        assert!(rcu.try_update().is_none());
+        SortRcuUpdateLocked::publish(&mut guard, 3);
+        drop(guard);
+
+        assert_eq!(1, *snapshot);
+        assert_eq!(3, *rcu.read());
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let rcu = Arc::new(SortRcu::new((0u64, 0u64)));
+        let writer_rcu = rcu.clone();
+
+        let writer = thread::spawn(move || {
+            for i in 1..=1000 {
+                let mut guard = writer_rcu.update().lock_all();
+                SortRcuUpdateLocked::publish(&mut guard, (i, i));
+            }
+        });
+
+        let mut last = 0;
+        for _ in 0..1000 {
+            let snapshot = rcu.read();
+            assert_eq!(snapshot.0, snapshot.1);
+            assert!(snapshot.0 >= last);
+            last = snapshot.0;
+        }
+
+        writer.join().unwrap();
+        assert_eq!((1000, 1000), *rcu.read());
+    }
+}
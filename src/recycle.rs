@@ -0,0 +1,198 @@
+use core::{fmt::{self, Debug, Display, Formatter}, mem::ManuallyDrop, ops::Deref};
+
+use crate::{MutexBackend, SortKey, SortMutex, SortRwLock};
+
+/// A lock whose sort key is reused by another lock once it is dropped.
+///
+/// Every new key is larger than the last, so a program that creates and drops many short-lived
+/// locks would otherwise keep creating larger keys. A `Recycled` lock takes its key from a list of
+/// keys returned by dropped `Recycled` locks when possible, and returns its key to that list when
+/// it is dropped.
+/// ```
+/// use sortlock::{Recycled, SortMutex, LockGroup};
+///
+/// let lock = Recycled::new(|key| SortMutex::with_key(1, key));
+/// let key = lock.sort_key();
+/// assert_eq!(1, *lock.lock().lock_all());
+/// drop(lock);
+///
+/// let lock = Recycled::new(|key| SortMutex::with_key(2, key));
+/// assert_eq!(key, lock.sort_key());
+/// ```
+///
+/// Recycled keys are not created in any particular order, so the order of recycled locks is
+/// unrelated to when they were created.
+///
+/// The wrapper only gives shared access to the lock, as replacing the lock would recycle the key
+/// while the old lock is still in use. The value of a recycled `SortMutex` or `SortRwLock` can be
+/// accessed mutably with `get_mut`.
+pub struct Recycled<L> {
+    /// The lock.
+    lock: ManuallyDrop<L>,
+    /// The sort key given to the lock.
+    key: SortKey,
+}
+
+impl <L> Recycled<L> {
+    /// Creates a new lock with a recycled sort key.
+    ///
+    /// A key from a dropped `Recycled` lock is used if there is one, and a new key is created
+    /// otherwise.
+    ///
+    /// - `f` - Creates the lock with the given key, such as with `SortMutex::with_key`. The lock
+    ///   must use this key.
+    #[track_caller]
+    pub fn new<F: FnOnce(SortKey) -> L>(f: F) -> Self {
+        let key = match SortKey::take_free(sortlock_keys::NORMAL_START) {
+            Some(key) => key,
+            None => SortKey::new(),
+        };
+
+        Self {
+            lock: ManuallyDrop::new(f(key)),
+            key,
+        }
+    }
+
+    /// Gets the sort key given to the lock.
+    ///
+    /// This is an associated function as a method could conflict with a method on `L`.
+    pub fn key(this: &Self) -> SortKey {
+        this.key
+    }
+
+    /// Consumes this wrapper and returns the lock.
+    ///
+    /// The key is not recycled as it is still used by the returned lock.
+    pub fn into_inner(this: Self) -> L {
+        let mut this = ManuallyDrop::new(this);
+
+        // SAFETY: The wrapper is never dropped so the lock is only taken once.
+        unsafe { ManuallyDrop::take(&mut this.lock) }
+    }
+}
+
+impl <L> Deref for Recycled<L> {
+    type Target = L;
+
+    fn deref(&self) -> &L {
+        &self.lock
+    }
+}
+
+impl <L: RecycledValue> Recycled<L> {
+    /// Gets a mutable reference to the value of the lock.
+    ///
+    /// As this requires mutable access to the lock no locking is required. The lock itself cannot
+    /// be accessed mutably as replacing it would recycle its key while it is still in use.
+    /// This is an associated function as a method could conflict with a method on `L`.
+    /// ```
+    /// use sortlock::{Recycled, SortMutex, LockGroup};
+    ///
+    /// let mut lock = Recycled::new(|key| SortMutex::with_key(1, key));
+    /// *Recycled::get_mut(&mut lock) += 1;
+    /// assert_eq!(2, *lock.lock().lock_all());
+    /// ```
+    pub fn get_mut(this: &mut Self) -> &mut L::Value {
+        this.lock.value_mut()
+    }
+}
+
+mod private {
+    /// Prevents `RecycledValue` from being implemented outside this crate.
+    pub trait Sealed {}
+}
+
+/// A lock whose value can be accessed mutably through `Recycled::get_mut`.
+///
+/// This is implemented for `SortMutex` and `SortRwLock`.
+pub trait RecycledValue: private::Sealed {
+    /// The type of the value protected by the lock.
+    type Value: ?Sized;
+
+    /// Gets a mutable reference to the value without giving access to the lock itself.
+    fn value_mut(&mut self) -> &mut Self::Value;
+}
+
+impl <T, B: MutexBackend> private::Sealed for SortMutex<T, B> {}
+
+impl <T, B: MutexBackend> RecycledValue for SortMutex<T, B> {
+    type Value = T;
+
+    fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The lock is borrowed mutably so nothing else can access the value.
+        unsafe { &mut *self.data_ptr() }
+    }
+}
+
+impl <T> private::Sealed for SortRwLock<T> {}
+
+impl <T> RecycledValue for SortRwLock<T> {
+    type Value = T;
+
+    fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The lock is borrowed mutably so nothing else can access the value.
+        unsafe { &mut *self.data_ptr() }
+    }
+}
+
+impl <L> Drop for Recycled<L> {
+    fn drop(&mut self) {
+        // SAFETY: The lock is never used again.
+        unsafe { ManuallyDrop::drop(&mut self.lock) };
+
+        self.key.free();
+    }
+}
+
+impl <L: Debug> Debug for Recycled<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <L: Display> Display for Recycled<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{LockGroup, Recycled, SortMutex, SortRwLock};
+
+    #[test]
+    fn test_recycled_keys() {
+        let locks: Vec<_> = (0..4).map(|i| Recycled::new(|key| SortMutex::with_key(i, key))).collect();
+        let keys: HashSet<_> = locks.iter().map(Recycled::key).collect();
+        assert_eq!(4, keys.len());
+
+        let (guard1, guard3) = (locks[1].lock(), locks[3].lock()).lock_all();
+        assert_eq!(4, *guard1 + *guard3);
+        drop((guard1, guard3));
+
+        let lock = Recycled::into_inner(Recycled::new(|key| SortMutex::with_key(4, key)));
+        assert!(!keys.contains(&lock.sort_key()));
+        drop(locks);
+
+        // Other tests may take keys from the list at the same time so only check that new locks
+        // never share a key.
+        let locks: Vec<_> = (0..4).map(|i| Recycled::new(|key| SortMutex::with_key(i, key))).collect();
+        let keys: HashSet<_> = locks.iter().map(Recycled::key).collect();
+        assert_eq!(4, keys.len());
+        assert!(!keys.contains(&lock.sort_key()));
+    }
+
+    #[test]
+    fn test_recycled_get_mut() {
+        let mut lock = Recycled::new(|key| SortMutex::with_key(1, key));
+        *Recycled::get_mut(&mut lock) += 1;
+        assert_eq!(2, *lock.lock().lock_all());
+
+        let mut lock = Recycled::new(|key| SortRwLock::with_key(1, key));
+        *Recycled::get_mut(&mut lock) += 1;
+        assert_eq!(2, *lock.read().lock_all());
+    }
+}
@@ -0,0 +1,358 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, marker::PhantomData, ops::Deref};
+
+use std::{sync::{Condvar, Mutex, MutexGuard, PoisonError}, thread::{self, ThreadId}};
+
+use crate::{LockAccess, LockGroup, SortKey, SortableLock, key::LazySortKey};
+
+/// A sortable lock that can be locked multiple times by the same thread.
+/// This is a sortable version of a reentrant mutex.
+///
+/// As the same value can be locked multiple times by one thread the guard only gives shared
+/// access to the value. A `Cell` or `RefCell` can be used for mutation.
+/// ```
+/// use std::cell::Cell;
+/// use sortlock::{SortReentrantMutex, LockGroup};
+///
+/// let lock = SortReentrantMutex::new(Cell::new(1));
+///
+/// let guard1 = lock.lock().lock_all();
+/// let guard2 = lock.lock().lock_all();
+/// guard2.set(guard1.get() + 1);
+/// assert_eq!(2, guard1.get());
+/// ```
+///
+/// Multiple requests for the same reentrant mutex can be locked in a single group:
+/// ```
+/// use std::cell::Cell;
+/// use sortlock::{SortMutex, SortReentrantMutex, LockGroup};
+///
+/// let lock1 = SortReentrantMutex::new(Cell::new(1));
+/// let lock2 = SortMutex::new(2);
+///
+/// let (guard1, guard2, guard3) = (lock1.lock(), lock2.lock(), lock1.lock()).lock_all();
+/// assert_eq!(4, guard1.get() + *guard2 + guard3.get());
+/// ```
+///
+/// Unlike `SortMutex` this lock is never poisoned.
+pub struct SortReentrantMutex<T: ?Sized> {
+    /// The thread holding the lock and the number of times it has been locked.
+    state: Mutex<ReentrantState>,
+    /// Notified when the lock is released.
+    released: Condvar,
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for SortReentrantMutex<T> {}
+unsafe impl <T: ?Sized + Send> Sync for SortReentrantMutex<T> {}
+
+/// The owner of a `SortReentrantMutex`.
+struct ReentrantState {
+    /// The thread holding the lock.
+    owner: Option<ThreadId>,
+    /// The number of times the owner has locked the lock.
+    count: usize,
+}
+
+impl <T> SortReentrantMutex<T> {
+    /// Creates a new `SortReentrantMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Mutex::new(ReentrantState::UNLOCKED),
+            released: Condvar::new(),
+            key: LazySortKey::new(SortKey::new()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortReentrantMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    /// ```
+    /// use sortlock::{SortReentrantMutex, LockGroup};
+    ///
+    /// static LOCK: SortReentrantMutex<u32> = SortReentrantMutex::const_new(0);
+    ///
+    /// assert_eq!(0, *LOCK.lock().lock_all());
+    /// ```
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            state: Mutex::new(ReentrantState::UNLOCKED),
+            released: Condvar::new(),
+            key: LazySortKey::unassigned(),
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl <T: ?Sized> SortReentrantMutex<T> {
+    /// Requests to lock this lock.
+    ///
+    /// If the current thread already holds this lock locking succeeds without blocking.
+    pub fn lock(&self) -> SortReentrantMutexGuard<'_, T> {
+        SortReentrantMutexGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock without blocking.
+    ///
+    /// This succeeds if the lock is unlocked or already held by the current thread. As only a
+    /// single lock is locked no sorting is required.
+    /// ```
+    /// use std::thread;
+    /// use sortlock::{SortReentrantMutex, LockGroup};
+    ///
+    /// let lock = SortReentrantMutex::new(1);
+    ///
+    /// let guard = lock.lock().lock_all();
+    /// assert!(lock.try_lock().is_some());
+    ///
+    /// thread::scope(|scope| {
+    ///     scope.spawn(|| assert!(lock.try_lock().is_none()));
+    /// });
+    /// ```
+    pub fn try_lock(&self) -> Option<SortReentrantMutexLocked<'_, T>> {
+        let current = thread::current().id();
+        let mut state = self.state();
+
+        match state.owner {
+            Some(owner) if owner != current => None,
+            _ => {
+                state.owner = Some(current);
+                state.count += 1;
+
+                Some(SortReentrantMutexLocked::new(self))
+            },
+        }
+    }
+
+    /// Returns `true` if the current thread holds this lock.
+    pub fn is_owned_by_current_thread(&self) -> bool {
+        self.state().owner == Some(thread::current().id())
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Gets a raw pointer to the value.
+    ///
+    /// The pointer is valid for as long as the lock is. Dereferencing it is only safe while the
+    /// lock is held by the current thread and the value is not mutably aliased.
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Locks this lock, waiting for any other thread to release it.
+    fn lock_raw(&self) {
+        let current = thread::current().id();
+
+        let mut state = self.released.wait_while(self.state(), |state| {
+            state.owner.is_some_and(|owner| owner != current)
+        }).unwrap_or_else(PoisonError::into_inner);
+
+        state.owner = Some(current);
+        state.count += 1;
+    }
+
+    /// Releases one lock held by the current thread.
+    fn unlock_raw(&self) {
+        let mut state = self.state();
+
+        state.count -= 1;
+
+        if state.count == 0 {
+            state.owner = None;
+            drop(state);
+
+            self.released.notify_one();
+        }
+    }
+
+    /// Locks the internal state.
+    ///
+    /// The state is never left inconsistent by a panic so poisoning is ignored.
+    fn state(&self) -> MutexGuard<'_, ReentrantState> {
+        self.state.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ReentrantState {
+    /// The state of a lock that is not held by any thread.
+    const UNLOCKED: Self = Self {
+        owner: None,
+        count: 0,
+    };
+}
+
+impl <T: ?Sized + Debug> Debug for SortReentrantMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortReentrantMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortReentrantMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortReentrantMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A guard for a `SortReentrantMutex`.
+pub struct SortReentrantMutexGuard<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortReentrantMutex<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortReentrantMutexGuard<'l, T> {
+    type Guard = SortReentrantMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.lock_raw();
+
+        SortReentrantMutexLocked::new(self.lock)
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// An acquired lock on a `SortReentrantMutex`.
+///
+/// The lock is released when this guard is dropped. The guard must be dropped on the thread that
+/// created it.
+pub struct SortReentrantMutexLocked<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortReentrantMutex<T>,
+    /// Prevents the guard from being sent to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortReentrantMutexLocked<'_, T> {}
+
+impl <'l, T: ?Sized> SortReentrantMutexLocked<'l, T> {
+    /// Creates a new `SortReentrantMutexLocked` for a lock held by the current thread.
+    ///
+    /// - `lock` - The lock that was acquired.
+    fn new(lock: &'l SortReentrantMutex<T>) -> Self {
+        Self {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for SortReentrantMutexLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The lock is held by the current thread and only shared references are given out.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> Drop for SortReentrantMutexLocked<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock_raw();
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortReentrantMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortReentrantMutexLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Arc, thread};
+
+    use crate::{LockGroup, SortMutex, SortReentrantMutex};
+
+    #[test]
+    fn test_reentrant() {
+        let lock = SortReentrantMutex::new(Cell::new(1));
+
+        let guard1 = lock.lock().lock_all();
+        let guard2 = lock.lock().lock_all();
+        guard2.set(2);
+        drop(guard1);
+
+        assert!(lock.is_owned_by_current_thread());
+        assert_eq!(2, guard2.get());
+        drop(guard2);
+
+        assert!(!lock.is_owned_by_current_thread());
+    }
+
+    #[test]
+    fn test_reentrant_group() {
+        let lock1 = SortReentrantMutex::new(1);
+        let lock2 = SortMutex::new(2);
+
+        let (guard1, guard2, guard3) = (lock1.lock(), lock2.lock(), lock1.lock()).lock_all();
+        assert_eq!(4, *guard1 + *guard2 + *guard3);
+    }
+
+    #[test]
+    fn test_reentrant_threads() {
+        let lock = Arc::new(SortReentrantMutex::new(Cell::new(0)));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let guard1 = lock.lock().lock_all();
+                    let guard2 = lock.lock().lock_all();
+                    guard2.set(guard1.get() + 1);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, lock.lock().lock_all().get());
+    }
+}
@@ -0,0 +1,138 @@
+use core::any::Any;
+
+use std::sync::{Mutex, PoisonError};
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+
+use crate::SortMutex;
+
+/// The registered locks by name.
+static REGISTRY: Mutex<BTreeMap<String, Arc<dyn Any + Send + Sync>>> = Mutex::new(BTreeMap::new());
+
+/// A process-wide registry of locks shared by name.
+///
+/// Independent parts of a program can share a lock by name without passing references between
+/// them. A lock keeps the same sort key for as long as the program runs, so locks from the
+/// registry are sorted consistently with every other lock.
+/// ```
+/// use sortlock::{LockGroup, LockRegistry};
+///
+/// fn record_hit() {
+///     *LockRegistry::get_or_create::<u32>("hits").lock().lock_all() += 1;
+/// }
+///
+/// record_hit();
+/// record_hit();
+///
+/// let hits = LockRegistry::get::<u32>("hits").unwrap();
+/// assert_eq!(2, *hits.lock().lock_all());
+/// ```
+///
+/// There is a single registry, so this type cannot be created.
+pub enum LockRegistry {}
+
+impl LockRegistry {
+    /// Gets the lock registered under a name.
+    ///
+    /// Returns `None` if no lock is registered under the name or if it holds a different type.
+    ///
+    /// - `name` - The name of the lock.
+    pub fn get<T: Send + 'static>(name: &str) -> Option<Arc<SortMutex<T>>> {
+        REGISTRY.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+            .and_then(|lock| lock.downcast().ok())
+    }
+
+    /// Gets the lock registered under a name, registering a lock holding the default value if
+    /// there is none.
+    ///
+    /// The lock is recorded as created by the caller that first requested it.
+    ///
+    /// - `name` - The name of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if a lock holding a different type is registered under the name.
+    #[track_caller]
+    pub fn get_or_create<T: Default + Send + 'static>(name: &str) -> Arc<SortMutex<T>> {
+        Self::get_or_create_with(name, T::default)
+    }
+
+    /// Gets the lock registered under a name, registering a lock holding the result of a closure
+    /// if there is none.
+    ///
+    /// The closure is called while the registry is locked, so it must not use the registry.
+    /// ```
+    /// use sortlock::{LockGroup, LockRegistry};
+    ///
+    /// let lock = LockRegistry::get_or_create_with("greeting", || String::from("hello"));
+    /// assert_eq!("hello", *lock.lock().lock_all());
+    /// ```
+    ///
+    /// - `name` - The name of the lock.
+    /// - `f` - The closure that creates the value of a new lock.
+    ///
+    /// # Panicking
+    /// This method will panic if a lock holding a different type is registered under the name.
+    #[track_caller]
+    pub fn get_or_create_with<T: Send + 'static, F: FnOnce() -> T>(name: &str, f: F) -> Arc<SortMutex<T>> {
+        let mut registry = REGISTRY.lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let lock = match registry.get(name) {
+            Some(lock) => lock.clone(),
+            None => {
+                let lock: Arc<dyn Any + Send + Sync> = Arc::new(SortMutex::new(f()));
+                registry.insert(String::from(name), lock.clone());
+
+                lock
+            },
+        };
+
+        lock.downcast()
+            .unwrap_or_else(|_| panic!("Failed to get lock from registry: the lock named {name:?} holds a different type."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, LockRegistry};
+
+    #[test]
+    fn test_shared_by_name() {
+        let lock1 = LockRegistry::get_or_create::<i32>("test_shared_by_name");
+        let lock2 = LockRegistry::get_or_create::<i32>("test_shared_by_name");
+
+        assert!(Arc::ptr_eq(&lock1, &lock2));
+        assert_eq!(lock1.sort_key(), lock2.sort_key());
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        LockRegistry::get_or_create::<i32>("test_wrong_type");
+
+        assert!(LockRegistry::get::<u32>("test_wrong_type").is_none());
+        assert!(LockRegistry::get::<i32>("test_missing").is_none());
+        assert!(thread::spawn(|| LockRegistry::get_or_create::<u32>("test_wrong_type")).join().is_err());
+    }
+
+    #[test]
+    fn test_registry_threads() {
+        let handles: Vec<_> = (0..4).map(|_| {
+            thread::spawn(|| {
+                for _ in 0..100 {
+                    *LockRegistry::get_or_create::<u32>("test_registry_threads").lock().lock_all() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *LockRegistry::get::<u32>("test_registry_threads").unwrap().lock().lock_all());
+    }
+}
@@ -1,12 +1,18 @@
-use core::fmt::{self, Debug, Display, Formatter};
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, marker::PhantomData, mem, ops::{Deref, DerefMut}, ptr::NonNull, sync::atomic::{AtomicUsize, Ordering}};
 
 #[cfg(feature = "std")]
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Condvar, LockResult, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
 #[cfg(not(feature = "std"))]
-use spin::{RwLock, RwLockWriteGuard, RwLockReadGuard};
+use spin::{Mutex, MutexGuard, RwLock, RwLockWriteGuard, RwLockReadGuard};
 
+#[cfg(any(feature = "alloc", feature = "diagnostics"))]
+use core::panic::Location;
 
-use crate::{LockGroup, SortKey, SortableLock};
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+
+use crate::{LockAccess, LockGroup, SortKey, SortableLock, finally, key::LazySortKey};
 
 /// A sortable lock that allows either exclusive write access or shared read access. 
 /// This is a sortable version of rust's `RwLock` type.
@@ -54,30 +60,162 @@ use crate::{LockGroup, SortKey, SortableLock};
 /// *guard1 += 1;
 /// println!("{}", *guard2);
 /// ```
-pub struct SortRwLock<T> {
+///
+/// A read lock that may later need to write can be taken with `upgradable_read`. Only one
+/// upgradable read or write lock can be held at a time so the upgrade cannot be raced by another
+/// writer.
+/// ```
+/// use sortlock::{SortRwLock, LockGroup};
+///
+/// let lock = SortRwLock::new(1);
+///
+/// let guard = lock.upgradable_read().lock_all();
+/// if *guard == 1 {
+///     let mut guard = guard.upgrade();
+///     *guard += 1;
+/// }
+/// ```
+pub struct SortRwLock<T: ?Sized> {
     /// The internal lock.
-    mutex: RwLock<T>,
+    mutex: RwLock<()>,
+    /// A lock held by writers and upgradable readers.
+    upgrade: Mutex<()>,
+    /// The number of recursive readers currently holding the lock.
+    recursive: AtomicUsize,
+    /// A lock held by a writer while it checks for recursive readers.
+    #[cfg(feature = "std")]
+    recursive_lock: Mutex<()>,
+    /// Notified when the last recursive reader releases the lock.
+    #[cfg(feature = "std")]
+    recursive_released: Condvar,
     /// The sort key for this lock.
-    key: SortKey,
+    key: LazySortKey,
+    /// Where this lock was created.
+    #[cfg(feature = "diagnostics")]
+    created: &'static Location<'static>,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
 }
 
+unsafe impl <T: ?Sized + Send> Send for SortRwLock<T> {}
+unsafe impl <T: ?Sized + Send + Sync> Sync for SortRwLock<T> {}
+
 impl <T> SortRwLock<T> {
     /// Creates a new `SortRwLock`.
     ///
     /// - `value` - The value of the lock.
+    #[track_caller]
     pub fn new(value: T) -> Self {
+        Self::with_key(value, SortKey::new())
+    }
+
+    /// Creates a new `SortRwLock` with a given sort key.
+    ///
+    /// This allows the order of the lock to be controlled with a key such as one from
+    /// `SortKey::from_value`. The key must not be used by any other lock.
+    ///
+    /// - `value` - The value of the lock.
+    /// - `key` - The sort key of the lock.
+    #[track_caller]
+    pub fn with_key(value: T, key: SortKey) -> Self {
+        Self {
+            mutex: RwLock::new(()),
+            upgrade: Mutex::new(()),
+            recursive: AtomicUsize::new(0),
+            #[cfg(feature = "std")]
+            recursive_lock: Mutex::new(()),
+            #[cfg(feature = "std")]
+            recursive_released: Condvar::new(),
+            key: LazySortKey::new(key),
+            #[cfg(feature = "diagnostics")]
+            created: Location::caller(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortRwLock` in an `Arc` with a sort key derived from its address.
+    ///
+    /// As the lock never moves while it is in the `Arc` its address identifies it, so no key has
+    /// to be taken from the global counter. This avoids contention on the counter when many locks
+    /// are created at once, and the key is reused once the allocation is freed.
+    ///
+    /// - `value` - The value of the lock.
+    ///
+    /// # Panicking
+    /// This method will panic if the lock is allocated at an address of `2^57` or above.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn new_arc(value: T) -> Arc<Self> {
+        let location = Location::caller();
+
+        Arc::new_cyclic(|lock| {
+            #[allow(unused_mut)]
+            let mut rwlock = Self::with_key(value, SortKey::from_address(lock.as_ptr() as usize, location));
+
+            #[cfg(feature = "diagnostics")]
+            {
+                rwlock.created = location;
+            }
+
+            rwlock
+        })
+    }
+
+    /// Creates a new `SortRwLock` in a `const` context.
+    ///
+    /// The sort key is assigned when the lock is first used rather than when it is created. This
+    /// allows the lock to be placed in a `static`.
+    /// ```
+    /// use sortlock::{SortRwLock, LockGroup};
+    ///
+    /// static LOCK: SortRwLock<u32> = SortRwLock::const_new(0);
+    ///
+    /// *LOCK.write().lock_all() += 1;
+    /// ```
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub const fn const_new(value: T) -> Self {
         Self {
-            mutex: RwLock::new(value),
-            key: SortKey::new()
+            mutex: RwLock::new(()),
+            upgrade: Mutex::new(()),
+            recursive: AtomicUsize::new(0),
+            #[cfg(feature = "std")]
+            recursive_lock: Mutex::new(()),
+            #[cfg(feature = "std")]
+            recursive_released: Condvar::new(),
+            key: LazySortKey::unassigned(),
+            #[cfg(feature = "diagnostics")]
+            created: Location::caller(),
+            data: UnsafeCell::new(value),
         }
     }
 
+    /// Gets a clone of the value of this lock.
+    ///
+    /// The lock is only held for reading while the value is cloned.
+    /// ```
+    /// use sortlock::SortRwLock;
+    ///
+    /// let lock = SortRwLock::new(vec![1, 2]);
+    ///
+    /// assert_eq!(vec![1, 2], lock.get_cloned());
+    /// ```
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn get_cloned(&self) -> T where T: Clone {
+        self.read().lock_all().clone()
+    }
+}
+
+impl <T: ?Sized> SortRwLock<T> {
     /// Requests to lock this lock for reading.
     /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
     ///
     /// # Panicking
     /// The guard will panic when locked if this lock becomes poisoned.
-    pub fn read(&self) -> SortReadGuard<T> {
+    pub fn read(&self) -> SortReadGuard<'_, T> {
         SortReadGuard {
             lock: self
         }
@@ -88,86 +226,1480 @@ impl <T> SortRwLock<T> {
     ///
     /// # Panicking
     /// The guard will panic when locked if this lock becomes poisoned.
-    pub fn write(&self) -> SortWriteGuard<T> {
+    pub fn write(&self) -> SortWriteGuard<'_, T> {
         SortWriteGuard {
             lock: self
         }
     }
+
+    /// Requests to lock this lock for reading with the option to upgrade to a write lock later.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Upgradable reads may be held alongside plain reads but exclude writers and other upgradable
+    /// reads.
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned.
+    pub fn upgradable_read(&self) -> SortUpgradableReadGuard<'_, T> {
+        SortUpgradableReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for reading in a way that allows a thread to lock it recursively.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Unlike `read`, locking is guaranteed to succeed without blocking if another recursive read
+    /// lock is held at the time, even if a writer is waiting. This prevents a thread that already
+    /// holds a recursive read lock from deadlocking when locking again. A writer waiting for
+    /// recursive readers to finish blocks, or spins without the `std` feature.
+    /// ```
+    /// use sortlock::{SortRwLock, LockGroup};
+    ///
+    /// let lock = SortRwLock::new(1);
+    ///
+    /// let guard1 = lock.read_recursive().lock_all();
+    /// let guard2 = lock.read_recursive().lock_all();
+    /// assert_eq!(*guard1, *guard2);
+    /// ```
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned.
+    pub fn read_recursive(&self) -> SortRecursiveReadGuard<'_, T> {
+        SortRecursiveReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for reading, reporting poisoning instead of panicking.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// When locked the guard produces a `LockResult` so poisoning can be handled for this lock
+    /// alone while the rest of a group is locked as normal.
+    /// ```
+    /// use sortlock::{SortMutex, SortRwLock, LockGroup};
+    ///
+    /// let lock1 = SortRwLock::new(1);
+    /// let lock2 = SortMutex::new(2);
+    ///
+    /// let (guard1, guard2) = (lock1.read_checked(), lock2.lock()).lock_all();
+    /// let guard1 = guard1.unwrap_or_else(|error| error.into_inner());
+    /// assert_eq!(3, *guard1 + *guard2);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_checked(&self) -> SortCheckedReadGuard<'_, T> {
+        SortCheckedReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for writing, reporting poisoning instead of panicking.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// When locked the guard produces a `LockResult` so poisoning can be handled for this lock
+    /// alone while the rest of a group is locked as normal.
+    #[cfg(feature = "std")]
+    pub fn write_checked(&self) -> SortCheckedWriteGuard<'_, T> {
+        SortCheckedWriteGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock for reading without blocking.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently locked for writing.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn try_read(&self) -> Option<SortReadLocked<'_, T>> {
+        self.try_read_raw()
+            .map(|guard| SortReadLocked::new(guard, self))
+    }
+
+    /// Attempts to lock this lock for writing without blocking.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// `None` is returned if the lock is currently locked for reading or writing.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn try_write(&self) -> Option<SortWriteLocked<'_, T>> {
+        let upgrade = self.try_upgrade_raw()?;
+        self.try_write_raw()
+            .map(|guard| SortWriteLocked::new(upgrade, guard, self))
+    }
+
+    /// Gets a raw pointer to the value protected by this lock.
+    ///
+    /// The lock is not acquired so the pointer must only be dereferenced while the lock is held,
+    /// or while no other references to the value can exist.
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Gets the sort key of this lock.
+    ///
+    /// This is the key used to order this lock when it is locked as part of a group. A lock
+    /// created with `const_new` is assigned its key on the first call to this method.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Gets where this lock was created.
+    ///
+    /// This is the code that called the constructor, which identifies the lock when only its key
+    /// or address is known.
+    #[cfg(feature = "diagnostics")]
+    pub fn created_at(&self) -> &'static Location<'static> {
+        self.created
+    }
+
+    /// Locks this lock for reading through an `Arc`, returning a guard that keeps the lock alive.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// The returned guard is not bound to a borrow of the lock so it can be stored in other
+    /// structures.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn read_owned(self: Arc<Self>) -> OwnedSortReadLocked<T> {
+        let guard = self.read_raw();
+        // SAFETY: The returned guard keeps the lock alive until after the internal guard is
+        // dropped.
+        let guard = unsafe { mem::transmute::<RwLockReadGuard<'_, ()>, RwLockReadGuard<'static, ()>>(guard) };
+
+        OwnedSortReadLocked {
+            _guard: guard,
+            lock: self,
+        }
+    }
+
+    /// Locks this lock for writing through an `Arc`, returning a guard that keeps the lock alive.
+    ///
+    /// As only a single lock is acquired no sorting is required so this bypasses `lock_all`.
+    /// The returned guard is not bound to a borrow of the lock so it can be stored in other
+    /// structures.
+    /// ```
+    /// use std::sync::Arc;
+    /// use sortlock::SortRwLock;
+    ///
+    /// let lock = Arc::new(SortRwLock::new(1));
+    ///
+    /// let mut guard = lock.clone().write_owned();
+    /// *guard += 1;
+    /// drop(guard);
+    ///
+    /// assert_eq!(2, *lock.read_owned());
+    /// ```
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn write_owned(self: Arc<Self>) -> OwnedSortWriteLocked<T> {
+        let upgrade = self.upgrade_raw();
+        let guard = self.write_raw();
+        // SAFETY: The returned guard keeps the lock alive until after the internal guards are
+        // dropped.
+        let (guard, upgrade) = unsafe {(
+            mem::transmute::<RwLockWriteGuard<'_, ()>, RwLockWriteGuard<'static, ()>>(guard),
+            mem::transmute::<MutexGuard<'_, ()>, MutexGuard<'static, ()>>(upgrade),
+        )};
+
+        OwnedSortWriteLocked {
+            guard,
+            upgrade,
+            lock: self,
+        }
+    }
+
+    /// Requests to lock this lock for reading through an `Arc`.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Once locked this produces an `OwnedSortReadLocked` so the locked group is not bound to a
+    /// borrow of any of its locks.
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn read_owned_request(self: Arc<Self>) -> OwnedSortReadGuard<T> {
+        OwnedSortReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for writing through an `Arc`.
+    /// This method returns a guard which can be used with `lock_all` to perform a sorted lock.
+    ///
+    /// Once locked this produces an `OwnedSortWriteLocked` so the locked group is not bound to a
+    /// borrow of any of its locks.
+    ///
+    /// # Panicking
+    /// The guard will panic when locked if this lock becomes poisoned.
+    #[cfg(feature = "alloc")]
+    pub fn write_owned_request(self: Arc<Self>) -> OwnedSortWriteGuard<T> {
+        OwnedSortWriteGuard {
+            lock: self
+        }
+    }
+
+    /// Panics as the internal lock is poisoned.
+    #[cfg(feature = "std")]
+    #[cold]
+    fn poisoned(&self) -> ! {
+        #[cfg(feature = "diagnostics")]
+        panic!("Failed to lock mutex: the lock created at {} is poisoned.", self.created);
+
+        #[cfg(not(feature = "diagnostics"))]
+        panic!("Failed to lock mutex.");
+    }
+
+    /// Locks the internal lock for reading.
+    #[cfg(feature = "std")]
+    fn read_raw(&self) -> RwLockReadGuard<'_, ()> {
+        self.mutex.read()
+            .unwrap_or_else(|_| self.poisoned())
+    }
+
+    /// Locks the internal lock for reading.
+    #[cfg(not(feature = "std"))]
+    fn read_raw(&self) -> RwLockReadGuard<'_, ()> {
+        self.mutex.read()
+    }
+
+    /// Registers a recursive reader, locking the internal lock only if there are no other
+    /// recursive readers.
+    fn read_recursive_raw(&self) {
+        let mut count = self.recursive.load(Ordering::Relaxed);
+
+        // Writers can only hold the lock while there are no recursive readers so joining existing
+        // readers never needs to wait.
+        while count > 0 {
+            match self.recursive.compare_exchange_weak(count, count + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => count = actual,
+            }
+        }
+
+        let guard = self.read_raw();
+        self.recursive.fetch_add(1, Ordering::Relaxed);
+        drop(guard);
+    }
+
+    /// Locks the internal lock for writing and waits for any recursive readers to finish.
+    ///
+    /// The upgrade lock must be held before calling this.
+    fn write_raw(&self) -> RwLockWriteGuard<'_, ()> {
+        let guard = self.write_internal();
+        self.wait_recursive();
+
+        guard
+    }
+
+    /// Locks the internal lock for reading without panicking if it is poisoned.
+    #[cfg(feature = "std")]
+    fn read_checked_raw(&self) -> LockResult<RwLockReadGuard<'_, ()>> {
+        self.mutex.read()
+    }
+
+    /// Locks the internal lock for writing without panicking if it is poisoned and waits for any
+    /// recursive readers to finish.
+    ///
+    /// The upgrade lock must be held before calling this.
+    #[cfg(feature = "std")]
+    fn write_checked_raw(&self) -> LockResult<RwLockWriteGuard<'_, ()>> {
+        let result = self.mutex.write();
+        self.wait_recursive();
+
+        result
+    }
+
+    /// Locks the internal lock for reading, even if it is poisoned.
+    #[cfg(feature = "std")]
+    fn read_ignoring_poison(&self) -> RwLockReadGuard<'_, ()> {
+        self.read_checked_raw()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the internal lock for reading, even if it is poisoned.
+    #[cfg(not(feature = "std"))]
+    fn read_ignoring_poison(&self) -> RwLockReadGuard<'_, ()> {
+        self.read_raw()
+    }
+
+    /// Locks the internal lock for writing, even if it is poisoned, and waits for any recursive
+    /// readers to finish.
+    ///
+    /// The upgrade lock must be held before calling this.
+    #[cfg(feature = "std")]
+    fn write_ignoring_poison(&self) -> RwLockWriteGuard<'_, ()> {
+        self.write_checked_raw()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the internal lock for writing, even if it is poisoned, and waits for any recursive
+    /// readers to finish.
+    ///
+    /// The upgrade lock must be held before calling this.
+    #[cfg(not(feature = "std"))]
+    fn write_ignoring_poison(&self) -> RwLockWriteGuard<'_, ()> {
+        self.write_raw()
+    }
+
+    /// Panics if the internal lock is poisoned.
+    fn check_poison(&self) {
+        #[cfg(feature = "std")]
+        if self.mutex.is_poisoned() {
+            self.poisoned();
+        }
+    }
+
+    /// Waits for all recursive readers to release the lock.
+    ///
+    /// The internal lock must be held for writing before calling this.
+    #[cfg(feature = "std")]
+    fn wait_recursive(&self) {
+        let mut guard = self.recursive_lock.lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        while self.recursive.load(Ordering::Acquire) != 0 {
+            guard = self.recursive_released.wait(guard)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Waits for all recursive readers to release the lock.
+    ///
+    /// Without the `std` feature there is nothing to block on so this spins until the last
+    /// recursive reader has released the lock.
+    ///
+    /// The internal lock must be held for writing before calling this.
+    #[cfg(not(feature = "std"))]
+    fn wait_recursive(&self) {
+        while self.recursive.load(Ordering::Acquire) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Releases a recursive read lock, waking a writer waiting for the last recursive reader.
+    fn release_recursive(&self) {
+        #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+        let last = self.recursive.fetch_sub(1, Ordering::Release) == 1;
+
+        // Taking the lock ensures a writer is either yet to check the count or already waiting.
+        #[cfg(feature = "std")]
+        if last {
+            drop(self.recursive_lock.lock());
+            self.recursive_released.notify_all();
+        }
+    }
+
+    /// Locks the internal lock for writing.
+    #[cfg(feature = "std")]
+    fn write_internal(&self) -> RwLockWriteGuard<'_, ()> {
+        self.mutex.write()
+            .unwrap_or_else(|_| self.poisoned())
+    }
+
+    /// Locks the internal lock for writing.
+    #[cfg(not(feature = "std"))]
+    fn write_internal(&self) -> RwLockWriteGuard<'_, ()> {
+        self.mutex.write()
+    }
+
+    /// Locks the upgrade lock.
+    ///
+    /// Poisoning is tracked by the internal `RwLock` so it is ignored here.
+    #[cfg(feature = "std")]
+    fn upgrade_raw(&self) -> MutexGuard<'_, ()> {
+        self.upgrade.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the upgrade lock.
+    #[cfg(not(feature = "std"))]
+    fn upgrade_raw(&self) -> MutexGuard<'_, ()> {
+        self.upgrade.lock()
+    }
+
+    /// Attempts to lock the internal lock for reading without blocking.
+    #[cfg(feature = "std")]
+    fn try_read_raw(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        match self.mutex.try_read() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(_)) => self.poisoned(),
+        }
+    }
+
+    /// Attempts to lock the internal lock for reading without blocking.
+    #[cfg(not(feature = "std"))]
+    fn try_read_raw(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        self.mutex.try_read()
+    }
+
+    /// Attempts to lock the internal lock for writing without blocking.
+    ///
+    /// This fails if there are any recursive readers.
+    fn try_write_raw(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        let guard = self.try_write_internal()?;
+
+        (self.recursive.load(Ordering::Acquire) == 0).then_some(guard)
+    }
+
+    /// Attempts to lock the internal lock for writing without blocking.
+    #[cfg(feature = "std")]
+    fn try_write_internal(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        match self.mutex.try_write() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(_)) => self.poisoned(),
+        }
+    }
+
+    /// Attempts to lock the internal lock for writing without blocking.
+    #[cfg(not(feature = "std"))]
+    fn try_write_internal(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        self.mutex.try_write()
+    }
+
+    /// Attempts to lock the upgrade lock without blocking.
+    #[cfg(feature = "std")]
+    fn try_upgrade_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        match self.upgrade.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(error)) => Some(error.into_inner()),
+        }
+    }
+
+    /// Attempts to lock the upgrade lock without blocking.
+    #[cfg(not(feature = "std"))]
+    fn try_upgrade_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        self.upgrade.try_lock()
+    }
 }
 
-impl <T: Debug> Debug for SortRwLock<T> {
+impl <T: ?Sized + Debug> Debug for SortRwLock<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.read().lock_all().fmt(f)
     }
 }
 
-impl <T: Display> Display for SortRwLock<T> {
+impl <T: ?Sized + Display> Display for SortRwLock<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.read().lock_all().fmt(f)
     }
 }
 
 impl <T: Default> Default for SortRwLock<T> {
+    #[track_caller]
     fn default() -> Self {
         Self::new(T::default())
     }
 }
 
-/// A read guard for a `SortRwLock`.
-pub struct SortReadGuard<'l, T> {
-    /// The lock this request references.
-    lock: &'l SortRwLock<T>,
+impl <T> From<T> for SortRwLock<T> {
+    #[track_caller]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Adopts the value of an existing `std` lock.
+///
+/// # Panicking
+/// This will panic if the lock is poisoned.
+#[cfg(feature = "std")]
+impl <T> From<RwLock<T>> for SortRwLock<T> {
+    #[track_caller]
+    fn from(lock: RwLock<T>) -> Self {
+        Self::new(lock.into_inner().expect("Failed to lock mutex."))
+    }
 }
 
-impl <'l, T> SortableLock for SortReadGuard<'l, T> {
-    type Guard = RwLockReadGuard<'l, T>;
+/// Allows a lock to be locked for writing as part of a group without calling `write` first.
+/// ```
+/// use sortlock::{SortRwLock, LockGroup};
+///
+/// let lock1 = SortRwLock::new(1);
+/// let lock2 = SortRwLock::new(2);
+///
+/// let (mut guard1, guard2) = (&lock1, &lock2).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+impl <'l, T: ?Sized> SortableLock for &'l SortRwLock<T> {
+    type Guard = SortWriteLocked<'l, T>;
 
     fn sort_key(&self) -> SortKey {
-        self.lock.key
+        SortRwLock::sort_key(self)
     }
 
-    #[cfg(feature = "std")]
-    fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.read()
-            .expect("Failed to lock mutex.")
-    }
-    
-    #[cfg(not(feature = "std"))]
     fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.read()
+        let upgrade = self.upgrade_raw();
+        SortWriteLocked::new(upgrade, self.write_raw(), self)
     }
 }
 
-/// A write guard for a `SortRwLock`.
-pub struct SortWriteGuard<'l, T> {
+/// A read guard for a `SortRwLock`.
+pub struct SortReadGuard<'l, T: ?Sized> {
     /// The lock this request references.
     lock: &'l SortRwLock<T>,
 }
 
-impl <'l, T> SortableLock for SortWriteGuard<'l, T> {
-    type Guard = RwLockWriteGuard<'l, T>;
+impl <'l, T: ?Sized> SortableLock for SortReadGuard<'l, T> {
+    type Guard = SortReadLocked<'l, T>;
 
     fn sort_key(&self) -> SortKey {
-        self.lock.key
+        self.lock.sort_key()
     }
 
-    #[cfg(feature = "std")]
     fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.write()
-            .expect("Failed to lock mutex.")
+        SortReadLocked::new(self.lock.read_raw(), self.lock)
     }
-    
-    #[cfg(not(feature = "std"))]
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A write guard for a `SortRwLock`.
+pub struct SortWriteGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortRwLock<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortWriteGuard<'l, T> {
+    type Guard = SortWriteLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        let upgrade = self.lock.upgrade_raw();
+        SortWriteLocked::new(upgrade, self.lock.write_raw(), self.lock)
+    }
+}
+
+/// A checked read guard for a `SortRwLock`.
+///
+/// Unlike `SortReadGuard` this does not panic if the lock is poisoned.
+#[cfg(feature = "std")]
+pub struct SortCheckedReadGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortRwLock<T>,
+}
+
+#[cfg(feature = "std")]
+impl <'l, T: ?Sized> SortableLock for SortCheckedReadGuard<'l, T> {
+    type Guard = LockResult<SortReadLocked<'l, T>>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
     fn lock_presorted(&self) -> Self::Guard {
-        self.lock.mutex.write()
+        map_lock_result(self.lock.read_checked_raw(), |guard| SortReadLocked::new(guard, self.lock))
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A checked write guard for a `SortRwLock`.
+///
+/// Unlike `SortWriteGuard` this does not panic if the lock is poisoned.
+#[cfg(feature = "std")]
+pub struct SortCheckedWriteGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortRwLock<T>,
+}
+
+#[cfg(feature = "std")]
+impl <'l, T: ?Sized> SortableLock for SortCheckedWriteGuard<'l, T> {
+    type Guard = LockResult<SortWriteLocked<'l, T>>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        let upgrade = self.lock.upgrade_raw();
+        map_lock_result(self.lock.write_checked_raw(), |guard| SortWriteLocked::new(upgrade, guard, self.lock))
+    }
+}
+
+/// Maps the guard in a `LockResult`, preserving whether the lock was poisoned.
+///
+/// - `result` - The result to map.
+/// - `f` - The function to map the guard with.
+#[cfg(feature = "std")]
+fn map_lock_result<G, U, F: FnOnce(G) -> U>(result: LockResult<G>, f: F) -> LockResult<U> {
+    match result {
+        Ok(guard) => Ok(f(guard)),
+        Err(error) => Err(PoisonError::new(f(error.into_inner()))),
+    }
+}
+
+/// An upgradable read guard for a `SortRwLock`.
+pub struct SortUpgradableReadGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortRwLock<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortUpgradableReadGuard<'l, T> {
+    type Guard = SortUpgradableReadLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        let upgrade = self.lock.upgrade_raw();
+        SortUpgradableReadLocked {
+            guard: self.lock.read_raw(),
+            upgrade,
+            lock: self.lock,
+        }
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Upgradable
+    }
+}
+
+/// A recursive read guard for a `SortRwLock`.
+pub struct SortRecursiveReadGuard<'l, T: ?Sized> {
+    /// The lock this request references.
+    lock: &'l SortRwLock<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortRecursiveReadGuard<'l, T> {
+    type Guard = SortRecursiveReadLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.read_recursive_raw();
+
+        SortRecursiveReadLocked {
+            lock: self.lock,
+        }
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A read guard for a `SortRwLock` that owns a reference to the lock.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortReadGuard<T: ?Sized> {
+    /// The lock this request references.
+    lock: Arc<SortRwLock<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> SortableLock for OwnedSortReadGuard<T> {
+    type Guard = OwnedSortReadLocked<T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.clone().read_owned()
+    }
+
+    fn access(&self) -> LockAccess {
+        LockAccess::Shared
+    }
+}
+
+/// A write guard for a `SortRwLock` that owns a reference to the lock.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortWriteGuard<T: ?Sized> {
+    /// The lock this request references.
+    lock: Arc<SortRwLock<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> SortableLock for OwnedSortWriteGuard<T> {
+    type Guard = OwnedSortWriteLocked<T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.lock.clone().write_owned()
+    }
+}
+
+/// An acquired read lock on a `SortRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortReadLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    ///
+    /// This is only `None` while the lock is temporarily released by `unlocked`.
+    guard: Option<RwLockReadGuard<'l, ()>>,
+    /// The lock this guard references.
+    lock: &'l SortRwLock<T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortReadLocked<'_, T> {}
+
+impl <'l, T: ?Sized> SortReadLocked<'l, T> {
+    /// Creates a new `SortReadLocked` from an acquired internal guard.
+    ///
+    /// - `guard` - The guard for the internal lock.
+    /// - `lock` - The lock the guard was acquired from.
+    fn new(guard: RwLockReadGuard<'l, ()>, lock: &'l SortRwLock<T>) -> Self {
+        Self {
+            guard: Some(guard),
+            lock,
+        }
+    }
+
+    /// Temporarily unlocks this lock while running a closure and then locks it again.
+    ///
+    /// The lock is reacquired on its own without sorting.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    ///
+    /// - `f` - The closure to run while unlocked.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock becomes poisoned. If the closure panics the lock is
+    /// acquired again before the panic continues.
+    ///
+    /// # Deadlocks
+    /// Relocking ignores the sort order, so this can deadlock if the thread holds other locks, such
+    /// as the rest of a group. See `SortMutexLocked::unlocked`, and use `GroupGuard::unlocked` to
+    /// release and reacquire a whole group instead.
+    pub fn unlocked<R, F: FnOnce() -> R>(this: &mut Self, f: F) -> R {
+        this.guard = None;
+
+        let result = finally(f, || this.guard = Some(this.lock.read_ignoring_poison()));
+
+        this.lock.check_poison();
+
+        result
+    }
+
+    /// Leaks this guard, keeping the lock locked for reading forever and returning a reference to
+    /// the value.
+    ///
+    /// Other readers can still lock the lock but any later attempt to lock it for writing will
+    /// block forever.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn leak(this: Self) -> &'l T {
+        let lock = this.lock;
+
+        mem::forget(this);
+
+        // SAFETY: The guard was forgotten so the lock is never unlocked and no writers can exist.
+        unsafe { &*lock.data.get() }
+    }
+
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortRwLock, SortReadLocked, LockGroup};
+    ///
+    /// let lock = SortRwLock::new((1, "value"));
+    ///
+    /// let guard = SortReadLocked::map(lock.read().lock_all(), |value| &value.1);
+    /// assert_eq!("value", *guard);
+    /// ```
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> MappedSortReadLocked<'l, U> {
+        let data = NonNull::from(f(&*this));
+
+        MappedSortReadLocked {
+            _guard: this.guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F) -> Result<MappedSortReadLocked<'l, U>, Self> {
+        match f(&*this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortReadLocked {
+                    _guard: this.guard,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for SortReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired write lock on a `SortRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortWriteLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    ///
+    /// This is only `None` while the lock is temporarily released by `unlocked`.
+    guard: Option<RwLockWriteGuard<'l, ()>>,
+    /// The guard for the upgrade lock.
+    ///
+    /// This is only `None` while the lock is temporarily released by `unlocked`.
+    upgrade: Option<MutexGuard<'l, ()>>,
+    /// The lock this guard references.
+    lock: &'l SortRwLock<T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortWriteLocked<'_, T> {}
+
+impl <'l, T: ?Sized> SortWriteLocked<'l, T> {
+    /// Creates a new `SortWriteLocked` from acquired internal guards.
+    ///
+    /// - `upgrade` - The guard for the upgrade lock.
+    /// - `guard` - The guard for the internal lock.
+    /// - `lock` - The lock the guards were acquired from.
+    fn new(upgrade: MutexGuard<'l, ()>, guard: RwLockWriteGuard<'l, ()>, lock: &'l SortRwLock<T>) -> Self {
+        Self {
+            guard: Some(guard),
+            upgrade: Some(upgrade),
+            lock,
+        }
+    }
+
+    /// Temporarily unlocks this lock while running a closure and then locks it again.
+    ///
+    /// This is useful for releasing a lock around a blocking call. The lock is reacquired on its own
+    /// without sorting.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortRwLock, SortWriteLocked, LockGroup};
+    ///
+    /// let lock = SortRwLock::new(1);
+    ///
+    /// let mut guard = lock.write().lock_all();
+    /// SortWriteLocked::unlocked(&mut guard, || {
+    ///     // The lock can be acquired elsewhere while unlocked.
+    ///     assert!(lock.try_read().is_some());
+    /// });
+    /// *guard += 1;
+    /// ```
+    ///
+    /// - `f` - The closure to run while unlocked.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock becomes poisoned. If the closure panics the lock is
+    /// acquired again before the panic continues.
+    ///
+    /// # Deadlocks
+    /// Relocking ignores the sort order, so this can deadlock if the thread holds other locks, such
+    /// as the rest of a group. See `SortMutexLocked::unlocked`, and use `GroupGuard::unlocked` to
+    /// release and reacquire a whole group instead.
+    pub fn unlocked<R, F: FnOnce() -> R>(this: &mut Self, f: F) -> R {
+        this.guard = None;
+        this.upgrade = None;
+
+        let result = finally(f, || {
+            this.upgrade = Some(this.lock.upgrade_raw());
+            this.guard = Some(this.lock.write_ignoring_poison());
+        });
+
+        this.lock.check_poison();
+
+        result
+    }
+
+    /// Leaks this guard, keeping the lock locked for writing forever and returning a reference to
+    /// the value.
+    ///
+    /// This is useful for locks in static or leaked storage that are locked once and never
+    /// released. The lock is never unlocked so any later attempt to lock it will block forever.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortRwLock, SortWriteLocked, LockGroup};
+    ///
+    /// static LOCK: SortRwLock<i32> = SortRwLock::const_new(1);
+    ///
+    /// let value: &'static mut i32 = SortWriteLocked::leak(LOCK.write().lock_all());
+    /// *value += 1;
+    /// assert!(LOCK.try_read().is_none());
+    /// ```
+    pub fn leak(this: Self) -> &'l mut T {
+        let lock = this.lock;
+
+        mem::forget(this);
+
+        // SAFETY: The guards were forgotten so the lock is never unlocked and this is the only
+        // reference to the value.
+        unsafe { &mut *lock.data.get() }
+    }
+
+    /// Converts this write lock into a read lock without releasing the lock.
+    ///
+    /// No other writer can acquire the lock between the write and the read so any changes made
+    /// while holding the write lock are guaranteed to be observed through the returned guard.
+    /// ```
+    /// use sortlock::{SortRwLock, LockGroup};
+    ///
+    /// let lock = SortRwLock::new(1);
+    ///
+    /// let mut guard = lock.write().lock_all();
+    /// *guard += 1;
+    ///
+    /// let guard = guard.downgrade();
+    /// assert_eq!(2, *guard);
+    /// ```
+    pub fn downgrade(self) -> SortReadLocked<'l, T> {
+        let Self { guard, upgrade, lock } = self;
+
+        let guard = guard.map(RwLockWriteGuard::downgrade);
+
+        drop(upgrade);
+
+        SortReadLocked {
+            guard,
+            lock,
+        }
+    }
+
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// The lock remains held until the returned guard is dropped.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    /// ```
+    /// use sortlock::{SortRwLock, SortWriteLocked, LockGroup};
+    ///
+    /// let lock = SortRwLock::new((1, 2));
+    ///
+    /// let mut guard = SortWriteLocked::map(lock.write().lock_all(), |value| &mut value.1);
+    /// *guard += 1;
+    /// drop(guard);
+    ///
+    /// assert_eq!((1, 3), *lock.read().lock_all());
+    /// ```
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortWriteLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortWriteLocked {
+            _guard: this.guard,
+            _upgrade: this.upgrade,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortWriteLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortWriteLocked {
+                    _guard: this.guard,
+                    _upgrade: this.upgrade,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for SortWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for SortWriteLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired upgradable read lock on a `SortRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortUpgradableReadLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    guard: RwLockReadGuard<'l, ()>,
+    /// The guard for the upgrade lock.
+    upgrade: MutexGuard<'l, ()>,
+    /// The lock this guard references.
+    lock: &'l SortRwLock<T>,
+}
+
+impl <'l, T: ?Sized> SortUpgradableReadLocked<'l, T> {
+    /// Upgrades this lock into a write lock.
+    ///
+    /// This blocks until all other readers have released the lock. No other writer can acquire
+    /// the lock in the meantime so the value will not have changed since it was read.
+    /// ```
+    /// use sortlock::{SortRwLock, LockGroup};
+    ///
+    /// let lock = SortRwLock::new(1);
+    ///
+    /// let guard = lock.upgradable_read().lock_all();
+    /// let mut guard = guard.upgrade();
+    /// *guard += 1;
+    ///
+    /// assert_eq!(2, *guard);
+    /// ```
+    pub fn upgrade(self) -> SortWriteLocked<'l, T> {
+        let Self { guard, upgrade, lock } = self;
+
+        drop(guard);
+
+        SortWriteLocked::new(upgrade, lock.write_raw(), lock)
+    }
+
+    /// Converts this lock into a plain read lock, allowing writers and other upgradable readers
+    /// to proceed once other readers have released the lock.
+    pub fn downgrade(self) -> SortReadLocked<'l, T> {
+        let Self { guard, upgrade, lock } = self;
+
+        drop(upgrade);
+
+        SortReadLocked::new(guard, lock)
+    }
+}
+
+impl <T: ?Sized> Deref for SortUpgradableReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortUpgradableReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortUpgradableReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired recursive read lock on a `SortRwLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortRecursiveReadLocked<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortRwLock<T>,
+}
+
+impl <T: ?Sized> Deref for SortRecursiveReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: Writers wait for all recursive readers so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> Drop for SortRecursiveReadLocked<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release_recursive();
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortRecursiveReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortRecursiveReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A read guard for a component of a value locked by a `SortRwLock`.
+///
+/// This is created with `SortReadLocked::map`. The lock is released when this guard is dropped.
+///
+/// Like `&T`, the guard borrows the locked value, so it cannot outlive the lock.
+/// ```compile_fail
+/// use sortlock::{SortRwLock, SortReadLocked, LockGroup};
+///
+/// let lock = SortRwLock::new((1, 2));
+/// let guard = SortReadLocked::map(lock.read().lock_all(), |value| &value.0);
+/// drop(lock);
+/// assert_eq!(1, *guard);
+/// ```
+pub struct MappedSortReadLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    _guard: Option<RwLockReadGuard<'l, ()>>,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Ties the guard to the lifetime of the value it reads.
+    _marker: PhantomData<&'l T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for MappedSortReadLocked<'_, T> {}
+
+impl <'l, T: ?Sized> MappedSortReadLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> MappedSortReadLocked<'l, U> {
+        let data = NonNull::from(f(&*this));
+
+        MappedSortReadLocked {
+            _guard: this._guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F) -> Result<MappedSortReadLocked<'l, U>, Self> {
+        match f(&*this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortReadLocked {
+                    _guard: this._guard,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for MappedSortReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for MappedSortReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for MappedSortReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A write guard for a component of a value locked by a `SortRwLock`.
+///
+/// This is created with `SortWriteLocked::map`. The lock is released when this guard is dropped.
+///
+/// Like `&mut T`, the guard is invariant over `T`, so it cannot be used to store a shorter lived
+/// reference in the locked value.
+/// ```compile_fail
+/// use sortlock::MappedSortWriteLocked;
+///
+/// fn shorten<'a, 'l>(guard: MappedSortWriteLocked<'l, &'static str>) -> MappedSortWriteLocked<'l, &'a str> {
+///     guard
+/// }
+/// ```
+pub struct MappedSortWriteLocked<'l, T: ?Sized> {
+    /// The guard for the internal lock.
+    _guard: Option<RwLockWriteGuard<'l, ()>>,
+    /// The guard for the upgrade lock.
+    _upgrade: Option<MutexGuard<'l, ()>>,
+    /// The component of the value protected by the lock.
+    data: NonNull<T>,
+    /// Makes the guard invariant over `T`, as it gives mutable access to the value.
+    _marker: PhantomData<&'l mut T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for MappedSortWriteLocked<'_, T> {}
+
+impl <'l, T: ?Sized> MappedSortWriteLocked<'l, T> {
+    /// Makes a new guard for a component of the locked value.
+    ///
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F) -> MappedSortWriteLocked<'l, U> {
+        let data = NonNull::from(f(&mut *this));
+
+        MappedSortWriteLocked {
+            _guard: this._guard,
+            _upgrade: this._upgrade,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to make a new guard for a component of the locked value.
+    ///
+    /// If the closure returns `None` the original guard is returned instead.
+    /// This is an associated function as a method could conflict with a method on `T`.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F) -> Result<MappedSortWriteLocked<'l, U>, Self> {
+        match f(&mut *this) {
+            Some(data) => {
+                let data = NonNull::from(data);
+
+                Ok(MappedSortWriteLocked {
+                    _guard: this._guard,
+                    _upgrade: this._upgrade,
+                    data,
+                    _marker: PhantomData,
+                })
+            },
+            None => Err(this),
+        }
+    }
+}
+
+impl <T: ?Sized> Deref for MappedSortWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for MappedSortWriteLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for MappedSortWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for MappedSortWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired read lock on a `SortRwLock` that owns a reference to the lock.
+///
+/// This is created with `SortRwLock::read_owned`. The lock is released when this guard is
+/// dropped.
+///
+/// When the `std` feature is enabled this guard is not `Send`, like the standard library's
+/// guards, so it must be dropped on the thread that created it. Without the `std` feature it is
+/// `Send`.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortReadLocked<T: ?Sized> {
+    /// The guard for the internal lock.
+    ///
+    /// This must be dropped before `lock`.
+    _guard: RwLockReadGuard<'static, ()>,
+    /// The lock this guard references.
+    lock: Arc<SortRwLock<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> OwnedSortReadLocked<T> {
+    /// Gets the lock this guard references.
+    pub fn lock(this: &Self) -> &Arc<SortRwLock<T>> {
+        &this.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> Deref for OwnedSortReadLocked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The read lock is held so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Debug> Debug for OwnedSortReadLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Display> Display for OwnedSortReadLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired write lock on a `SortRwLock` that owns a reference to the lock.
+///
+/// This is created with `SortRwLock::write_owned`. The lock is released when this guard is
+/// dropped.
+///
+/// When the `std` feature is enabled this guard is not `Send`, like the standard library's
+/// guards, so it must be dropped on the thread that created it. Without the `std` feature it is
+/// `Send`.
+#[cfg(feature = "alloc")]
+pub struct OwnedSortWriteLocked<T: ?Sized> {
+    /// The guard for the internal lock.
+    ///
+    /// This must be dropped before `lock`.
+    guard: RwLockWriteGuard<'static, ()>,
+    /// The guard for the upgrade lock.
+    ///
+    /// This must be dropped before `lock`.
+    upgrade: MutexGuard<'static, ()>,
+    /// The lock this guard references.
+    lock: Arc<SortRwLock<T>>,
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl <T: ?Sized + Sync> Sync for OwnedSortWriteLocked<T> {}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> OwnedSortWriteLocked<T> {
+    /// Gets the lock this guard references.
+    pub fn lock(this: &Self) -> &Arc<SortRwLock<T>> {
+        &this.lock
+    }
+
+    /// Converts this write lock into a read lock without releasing the lock.
+    pub fn downgrade(self) -> OwnedSortReadLocked<T> {
+        let Self { guard, upgrade, lock } = self;
+
+        #[cfg(feature = "std")]
+        let guard = RwLockWriteGuard::downgrade(guard);
+        #[cfg(not(feature = "std"))]
+        let guard = guard.downgrade();
+
+        drop(upgrade);
+
+        OwnedSortReadLocked {
+            _guard: guard,
+            lock,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> Deref for OwnedSortWriteLocked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized> DerefMut for OwnedSortWriteLocked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The write lock is held so this is the only reference to the value.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Debug> Debug for OwnedSortWriteLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl <T: ?Sized + Display> Display for OwnedSortWriteLocked<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{any::Any, sync::Arc, thread};
+    use std::{any::Any, panic::{self, AssertUnwindSafe}, sync::{Arc, mpsc}, thread, time::Duration};
+
+    use crate::{SortRwLock, SortReadLocked, SortWriteLocked, MappedSortWriteLocked, LockGroup};
+
+    #[cfg(all(feature = "diagnostics", feature = "std"))]
+    #[test]
+    fn test_created_at() {
+        let line = line!();
+        let lock = Arc::new(SortRwLock::new(1));
+        assert_eq!(line + 1, lock.created_at().line());
 
-    use crate::{SortRwLock, LockGroup};
+        let poisoner = lock.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.write().lock_all();
+            panic!("poison the lock");
+        }).join();
+
+        let error = thread::spawn(move || *lock.read().lock_all()).join().unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains(&format!("{}:{}", file!(), line + 1)));
+    }
 
     #[test]
     fn test_lock2() {
@@ -178,6 +1710,283 @@ mod tests {
 
         println!("{} {}", guard1, guard2);
     }
+
+    #[test]
+    fn test_try_read_write() {
+        let lock = SortRwLock::new(1);
+
+        let guard1 = lock.try_read().unwrap();
+        let guard2 = lock.try_read().unwrap();
+        assert_eq!(*guard1, *guard2);
+        assert!(lock.try_write().is_none());
+
+        drop(guard1);
+        drop(guard2);
+
+        let guard = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(guard);
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let lock = SortRwLock::new(1);
+
+        let mut guard = lock.write().lock_all();
+        *guard = 2;
+
+        let guard = guard.downgrade();
+        assert_eq!(2, *guard);
+        assert!(lock.try_read().is_some());
+        assert!(lock.try_write().is_none());
+    }
+
+    #[test]
+    fn test_from() {
+        let lock = SortRwLock::from(1);
+        assert_eq!(1, *lock.read().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_std() {
+        let lock: SortRwLock<i32> = std::sync::RwLock::new(2).into();
+        assert_eq!(2, *lock.read().lock_all());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_checked() {
+        let lock = SortRwLock::new(1);
+
+        let result = thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = lock.write().lock_all();
+                panic!("poison the lock");
+            }).join()
+        });
+        assert!(result.is_err());
+
+        let other = SortRwLock::new(2);
+        let (guard1, guard2) = (lock.read_checked(), other.write_checked()).lock_all();
+        assert_eq!(1, *guard1.unwrap_err().into_inner());
+        assert_eq!(2, *guard2.unwrap());
+
+        let mut guard = lock.write_checked().lock_all().unwrap_or_else(|error| error.into_inner());
+        *guard += 1;
+    }
+
+    #[test]
+    fn test_compatible_requests() {
+        let lock = SortRwLock::new(1);
+
+        let (guard1, guard2, guard3) = (lock.read(), lock.upgradable_read(), lock.read()).lock_all();
+        assert_eq!(3, *guard1 + *guard2 + *guard3);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicting requests")]
+    fn test_conflicting_requests() {
+        let lock = SortRwLock::new(1);
+        let other = SortRwLock::new(2);
+
+        let _guards = (lock.read(), other.read(), lock.write()).lock_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicting requests")]
+    fn test_conflicting_upgradable_requests() {
+        let lock = SortRwLock::new(1);
+
+        let _guards = [lock.upgradable_read(), lock.upgradable_read()].lock_all();
+    }
+
+    #[test]
+    fn test_unlocked() {
+        let lock = SortRwLock::new(1);
+
+        let mut guard = lock.write().lock_all();
+        SortWriteLocked::unlocked(&mut guard, || {
+            *lock.write().lock_all() += 1;
+        });
+        *guard += 1;
+        assert!(lock.try_read().is_none());
+        drop(guard);
+
+        let mut guard = lock.read().lock_all();
+        SortReadLocked::unlocked(&mut guard, || {
+            assert!(lock.try_write().is_some());
+        });
+        assert_eq!(3, *guard);
+        assert!(lock.try_write().is_none());
+    }
+
+    #[test]
+    fn test_unlocked_panic() {
+        let lock = SortRwLock::new(1);
+
+        let mut guard = lock.write().lock_all();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            SortWriteLocked::unlocked(&mut guard, || panic!("unlocked"));
+        }));
+        assert!(result.is_err());
+
+        // The lock is held again so the guard is still the only access to the value.
+        assert!(lock.try_read().is_none());
+        *guard += 1;
+        drop(guard);
+
+        let mut guard = lock.read().lock_all();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            SortReadLocked::unlocked(&mut guard, || panic!("unlocked"));
+        }));
+        assert!(result.is_err());
+        assert!(lock.try_write().is_none());
+        assert_eq!(2, *guard);
+    }
+
+    #[test]
+    fn test_map() {
+        let lock = SortRwLock::new((1, vec![1, 2, 3]));
+
+        let guard = SortWriteLocked::map(lock.write().lock_all(), |value| &mut value.1);
+        let mut guard = MappedSortWriteLocked::map(guard, |value| value.as_mut_slice());
+        guard[0] = 4;
+        drop(guard);
+
+        let guard = lock.read().lock_all();
+        let guard = SortReadLocked::try_map(guard, |value| value.1.get(5)).unwrap_err();
+        let guard = SortReadLocked::try_map(guard, |value| value.1.first()).unwrap();
+        assert_eq!(4, *guard);
+        assert!(lock.try_write().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_owned() {
+        let lock = Arc::new(SortRwLock::new(1));
+
+        let mut guard = lock.clone().write_owned();
+        *guard += 1;
+        assert!(lock.try_read().is_none());
+
+        let guard = guard.downgrade();
+        let guard2 = lock.clone().read_owned();
+        assert_eq!(*guard, *guard2);
+        assert!(lock.try_write().is_none());
+        drop(guard);
+        drop(guard2);
+
+        assert!(lock.try_write().is_some());
+    }
+
+    #[test]
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    fn test_owned_send() {
+        let lock = Arc::new(SortRwLock::new(1));
+
+        let mut guard = lock.clone().write_owned();
+        *guard += 1;
+
+        thread::spawn(move || *guard += 1).join().unwrap();
+
+        let guard = lock.clone().read_owned();
+        thread::spawn(move || assert_eq!(3, *guard)).join().unwrap();
+
+        assert!(lock.try_write().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_owned_group() {
+        let lock1 = Arc::new(SortRwLock::new(1));
+        let lock2 = Arc::new(SortRwLock::new(2));
+
+        let (guard2, mut guard1) = (lock2.clone().read_owned_request(), lock1.clone().write_owned_request()).lock_all();
+        *guard1 += *guard2;
+        assert!(lock1.try_read().is_none());
+        assert!(lock2.try_write().is_none());
+        drop((guard1, guard2));
+
+        assert_eq!(3, *lock1.read().lock_all());
+    }
+
+    #[test]
+    fn test_unsized() {
+        let lock: Box<SortRwLock<[u8]>> = Box::new(SortRwLock::new([1, 2, 3]));
+
+        lock.write().lock_all()[0] = 4;
+        assert_eq!([4, 2, 3], *lock.read().lock_all());
+    }
+
+    #[test]
+    fn test_read_recursive() {
+        let lock = Arc::new(SortRwLock::new(1));
+
+        let guard1 = lock.read_recursive().lock_all();
+        assert!(lock.try_write().is_none());
+
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || *lock.write().lock_all() += 1)
+        };
+
+        // Give the writer a chance to start waiting.
+        thread::sleep(Duration::from_millis(50));
+
+        let guard2 = lock.read_recursive().lock_all();
+        assert_eq!(*guard1, *guard2);
+        drop(guard1);
+        drop(guard2);
+
+        writer.join().unwrap();
+        assert_eq!(2, *lock.read().lock_all());
+    }
+
+    #[test]
+    fn test_write_after_read_recursive() {
+        let lock = SortRwLock::new(1);
+        let guard = lock.read_recursive().lock_all();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                *lock.write().lock_all() += 1;
+                sender.send(()).unwrap();
+            });
+
+            // The writer waits for the recursive reader.
+            assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+
+            // Releasing the reader from another thread wakes the writer.
+            scope.spawn(move || drop(guard)).join().unwrap();
+
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        });
+
+        assert_eq!(2, *lock.read().lock_all());
+    }
+
+    #[test]
+    fn test_upgradable_read() {
+        let lock = SortRwLock::new(1);
+
+        let guard = lock.upgradable_read().lock_all();
+        assert!(lock.try_read().is_some());
+        assert!(lock.try_write().is_none());
+
+        let mut guard = guard.upgrade();
+        *guard += 1;
+        assert!(lock.try_read().is_none());
+
+        let guard = guard.downgrade();
+        assert_eq!(2, *guard);
+        assert!(lock.try_write().is_none());
+        drop(guard);
+
+        assert!(lock.try_write().is_some());
+    }
     
     #[test]
     fn test_deadlock() -> Result<(), Box<dyn Any + Send + 'static>> {
@@ -0,0 +1,337 @@
+use core::{fmt::{self, Debug, Formatter}, mem};
+
+#[cfg(feature = "std")]
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+#[cfg(not(feature = "std"))]
+use spin::{Mutex, MutexGuard};
+
+use crate::{SortKey, SortableLock, key::LazySortKey};
+
+/// A sortable counting semaphore.
+///
+/// A semaphore holds a number of permits. Acquiring takes permits from the semaphore, waiting
+/// until enough are available, and they are returned when the acquired guard is dropped.
+/// Acquiring permits can be sorted with other locks through the use of `lock_all`.
+/// ```
+/// use sortlock::{SortMutex, SortSemaphore, LockGroup};
+///
+/// let semaphore = SortSemaphore::new(2);
+/// let lock = SortMutex::new(1);
+///
+/// let (permit, guard) = (semaphore.acquire(), lock.lock()).lock_all();
+/// assert_eq!(1, semaphore.available_permits());
+/// assert_eq!(1, *guard);
+/// ```
+///
+/// Multiple permits can be acquired at once with `acquire_many`. Permits are acquired all at once
+/// so a group should contain at most one request for each semaphore. Acquiring permits one at a
+/// time while holding others can deadlock with another thread doing the same.
+pub struct SortSemaphore {
+    /// The number of available permits.
+    permits: Mutex<usize>,
+    /// Notified when permits are released.
+    #[cfg(feature = "std")]
+    released: Condvar,
+    /// The sort key for this semaphore.
+    key: LazySortKey,
+}
+
+impl SortSemaphore {
+    /// Creates a new `SortSemaphore`.
+    ///
+    /// - `permits` - The number of permits initially available.
+    #[track_caller]
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: Mutex::new(permits),
+            #[cfg(feature = "std")]
+            released: Condvar::new(),
+            key: LazySortKey::new(SortKey::new()),
+        }
+    }
+
+    /// Creates a new `SortSemaphore` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    /// ```
+    /// use sortlock::{SortSemaphore, LockGroup};
+    ///
+    /// static SEMAPHORE: SortSemaphore = SortSemaphore::const_new(4);
+    ///
+    /// let permit = SEMAPHORE.acquire().lock_all();
+    /// assert_eq!(3, SEMAPHORE.available_permits());
+    /// ```
+    ///
+    /// - `permits` - The number of permits initially available.
+    pub const fn const_new(permits: usize) -> Self {
+        Self {
+            permits: Mutex::new(permits),
+            #[cfg(feature = "std")]
+            released: Condvar::new(),
+            key: LazySortKey::unassigned(),
+        }
+    }
+
+    /// Requests to acquire a single permit.
+    pub fn acquire(&self) -> SortSemaphoreGuard<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Requests to acquire multiple permits at once.
+    ///
+    /// Locking waits until all of the permits are available at the same time.
+    /// ```
+    /// use sortlock::{SortSemaphore, LockGroup};
+    ///
+    /// let semaphore = SortSemaphore::new(4);
+    ///
+    /// let permits = semaphore.acquire_many(3).lock_all();
+    /// assert_eq!(3, permits.permits());
+    /// assert!(semaphore.try_acquire_many(2).is_none());
+    /// ```
+    ///
+    /// - `permits` - The number of permits to acquire.
+    pub fn acquire_many(&self, permits: usize) -> SortSemaphoreGuard<'_> {
+        SortSemaphoreGuard {
+            semaphore: self,
+            permits,
+        }
+    }
+
+    /// Attempts to acquire a single permit without blocking.
+    ///
+    /// As only a single semaphore is acquired no sorting is required.
+    pub fn try_acquire(&self) -> Option<SortSemaphoreLocked<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Attempts to acquire multiple permits without blocking.
+    ///
+    /// As only a single semaphore is acquired no sorting is required.
+    ///
+    /// - `permits` - The number of permits to acquire.
+    pub fn try_acquire_many(&self, permits: usize) -> Option<SortSemaphoreLocked<'_>> {
+        let mut available = self.permits();
+
+        if *available < permits {
+            return None;
+        }
+
+        *available -= permits;
+
+        Some(SortSemaphoreLocked {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    /// Gets the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        *self.permits()
+    }
+
+    /// Adds permits to this semaphore.
+    ///
+    /// - `permits` - The number of permits to add.
+    pub fn add_permits(&self, permits: usize) {
+        *self.permits() += permits;
+
+        #[cfg(feature = "std")]
+        self.released.notify_all();
+    }
+
+    /// Gets the sort key of this semaphore.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Waits until enough permits are available and takes them.
+    ///
+    /// - `permits` - The number of permits to take.
+    #[cfg(feature = "std")]
+    fn acquire_raw(&self, permits: usize) {
+        let mut available = self.released.wait_while(self.permits(), |available| *available < permits)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *available -= permits;
+    }
+
+    /// Waits until enough permits are available and takes them.
+    ///
+    /// - `permits` - The number of permits to take.
+    #[cfg(not(feature = "std"))]
+    fn acquire_raw(&self, permits: usize) {
+        loop {
+            let mut available = self.permits();
+
+            if *available >= permits {
+                *available -= permits;
+                return;
+            }
+
+            drop(available);
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Locks the number of available permits.
+    ///
+    /// The count is never left inconsistent by a panic so poisoning is ignored.
+    #[cfg(feature = "std")]
+    fn permits(&self) -> MutexGuard<'_, usize> {
+        self.permits.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the number of available permits.
+    #[cfg(not(feature = "std"))]
+    fn permits(&self) -> MutexGuard<'_, usize> {
+        self.permits.lock()
+    }
+}
+
+impl Debug for SortSemaphore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortSemaphore")
+            .field("permits", &self.available_permits())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard for a `SortSemaphore`.
+///
+/// This requests a number of permits from the semaphore.
+pub struct SortSemaphoreGuard<'l> {
+    /// The semaphore this guard references.
+    semaphore: &'l SortSemaphore,
+    /// The number of permits to acquire.
+    permits: usize,
+}
+
+impl <'l> SortableLock for SortSemaphoreGuard<'l> {
+    type Guard = SortSemaphoreLocked<'l>;
+
+    fn sort_key(&self) -> SortKey {
+        self.semaphore.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.semaphore.acquire_raw(self.permits);
+
+        SortSemaphoreLocked {
+            semaphore: self.semaphore,
+            permits: self.permits,
+        }
+    }
+}
+
+/// Permits acquired from a `SortSemaphore`.
+///
+/// The permits are returned to the semaphore when this guard is dropped.
+pub struct SortSemaphoreLocked<'l> {
+    /// The semaphore the permits were acquired from.
+    semaphore: &'l SortSemaphore,
+    /// The number of permits held.
+    permits: usize,
+}
+
+impl SortSemaphoreLocked<'_> {
+    /// Gets the number of permits held by this guard.
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permits held by this guard so they are never returned to the semaphore.
+    /// ```
+    /// use sortlock::{SortSemaphore, LockGroup};
+    ///
+    /// let semaphore = SortSemaphore::new(2);
+    ///
+    /// semaphore.acquire().lock_all().forget();
+    /// assert_eq!(1, semaphore.available_permits());
+    /// ```
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for SortSemaphoreLocked<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+impl Debug for SortSemaphoreLocked<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortSemaphoreLocked")
+            .field("permits", &self.permits)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{Arc, atomic::{AtomicUsize, Ordering}}, thread};
+
+    use crate::{LockGroup, SortMutex, SortSemaphore};
+
+    #[test]
+    fn test_acquire() {
+        let semaphore = SortSemaphore::new(2);
+
+        let permit1 = semaphore.acquire().lock_all();
+        let permit2 = semaphore.try_acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+
+        drop(permit1);
+        assert_eq!(1, semaphore.available_permits());
+        drop(permit2);
+        assert_eq!(2, semaphore.available_permits());
+    }
+
+    #[test]
+    fn test_acquire_many() {
+        let semaphore = SortSemaphore::new(3);
+        let lock = SortMutex::new(1);
+
+        let (guard, permits) = (lock.lock(), semaphore.acquire_many(3)).lock_all();
+        assert_eq!(1, *guard);
+        assert_eq!(3, permits.permits());
+        assert_eq!(0, semaphore.available_permits());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_conflicting_requests() {
+        let semaphore = SortSemaphore::new(2);
+
+        let _ = (semaphore.acquire(), semaphore.acquire()).lock_all();
+    }
+
+    #[test]
+    fn test_limit() {
+        let semaphore = Arc::new(SortSemaphore::new(2));
+        let active = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8).map(|_| {
+            let semaphore = semaphore.clone();
+            let active = active.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let permit = semaphore.acquire().lock_all();
+                    assert!(active.fetch_add(1, Ordering::SeqCst) < 2);
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(2, semaphore.available_permits());
+    }
+}
@@ -0,0 +1,449 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Formatter}, mem::{self, MaybeUninit}, ops::Deref, sync::atomic::{self, AtomicU8, AtomicUsize, Ordering}};
+
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+#[cfg(not(feature = "std"))]
+use spin::{Mutex, MutexGuard};
+
+use crate::{SortKey, SortableLock, key::LazySortKey};
+
+/// A sortable sequence lock that allows reading without blocking.
+///
+/// Readers never take a lock. Instead a read copies the value and retries if a writer modified it
+/// during the copy. Only writers take the lock, which can be sorted with other locks through the
+/// use of `lock_all`. This makes reads very cheap for values that are read far more often than
+/// they are written.
+/// ```
+/// use sortlock::{SortMutex, SortSeqLock, LockGroup};
+///
+/// let lock1 = SortSeqLock::new([1, 2]);
+/// let lock2 = SortMutex::new(3);
+///
+/// let (mut guard1, guard2) = (lock1.write(), lock2.lock()).lock_all();
+/// guard1.update(|value| value[0] = *guard2);
+/// drop(guard1);
+///
+/// assert_eq!([3, 2], lock1.read());
+/// ```
+///
+/// As readers may copy a value while it is being written, only `Copy` values can be stored. For the
+/// same reason the value is only ever copied in and out of the lock with atomic operations, so a
+/// write guard replaces the value with `set` or `update` rather than giving out a mutable
+/// reference. Copying padding this way would read uninitialized memory, so only values without
+/// padding, which implement `NoUninit`, can be stored.
+/// ```compile_fail
+/// use sortlock::SortSeqLock;
+///
+/// // There is padding after the fields of `(u64, u8)`.
+/// let lock = SortSeqLock::new((1u64, 2u8));
+/// ```
+pub struct SortSeqLock<T: NoUninit> {
+    /// The internal mutex held by writers.
+    mutex: Mutex<()>,
+    /// The sequence number. This is odd while a writer is modifying the value.
+    sequence: AtomicUsize,
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: NoUninit + Send> Send for SortSeqLock<T> {}
+unsafe impl <T: NoUninit + Send> Sync for SortSeqLock<T> {}
+
+impl <T: NoUninit> SortSeqLock<T> {
+    /// Creates a new `SortSeqLock`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            sequence: AtomicUsize::new(0),
+            key: LazySortKey::new(SortKey::new()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortSeqLock` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            sequence: AtomicUsize::new(0),
+            key: LazySortKey::unassigned(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Reads the value without blocking writers.
+    ///
+    /// This retries until a copy of the value is made without a writer modifying it.
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempts to read the value once.
+    ///
+    /// This returns `None` if a writer modified the value during the read.
+    /// ```
+    /// use sortlock::{SortSeqLock, LockGroup};
+    ///
+    /// let lock = SortSeqLock::new(1);
+    /// assert_eq!(Some(1), lock.try_read());
+    ///
+    /// let guard = lock.write().lock_all();
+    /// assert_eq!(None, lock.try_read());
+    /// ```
+    pub fn try_read(&self) -> Option<T> {
+        let before = self.sequence.load(Ordering::Acquire);
+
+        if !before.is_multiple_of(2) {
+            return None;
+        }
+
+        // SAFETY: Writers only store to the value with `atomic_store` so the atomic copy does not
+        // race with them, although it may be torn.
+        let value = unsafe { atomic_load(self.data.get()) };
+
+        atomic::fence(Ordering::Acquire);
+
+        if self.sequence.load(Ordering::Relaxed) != before {
+            return None;
+        }
+
+        // SAFETY: The sequence number is unchanged so no writer modified the value during the copy
+        // and it is a complete copy of a valid `T`.
+        Some(unsafe { value.assume_init() })
+    }
+
+    /// Requests to lock this lock for writing.
+    pub fn write(&self) -> SortSeqWriteGuard<'_, T> {
+        SortSeqWriteGuard {
+            lock: self
+        }
+    }
+
+    /// Attempts to lock this lock for writing without blocking.
+    ///
+    /// As only a single lock is locked no sorting is required.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    pub fn try_write(&self) -> Option<SortSeqWriteLocked<'_, T>> {
+        self.try_lock_raw()
+            .map(|guard| SortSeqWriteLocked::new(guard, self))
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Locks the internal mutex.
+    #[cfg(feature = "std")]
+    fn lock_raw(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+            .expect("Failed to lock mutex: mutex is poisoned.")
+    }
+
+    /// Locks the internal mutex.
+    #[cfg(not(feature = "std"))]
+    fn lock_raw(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+    }
+
+    /// Attempts to lock the internal mutex without blocking.
+    #[cfg(feature = "std")]
+    fn try_lock_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        match self.mutex.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(_)) => panic!("Failed to lock mutex: mutex is poisoned."),
+        }
+    }
+
+    /// Attempts to lock the internal mutex without blocking.
+    #[cfg(not(feature = "std"))]
+    fn try_lock_raw(&self) -> Option<MutexGuard<'_, ()>> {
+        self.mutex.try_lock()
+    }
+}
+
+impl <T: NoUninit + Debug> Debug for SortSeqLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.read().fmt(f)
+    }
+}
+
+impl <T: NoUninit + Default> Default for SortSeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T: NoUninit> From<T> for SortSeqLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A write guard for a `SortSeqLock`.
+pub struct SortSeqWriteGuard<'l, T: NoUninit> {
+    /// The lock this guard references.
+    lock: &'l SortSeqLock<T>,
+}
+
+impl <'l, T: NoUninit> SortableLock for SortSeqWriteGuard<'l, T> {
+    type Guard = SortSeqWriteLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortSeqWriteLocked::new(self.lock.lock_raw(), self.lock)
+    }
+}
+
+/// An acquired write lock on a `SortSeqLock`.
+///
+/// Readers retry until this guard is dropped. As readers copy the value concurrently, the value
+/// can only be changed through `set` and `update`, which store it atomically.
+pub struct SortSeqWriteLocked<'l, T: NoUninit> {
+    /// The guard for the internal mutex.
+    _guard: MutexGuard<'l, ()>,
+    /// The lock this guard references.
+    lock: &'l SortSeqLock<T>,
+}
+
+unsafe impl <T: NoUninit + Sync> Sync for SortSeqWriteLocked<'_, T> {}
+
+impl <'l, T: NoUninit> SortSeqWriteLocked<'l, T> {
+    /// Creates a new `SortSeqWriteLocked`, marking the value as being written.
+    ///
+    /// - `guard` - The guard for the internal mutex.
+    /// - `lock` - The lock the guard was acquired from.
+    fn new(guard: MutexGuard<'l, ()>, lock: &'l SortSeqLock<T>) -> Self {
+        lock.sequence.fetch_add(1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        Self {
+            _guard: guard,
+            lock,
+        }
+    }
+
+    /// Replaces the value.
+    ///
+    /// - `value` - The new value.
+    pub fn set(&mut self, value: T) {
+        // SAFETY: The internal mutex is held so no other writer stores to the value and readers
+        // only copy it with `atomic_load`.
+        unsafe { atomic_store(self.lock.data.get(), value) }
+    }
+
+    /// Modifies a copy of the value with a closure and then stores it back.
+    ///
+    /// - `f` - The closure that modifies the value.
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> R {
+        let mut value = **self;
+        let result = f(&mut value);
+        self.set(value);
+
+        result
+    }
+}
+
+impl <T: NoUninit> Deref for SortSeqWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: Only the holder of the internal mutex stores to the value, which requires a
+        // mutable borrow of this guard, and readers only load from it.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: NoUninit> Drop for SortSeqWriteLocked<'_, T> {
+    fn drop(&mut self) {
+        self.lock.sequence.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl <T: NoUninit + Debug> Debug for SortSeqWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A type without uninitialized bytes, which can be copied in and out of a `SortSeqLock` with
+/// atomic operations.
+///
+/// This is implemented for the primitive integer and floating point types, `bool`, `char`, `()`
+/// and arrays of these types.
+///
+/// # Safety
+/// Every byte of every value of the type must be initialized. A struct must have no padding
+/// between or after its fields and each field must implement `NoUninit`, such as a `#[repr(C)]`
+/// struct of `u32` fields. Unions and enums with fields must not implement this.
+pub unsafe trait NoUninit: Copy {}
+
+/// Implements `NoUninit` for types without uninitialized bytes.
+macro_rules! impl_no_uninit {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl NoUninit for $ty {}
+        )*
+    };
+}
+
+impl_no_uninit!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+unsafe impl <T: NoUninit, const N: usize> NoUninit for [T; N] {}
+
+/// Whether a value is copied in words rather than bytes by `atomic_load` and `atomic_store`.
+const fn copies_words<T>() -> bool {
+    mem::align_of::<T>() >= mem::align_of::<AtomicUsize>()
+        && mem::size_of::<T>().is_multiple_of(mem::size_of::<AtomicUsize>())
+}
+
+/// Copies a value out of a `SortSeqLock` with relaxed atomic loads.
+///
+/// The copy may be torn if a writer stores to the value at the same time, so it must only be
+/// assumed to be initialized once the sequence number shows that no writer did.
+///
+/// # Safety
+/// `src` must be valid for reads and only be written to with `atomic_store`. As `T` implements
+/// `NoUninit` every byte that is loaded is initialized.
+unsafe fn atomic_load<T: NoUninit>(src: *const T) -> MaybeUninit<T> {
+    let mut value = MaybeUninit::<T>::uninit();
+
+    if copies_words::<T>() {
+        let src = src.cast::<AtomicUsize>();
+        let dst = value.as_mut_ptr().cast::<usize>();
+
+        for i in 0..mem::size_of::<T>() / mem::size_of::<AtomicUsize>() {
+            // SAFETY: `T` is aligned and sized for whole words so each word is in bounds.
+            unsafe { dst.add(i).write((*src.add(i)).load(Ordering::Relaxed)) };
+        }
+    } else {
+        let src = src.cast::<AtomicU8>();
+        let dst = value.as_mut_ptr().cast::<u8>();
+
+        for i in 0..mem::size_of::<T>() {
+            // SAFETY: Each byte is within the value.
+            unsafe { dst.add(i).write((*src.add(i)).load(Ordering::Relaxed)) };
+        }
+    }
+
+    value
+}
+
+/// Copies a value into a `SortSeqLock` with relaxed atomic stores.
+///
+/// # Safety
+/// `dst` must be valid for writes and no other writer may store to it at the same time.
+unsafe fn atomic_store<T: NoUninit>(dst: *mut T, value: T) {
+    let value = MaybeUninit::new(value);
+
+    if copies_words::<T>() {
+        let src = value.as_ptr().cast::<usize>();
+        let dst = dst.cast::<AtomicUsize>();
+
+        for i in 0..mem::size_of::<T>() / mem::size_of::<AtomicUsize>() {
+            // SAFETY: `T` is aligned and sized for whole words so each word is in bounds.
+            unsafe { (*dst.add(i)).store(src.add(i).read(), Ordering::Relaxed) };
+        }
+    } else {
+        let src = value.as_ptr().cast::<u8>();
+        let dst = dst.cast::<AtomicU8>();
+
+        for i in 0..mem::size_of::<T>() {
+            // SAFETY: Each byte is within the value.
+            unsafe { (*dst.add(i)).store(src.add(i).read(), Ordering::Relaxed) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortSeqLock};
+
+    #[test]
+    fn test_read_write() {
+        let lock = SortSeqLock::new(1);
+
+        let mut guard = lock.write().lock_all();
+        guard.update(|value| *value += 1);
+        assert!(lock.try_write().is_none());
+        assert_eq!(None, lock.try_read());
+        drop(guard);
+
+        assert_eq!(2, lock.read());
+    }
+
+    #[test]
+    fn test_consistent_reads() {
+        let lock = Arc::new(SortSeqLock::new([0u64; 2]));
+        let writer_lock = lock.clone();
+
+        let writer = thread::spawn(move || {
+            for i in 1..=1000 {
+                writer_lock.write().lock_all().set([i, i]);
+            }
+        });
+
+        for _ in 0..1000 {
+            let [a, b] = lock.read();
+            assert_eq!(a, b);
+        }
+
+        writer.join().unwrap();
+        assert_eq!([1000, 1000], lock.read());
+    }
+
+    #[test]
+    fn test_consistent_unaligned_reads() {
+        let lock = Arc::new(SortSeqLock::new([0u8; 3]));
+        let writer_lock = lock.clone();
+
+        let writer = thread::spawn(move || {
+            for i in 1..=200 {
+                writer_lock.write().lock_all().set([i; 3]);
+            }
+        });
+
+        for _ in 0..1000 {
+            let [a, b, c] = lock.read();
+            assert!(a == b && b == c);
+        }
+
+        writer.join().unwrap();
+        assert_eq!([200; 3], lock.read());
+    }
+}
@@ -0,0 +1,329 @@
+use core::{cell::UnsafeCell, fmt::{self, Debug, Display, Formatter}, ops::{Deref, DerefMut}, sync::atomic::{AtomicUsize, Ordering}};
+
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{LockGroup, SortKey, SortableLock, key::LazySortKey};
+
+/// The number of reader shards in a `SortShardedLock`.
+const SHARDS: usize = 8;
+
+/// The next shard to assign to a thread.
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+std::thread_local! {
+    /// The shard used by the current thread for reading.
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/// A reader shard padded to avoid sharing a cache line with other shards.
+#[repr(align(128))]
+struct Shard(RwLock<()>);
+
+/// A sortable lock that allows either exclusive write access or shared read access, optimized
+/// for reading from many threads.
+///
+/// Readers only lock one of several internal shards so readers on different threads rarely
+/// contend on the same cache line. Writers lock every shard in a fixed order, making writing
+/// slower than with `SortRwLock`. Locking uses the same `read` and `write` requests as
+/// `SortRwLock` and can be sorted with other locks through the use of `lock_all`.
+/// ```
+/// use sortlock::{SortShardedLock, SortMutex, LockGroup};
+///
+/// let lock1 = SortShardedLock::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (guard1, mut guard2) = (lock1.read(), lock2.lock()).lock_all();
+/// *guard2 += *guard1;
+/// drop(guard1);
+///
+/// *lock1.write().lock_all() = 3;
+/// assert_eq!(3, *lock1.read().lock_all());
+/// ```
+pub struct SortShardedLock<T: ?Sized> {
+    /// The reader shards.
+    shards: [Shard; SHARDS],
+    /// The sort key for this lock.
+    key: LazySortKey,
+    /// The value protected by the lock.
+    data: UnsafeCell<T>,
+}
+
+unsafe impl <T: ?Sized + Send> Send for SortShardedLock<T> {}
+unsafe impl <T: ?Sized + Send + Sync> Sync for SortShardedLock<T> {}
+
+impl <T> SortShardedLock<T> {
+    /// Creates a new `SortShardedLock`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            shards: [const { Shard(RwLock::new(())) }; SHARDS],
+            key: LazySortKey::new(SortKey::new()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new `SortShardedLock` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            shards: [const { Shard(RwLock::new(())) }; SHARDS],
+            key: LazySortKey::unassigned(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortShardedLock<T> {
+    /// Requests to lock this lock for reading.
+    pub fn read(&self) -> SortShardedReadGuard<'_, T> {
+        SortShardedReadGuard {
+            lock: self
+        }
+    }
+
+    /// Requests to lock this lock for writing.
+    pub fn write(&self) -> SortShardedWriteGuard<'_, T> {
+        SortShardedWriteGuard {
+            lock: self
+        }
+    }
+
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Gets the sort key of this lock.
+    pub fn sort_key(&self) -> SortKey {
+        self.key.get()
+    }
+
+    /// Locks the shard of the current thread for reading.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    fn read_raw(&self) -> RwLockReadGuard<'_, ()> {
+        self.shards[SHARD.with(|shard| *shard)].0.read()
+            .expect("Failed to lock mutex.")
+    }
+
+    /// Locks every shard for writing in order.
+    ///
+    /// # Panicking
+    /// This method will panic if this lock is poisoned.
+    fn write_raw(&self) -> [RwLockWriteGuard<'_, ()>; SHARDS] {
+        core::array::from_fn(|index| self.shards[index].0.write()
+            .expect("Failed to lock mutex."))
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortShardedLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.read().lock_all()).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortShardedLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.read().lock_all()).fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortShardedLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortShardedLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A read guard for a `SortShardedLock`.
+///
+/// A group may only contain one request for each sharded lock, even if every request is for
+/// reading. A second read would lock the shard of the thread again, which can deadlock if a
+/// writer is waiting for the shard.
+pub struct SortShardedReadGuard<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortShardedLock<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortShardedReadGuard<'l, T> {
+    type Guard = SortShardedReadLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortShardedReadLocked {
+            _guard: self.lock.read_raw(),
+            lock: self.lock,
+        }
+    }
+}
+
+/// A write guard for a `SortShardedLock`.
+pub struct SortShardedWriteGuard<'l, T: ?Sized> {
+    /// The lock this guard references.
+    lock: &'l SortShardedLock<T>,
+}
+
+impl <'l, T: ?Sized> SortableLock for SortShardedWriteGuard<'l, T> {
+    type Guard = SortShardedWriteLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.lock.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        SortShardedWriteLocked {
+            _guards: self.lock.write_raw(),
+            lock: self.lock,
+        }
+    }
+}
+
+/// An acquired read lock on a `SortShardedLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortShardedReadLocked<'l, T: ?Sized> {
+    /// The guard for the shard of the current thread.
+    _guard: RwLockReadGuard<'l, ()>,
+    /// The lock this guard references.
+    lock: &'l SortShardedLock<T>,
+}
+
+impl <T: ?Sized> Deref for SortShardedReadLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: A shard is held for reading so no writers can exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortShardedReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortShardedReadLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// An acquired write lock on a `SortShardedLock`.
+///
+/// The lock is released when this guard is dropped.
+pub struct SortShardedWriteLocked<'l, T: ?Sized> {
+    /// The guards for every shard.
+    _guards: [RwLockWriteGuard<'l, ()>; SHARDS],
+    /// The lock this guard references.
+    lock: &'l SortShardedLock<T>,
+}
+
+unsafe impl <T: ?Sized + Sync> Sync for SortShardedWriteLocked<'_, T> {}
+
+impl <T: ?Sized> Deref for SortShardedWriteLocked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: Every shard is held for writing so no other references exist.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized> DerefMut for SortShardedWriteLocked<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: Every shard is held for writing so no other references exist.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortShardedWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortShardedWriteLocked<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortShardedLock};
+
+    #[test]
+    fn test_read_write() {
+        let lock = SortShardedLock::new(1);
+
+        assert_eq!(1, *lock.read().lock_all());
+
+        *lock.write().lock_all() += 1;
+        assert_eq!(2, *lock.read().lock_all());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_conflicting_requests() {
+        let lock = SortShardedLock::new(1);
+
+        let _ = (lock.read(), lock.write()).lock_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to lock group")]
+    fn test_repeated_reads() {
+        let lock = SortShardedLock::new(1);
+
+        let _ = (lock.read(), lock.read()).lock_all();
+    }
+
+    #[test]
+    fn test_sharded_threads() {
+        let lock = Arc::new(SortShardedLock::new(0));
+
+        let handles: Vec<_> = (0..8).map(|i| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    if i % 2 == 0 {
+                        *lock.write().lock_all() += 1;
+                    } else {
+                        assert!(*lock.read().lock_all() <= 400);
+                    }
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *lock.read().lock_all());
+    }
+}
@@ -0,0 +1,143 @@
+use core::{fmt::{self, Debug, Display, Formatter}, ops::Deref};
+
+use crate::{LockGroup, SortMutex, SortMutexGuard, SortMutexLocked, SpinBackend};
+
+/// A sortable lock that ensures exclusive access to a resource by spinning.
+///
+/// Waiting threads busy-wait instead of yielding to the scheduler, even when the `std` feature is
+/// enabled. This has the lowest overhead for very short critical sections, such as code that runs
+/// alongside interrupt handlers, but wastes processor time if the lock is held for long.
+/// ```
+/// use sortlock::{SortSpinMutex, SortMutex, LockGroup};
+///
+/// let lock1 = SortSpinMutex::new(1);
+/// let lock2 = SortMutex::new(2);
+///
+/// let (mut guard1, guard2) = (lock1.lock(), lock2.lock()).lock_all();
+/// *guard1 += *guard2;
+/// assert_eq!(3, *guard1);
+/// ```
+///
+/// This is a `SortMutex` using a `SpinBackend`, which it derefs to. Unlike `SortMutex` this lock
+/// is never poisoned.
+pub struct SortSpinMutex<T: ?Sized> {
+    /// The internal lock.
+    mutex: SortMutex<T, SpinBackend>,
+}
+
+impl <T> SortSpinMutex<T> {
+    /// Creates a new `SortSpinMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub fn new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::with_backend(value, SpinBackend::new()),
+        }
+    }
+
+    /// Creates a new `SortSpinMutex` in a `const` context.
+    ///
+    /// The sort key is assigned when it is first used.
+    ///
+    /// - `value` - The value of the lock.
+    #[track_caller]
+    pub const fn const_new(value: T) -> Self {
+        Self {
+            mutex: SortMutex::const_with_backend(value, SpinBackend::new()),
+        }
+    }
+
+    /// Consumes this lock and returns the value.
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl <T: ?Sized> SortSpinMutex<T> {
+    /// Gets a mutable reference to the value.
+    ///
+    /// As this requires mutable access to the lock no locking is required.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+}
+
+impl <T: ?Sized> Deref for SortSpinMutex<T> {
+    type Target = SortMutex<T, SpinBackend>;
+
+    fn deref(&self) -> &SortMutex<T, SpinBackend> {
+        &self.mutex
+    }
+}
+
+impl <T: ?Sized + Debug> Debug for SortSpinMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: ?Sized + Display> Display for SortSpinMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (*self.lock().lock_all()).fmt(f)
+    }
+}
+
+impl <T: Default> Default for SortSpinMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl <T> From<T> for SortSpinMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A guard for a `SortSpinMutex`.
+pub type SortSpinMutexGuard<'l, T> = SortMutexGuard<'l, T, SpinBackend>;
+
+/// An acquired lock on a `SortSpinMutex`.
+///
+/// The lock is released when this guard is dropped.
+pub type SortSpinMutexLocked<'l, T> = SortMutexLocked<'l, T, SpinBackend>;
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortSpinMutex};
+
+    #[test]
+    fn test_try_lock() {
+        let lock = SortSpinMutex::new(1);
+
+        let guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(1, *lock.try_lock().unwrap());
+    }
+
+    #[test]
+    fn test_spin_threads() {
+        let lock = Arc::new(SortSpinMutex::new(0));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    *lock.lock().lock_all() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(400, *lock.lock().lock_all());
+    }
+}
@@ -0,0 +1,237 @@
+use core::{fmt::{self, Debug, Formatter}, ops::Deref};
+
+use crate::{NestedLockGroup, SortKey, SortMutex, SortMutexGuard, SortMutexLocked, SortableLock};
+
+/// A lock request whose sort key is known at compile time.
+///
+/// The sort key of the request is the manual key with the value `KEY`, the same key as
+/// `SortKey::from_value(KEY)` would create.
+pub trait StaticSortableLock: SortableLock {
+    /// The value of the sort key.
+    const KEY: u64;
+}
+
+/// A `SortMutex` with a sort key that is part of its type.
+///
+/// As the key is known at compile time, a group made only of requests for statically keyed locks
+/// can be checked for duplicate keys and sorted while compiling with `StaticLockGroup`, so no
+/// sorting is done when the group is locked. The lock derefs to a `SortMutex` so it can also be
+/// used wherever a `SortMutex` can.
+/// ```
+/// use sortlock::{StaticSortMutex, StaticLockGroup};
+///
+/// static ACCOUNTS: StaticSortMutex<u32, 1> = StaticSortMutex::new(10);
+/// static LEDGER: StaticSortMutex<u32, 2> = StaticSortMutex::new(0);
+///
+/// // ACCOUNTS is locked first as its key is smaller.
+/// let (mut ledger, mut accounts) = (LEDGER.lock(), ACCOUNTS.lock()).lock_all_static();
+/// *accounts -= 5;
+/// *ledger += 5;
+/// ```
+///
+/// The key must be less than `2^61`, which is checked at compile time. Each key must only be used
+/// for a single lock.
+pub struct StaticSortMutex<T: ?Sized, const KEY: u64> {
+    /// The internal lock.
+    mutex: SortMutex<T>,
+}
+
+impl <T, const KEY: u64> StaticSortMutex<T, KEY> {
+    /// Checks that the key can be used as a manual key.
+    const VALID_KEY: () = assert!(KEY < SortKey::MANUAL_BIT, "Failed to create lock: static keys must be less than 2^61.");
+
+    /// Creates a new `StaticSortMutex`.
+    ///
+    /// - `value` - The value of the lock.
+    pub const fn new(value: T) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_KEY;
+
+        Self {
+            mutex: SortMutex::const_with_key(value, SortKey::manual(KEY)),
+        }
+    }
+}
+
+impl <T: ?Sized, const KEY: u64> StaticSortMutex<T, KEY> {
+    /// Requests to lock this lock.
+    /// This method returns a guard which can be used with `lock_all` or `lock_all_static`.
+    pub fn lock(&self) -> StaticSortMutexGuard<'_, T, KEY> {
+        StaticSortMutexGuard {
+            guard: self.mutex.lock()
+        }
+    }
+}
+
+impl <T: ?Sized, const KEY: u64> Deref for StaticSortMutex<T, KEY> {
+    type Target = SortMutex<T>;
+
+    fn deref(&self) -> &SortMutex<T> {
+        &self.mutex
+    }
+}
+
+impl <T: ?Sized + Debug, const KEY: u64> Debug for StaticSortMutex<T, KEY> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.mutex.fmt(f)
+    }
+}
+
+impl <T: Default, const KEY: u64> Default for StaticSortMutex<T, KEY> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// A guard for a `StaticSortMutex`.
+pub struct StaticSortMutexGuard<'l, T: ?Sized, const KEY: u64> {
+    /// The request for the internal lock.
+    guard: SortMutexGuard<'l, T>,
+}
+
+impl <'l, T: ?Sized, const KEY: u64> SortableLock for StaticSortMutexGuard<'l, T, KEY> {
+    type Guard = SortMutexLocked<'l, T>;
+
+    fn sort_key(&self) -> SortKey {
+        self.guard.sort_key()
+    }
+
+    fn lock_presorted(&self) -> Self::Guard {
+        self.guard.lock_presorted()
+    }
+}
+
+impl <T: ?Sized, const KEY: u64> StaticSortableLock for StaticSortMutexGuard<'_, T, KEY> {
+    const KEY: u64 = KEY;
+}
+
+/// A group of statically keyed requests that is sorted at compile time.
+///
+/// The order the group is locked in is computed while compiling, so locking the group does no
+/// sorting at all. A group containing two requests with the same key fails to compile.
+/// ```compile_fail
+/// use sortlock::{StaticSortMutex, StaticLockGroup};
+///
+/// static LOCK1: StaticSortMutex<u32, 1> = StaticSortMutex::new(1);
+/// static LOCK2: StaticSortMutex<u32, 1> = StaticSortMutex::new(2);
+///
+/// let _guards = (LOCK1.lock(), LOCK2.lock()).lock_all_static();
+/// ```
+pub trait StaticLockGroup: NestedLockGroup {
+    /// The flattened index of each lock in the group in the order they are locked.
+    const ORDER: &'static [usize];
+
+    /// Lock all items in the group in the order computed at compile time.
+    ///
+    /// This locks in the same order as `lock_all`.
+    fn lock_all_static(self) -> Self::Locked where Self: Sized {
+        let mut partial = Self::unlocked_partial();
+
+        for i in Self::ORDER {
+            self.lock_member(*i, &mut partial);
+        }
+
+        Self::complete_partial(partial)
+    }
+}
+
+/// Computes the order to lock a group of statically keyed locks in.
+///
+/// - `keys` - The key value of each lock in the group.
+///
+/// # Panicking
+/// This function will panic if two locks have the same key. It is evaluated at compile time so
+/// this is a compile error.
+const fn static_order<const N: usize>(keys: [u64; N]) -> [usize; N] {
+    let mut order = [0; N];
+    let mut i = 0;
+
+    while i < N {
+        order[i] = i;
+        i += 1;
+    }
+
+    // An insertion sort, as sorting slices is not available in a `const` context.
+    let mut i = 1;
+
+    while i < N {
+        let mut j = i;
+
+        while j > 0 && keys[order[j - 1]] >= keys[order[j]] {
+            if keys[order[j - 1]] == keys[order[j]] {
+                panic!("Failed to lock static group: the group contains two locks with the same key.");
+            }
+
+            let swap = order[j - 1];
+            order[j - 1] = order[j];
+            order[j] = swap;
+            j -= 1;
+        }
+
+        i += 1;
+    }
+
+    order
+}
+
+/// Implements `StaticLockGroup` for tuples of `StaticSortableLock`s.
+macro_rules! tuple_static_lock_group {
+    ($(($($t:ident),+);)+) => {
+        $(
+            impl <$($t: StaticSortableLock),+> StaticLockGroup for ($($t,)+) {
+                const ORDER: &'static [usize] = &static_order([$($t::KEY),+]);
+            }
+        )+
+    };
+}
+
+tuple_static_lock_group! {
+    (T1, T2);
+    (T1, T2, T3);
+    (T1, T2, T3, T4);
+    (T1, T2, T3, T4, T5);
+    (T1, T2, T3, T4, T5, T6);
+    (T1, T2, T3, T4, T5, T6, T7);
+    (T1, T2, T3, T4, T5, T6, T7, T8);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15);
+    (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LockGroup, NestedLockGroup, SortMutex, StaticLockGroup, StaticSortMutex};
+
+    /// Gets the order a group is locked in by `lock_all_static`.
+    fn static_order<G: StaticLockGroup>(_group: &G) -> &'static [usize] {
+        G::ORDER
+    }
+
+    #[test]
+    fn test_static_order() {
+        let lock3 = StaticSortMutex::<_, 30>::new(3);
+        let lock1 = StaticSortMutex::<_, 10>::new(1);
+        let lock2 = StaticSortMutex::<_, 20>::new(2);
+
+        let group = (lock2.lock(), lock3.lock(), lock1.lock());
+        assert_eq!([2, 0, 1], static_order(&group));
+        assert_eq!(
+            group.acquisition_order().as_ref().iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            static_order(&group),
+        );
+
+        let (guard2, guard3, guard1) = group.lock_all_static();
+        assert_eq!(6, *guard1 + *guard2 + *guard3);
+        drop((guard1, guard2, guard3));
+
+        // Statically keyed locks can be locked with other locks.
+        let lock4 = SortMutex::new(4);
+        let (guard4, guard1) = (lock4.lock(), lock1.lock()).lock_all();
+        assert_eq!(5, *guard1 + *guard4);
+    }
+}
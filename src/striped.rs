@@ -0,0 +1,226 @@
+use core::{fmt::{self, Debug, Formatter}, hash::{BuildHasher, Hash}};
+
+use std::collections::hash_map::RandomState;
+
+use alloc::vec::Vec;
+
+use crate::{LockGroup, SortMutex, SortMutexGuard, SortMutexLocked};
+
+/// A fixed set of locks where each key is protected by the lock selected by its hash.
+///
+/// This protects many items with a bounded number of locks, such as the shards of a cache,
+/// without allocating a lock for each item. Keys with the same hash share a stripe, so locking one
+/// key may block another. Several keys can be locked at once with `lock_many`, which locks each
+/// stripe they need once in sorted order.
+/// ```
+/// use std::collections::HashMap;
+/// use sortlock::{SortStripedLock, LockGroup};
+///
+/// let cache: SortStripedLock<HashMap<&str, u32>, 4> = SortStripedLock::new();
+///
+/// cache.lock(&"a").lock_all().insert("a", 1);
+///
+/// let mut guards = cache.lock_many([&"a", &"b"]);
+/// let a = guards.get(&"a").unwrap()["a"];
+/// guards.get_mut(&"b").unwrap().insert("b", a + 1);
+/// ```
+pub struct SortStripedLock<T, const N: usize, S = RandomState> {
+    /// The locks for each stripe.
+    stripes: [SortMutex<T>; N],
+    /// The hasher used to select stripes.
+    hasher: S,
+}
+
+impl <T: Default, const N: usize> SortStripedLock<T, N> {
+    /// Creates a new `SortStripedLock` where every stripe holds the default value.
+    pub fn new() -> Self {
+        Self::from_fn_with_hasher(|_| T::default(), RandomState::new())
+    }
+}
+
+impl <T, const N: usize> SortStripedLock<T, N> {
+    /// Creates a new `SortStripedLock` with a value for each stripe.
+    ///
+    /// - `f` - The closure that creates the value for the stripe at each index.
+    pub fn from_fn<F: FnMut(usize) -> T>(f: F) -> Self {
+        Self::from_fn_with_hasher(f, RandomState::new())
+    }
+}
+
+impl <T, const N: usize, S> SortStripedLock<T, N, S> {
+    /// Creates a new `SortStripedLock` with a value for each stripe using a hasher.
+    ///
+    /// - `f` - The closure that creates the value for the stripe at each index.
+    /// - `hasher` - The hasher used to select stripes.
+    ///
+    /// # Panicking
+    /// This function will panic if `N` is zero.
+    pub fn from_fn_with_hasher<F: FnMut(usize) -> T>(mut f: F, hasher: S) -> Self {
+        assert!(N > 0, "Failed to create striped lock: there must be at least one stripe.");
+
+        Self {
+            stripes: core::array::from_fn(|index| SortMutex::new(f(index))),
+            hasher,
+        }
+    }
+
+    /// Gets the locks for each stripe.
+    pub fn stripes(&self) -> &[SortMutex<T>; N] {
+        &self.stripes
+    }
+
+    /// Requests to lock every stripe.
+    pub fn lock_all_stripes(&self) -> [SortMutexGuard<'_, T>; N] {
+        core::array::from_fn(|index| self.stripes[index].lock())
+    }
+}
+
+impl <T, const N: usize, S: BuildHasher> SortStripedLock<T, N, S> {
+    /// Gets the index of the stripe protecting a key.
+    ///
+    /// - `key` - The key to find the stripe of.
+    pub fn stripe_index<Q: ?Sized + Hash>(&self, key: &Q) -> usize {
+        (self.hasher.hash_one(key) % N as u64) as usize
+    }
+
+    /// Gets the lock of the stripe protecting a key.
+    ///
+    /// - `key` - The key to find the stripe of.
+    pub fn stripe<Q: ?Sized + Hash>(&self, key: &Q) -> &SortMutex<T> {
+        &self.stripes[self.stripe_index(key)]
+    }
+
+    /// Requests to lock the stripe protecting a key.
+    ///
+    /// - `key` - The key to lock the stripe of.
+    pub fn lock<Q: ?Sized + Hash>(&self, key: &Q) -> SortMutexGuard<'_, T> {
+        self.stripe(key).lock()
+    }
+
+    /// Locks the stripes protecting several keys in sorted order.
+    ///
+    /// Each stripe is only locked once, even if several keys share it.
+    ///
+    /// - `keys` - The keys to lock the stripes of.
+    pub fn lock_many<'q, Q: ?Sized + Hash + 'q, I: IntoIterator<Item = &'q Q>>(&self, keys: I) -> SortStripedLocked<'_, T, N, S> {
+        let mut indices: Vec<_> = keys.into_iter()
+            .map(|key| self.stripe_index(key))
+            .collect();
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        let requests: Vec<_> = indices.iter()
+            .map(|index| self.stripes[*index].lock())
+            .collect();
+
+        let mut guards: [_; N] = core::array::from_fn(|_| None);
+        for (index, guard) in indices.into_iter().zip(requests.lock_all()) {
+            guards[index] = Some(guard);
+        }
+
+        SortStripedLocked {
+            lock: self,
+            guards,
+        }
+    }
+}
+
+impl <T: Default, const N: usize> Default for SortStripedLock<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: Debug, const N: usize, S> Debug for SortStripedLock<T, N, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SortStripedLock")
+            .field("stripes", &self.stripes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The locked stripes of a `SortStripedLock`.
+///
+/// This is created with `lock_many`. The stripes are unlocked when this is dropped.
+pub struct SortStripedLocked<'l, T, const N: usize, S = RandomState> {
+    /// The striped lock the stripes were locked from.
+    lock: &'l SortStripedLock<T, N, S>,
+    /// The guard for each locked stripe by index.
+    guards: [Option<SortMutexLocked<'l, T>>; N],
+}
+
+impl <T, const N: usize, S: BuildHasher> SortStripedLocked<'_, T, N, S> {
+    /// Gets the value of the stripe protecting a key.
+    ///
+    /// Returns `None` if the stripe was not locked.
+    ///
+    /// - `key` - The key to get the stripe of.
+    pub fn get<Q: ?Sized + Hash>(&self, key: &Q) -> Option<&T> {
+        self.guards[self.lock.stripe_index(key)].as_deref()
+    }
+
+    /// Gets the value of the stripe protecting a key mutably.
+    ///
+    /// Returns `None` if the stripe was not locked.
+    ///
+    /// - `key` - The key to get the stripe of.
+    pub fn get_mut<Q: ?Sized + Hash>(&mut self, key: &Q) -> Option<&mut T> {
+        self.guards[self.lock.stripe_index(key)].as_deref_mut()
+    }
+}
+
+impl <T: Debug, const N: usize, S> Debug for SortStripedLocked<'_, T, N, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.guards.iter().flatten())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{LockGroup, SortStripedLock};
+
+    #[test]
+    fn test_lock_many() {
+        let lock: SortStripedLock<u32, 4> = SortStripedLock::new();
+
+        let keys = [1, 2, 3, 4, 5, 1];
+        let mut guards = lock.lock_many(&keys);
+        for key in &keys {
+            *guards.get_mut(key).unwrap() += 1;
+        }
+        drop(guards);
+
+        let total: u32 = lock.lock_all_stripes().lock_all().iter().map(|guard| **guard).sum();
+        assert_eq!(6, total);
+        assert!(*lock.lock(&1).lock_all() >= 2);
+    }
+
+    #[test]
+    fn test_striped_threads() {
+        let lock: Arc<SortStripedLock<u32, 8>> = Arc::new(SortStripedLock::new());
+
+        let handles: Vec<_> = (0..4).map(|i| {
+            let lock = lock.clone();
+
+            thread::spawn(move || {
+                for j in 0..100 {
+                    let keys = [i * 100 + j, j, 1000 - j];
+                    let mut guards = lock.lock_many(&keys);
+                    *guards.get_mut(&keys[0]).unwrap() += 1;
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total: u32 = lock.lock_all_stripes().lock_all().iter().map(|guard| **guard).sum();
+        assert_eq!(400, total);
+    }
+}